@@ -0,0 +1,106 @@
+/*
+ * History of the active storage providers per region: a snapshot of the counts is recorded, keyed by the block
+ * time in seconds, whenever a provider write changes them. Snapshots expire with the price retention
+ */
+
+use crate::*;
+
+// expired snapshots pruned by every recorded snapshot
+const AUTO_PRUNE_SNAPSHOTS: usize = 2;
+
+impl FilMarket {
+    // record the current counts unless they match the latest snapshot
+    pub(crate) fn record_active_history(&mut self) {
+        let latest = self.active_history.max().and_then(|timestamp| self.active_history.get(&timestamp));
+        if latest.as_ref() == Some(&self.active_per_region) {
+            return;
+        }
+
+        if let Some(cutoff_ts) = self.retention_cutoff() {
+            let expired: Vec<u64> = self.active_history
+                .iter()
+                .map(|(timestamp, _)| timestamp)
+                .take_while(|timestamp| *timestamp < cutoff_ts)
+                .take(AUTO_PRUNE_SNAPSHOTS)
+                .collect();
+            for timestamp in expired.iter() {
+                self.active_history.remove(timestamp);
+            }
+        }
+
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        self.active_history.insert(&now, &self.active_per_region);
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get up to limit snapshots with from_timestamp <= timestamp <= to_timestamp, in ascending order
+    pub fn get_active_history(&self, from_timestamp: u64, to_timestamp: u64, limit: u64) -> Vec<(u64, ActivePerRegion)> {
+        require!(from_timestamp <= to_timestamp, "from_timestamp must not be greater than to_timestamp");
+
+        self.active_history
+            .range((Bound::Included(from_timestamp), Bound::Included(to_timestamp)))
+            .take(view_limit(limit))
+            .collect()
+    }
+
+    // get the n latest snapshots, in ascending order
+    pub fn get_latest_n_active(&self, n: u64) -> Vec<(u64, ActivePerRegion)> {
+        let mut snapshots: Vec<(u64, ActivePerRegion)> = self.active_history.iter_rev().take(view_limit(n)).collect();
+
+        snapshots.reverse();
+        snapshots
+    }
+
+    // get the counts as of target_ts, from the snapshot with the greatest timestamp <= target_ts
+    pub fn get_active_nearest(&self, target_ts: u64) -> Option<(u64, ActivePerRegion)> {
+        let timestamp = self.active_history.floor_key(&target_ts)?;
+        self.active_history.get(&timestamp).map(|active| (timestamp, active))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn provider(id: &str, region: u8) -> StorageProvider {
+        StorageProvider { id: id.to_string(), region, power: gib(10.0), price: fil(0.1), ..Default::default() }
+    }
+
+    #[test]
+    fn active_history_follows_provider_writes() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        contract.update_storage_providers(vec![provider("f01", REGION_EUROPE), provider("f02", REGION_ASIA)], None);
+
+        // an update leaving the counts unchanged records nothing
+        set_context("carol_near", NEAR, 200);
+        contract.update_storage_providers(vec![provider("f01", REGION_EUROPE)], None);
+
+        set_context("carol_near", NEAR, 300);
+        contract.delete_storage_providers(vec!["f02".to_string()]);
+
+        let history = contract.get_active_history(0, u64::MAX, 10);
+        assert_eq!(vec![100, 300], history.iter().map(|(timestamp, _)| *timestamp).collect::<Vec<u64>>());
+        assert_eq!(1, history[0].1.get(REGION_ASIA));
+        assert_eq!(0, history[1].1.get(REGION_ASIA));
+
+        assert_eq!(100, contract.get_active_nearest(299).unwrap().0);
+        assert!(contract.get_active_nearest(99).is_none());
+        assert_eq!(300, contract.get_latest_n_active(1)[0].0);
+    }
+
+    #[test]
+    fn active_history_expires_with_price_retention() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        contract.set_price_retention(1_000);
+        contract.update_storage_providers(vec![provider("f01", REGION_EUROPE)], None);
+
+        set_context("carol_near", NEAR, 2_000);
+        contract.update_storage_providers(vec![provider("f02", REGION_EUROPE)], None);
+        assert_eq!(vec![2_000], contract.get_active_history(0, u64::MAX, 10).into_iter().map(|(timestamp, _)| timestamp).collect::<Vec<u64>>());
+    }
+}
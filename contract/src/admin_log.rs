@@ -0,0 +1,183 @@
+/*
+ * Log of privileged actions for audits: every event emitted by a call that passed an owner, role, configuration
+ * authority or council check is recorded with the caller, the time and the number of items it touched. The log
+ * keeps the latest MAX_ADMIN_LOG_ENTRIES entries, older ones are overwritten
+ */
+
+use crate::*;
+use near_sdk::serde_json::Value;
+
+pub const MAX_ADMIN_LOG_ENTRIES: u64 = 500;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AdminLogEntry {
+    pub index: u64, // position in the log, counting the overwritten entries
+    pub account_id: AccountId,
+    pub action: String, // the event name
+    pub items: u64,     // the entries of the event's lists, 1 for an action without a list
+    pub seq: u64,       // sequence number of the event
+    pub timestamp: u64, // seconds
+    pub block_height: BlockHeight,
+}
+
+// count the entries of the top-level lists of an event, at least 1
+fn count_items(data: &Value) -> u64 {
+    let items: usize = data
+        .as_object()
+        .map(|fields| fields.values().filter_map(|value| value.as_array()).map(|list| list.len()).sum())
+        .unwrap_or(0);
+
+    (items as u64).max(1)
+}
+
+impl FilMarket {
+    // note that the caller passed a privileged check, so the events of the call are logged
+    pub(crate) fn mark_privileged(&self, account_id: AccountId) {
+        self.privileged_caller.set(Some(account_id));
+    }
+
+    // record an event in the admin log when the caller passed a privileged check
+    pub(crate) fn record_admin_action(&mut self, action: &str, data: &Value) {
+        let account_id = env::predecessor_account_id();
+        if self.privileged_caller.get_mut().as_ref() != Some(&account_id) {
+            return;
+        }
+
+        let entry = AdminLogEntry {
+            index: self.admin_log_len,
+            account_id,
+            action: action.to_string(),
+            items: count_items(data),
+            seq: self.event_seq,
+            timestamp: env::block_timestamp() / NANOSECONDS_PER_SECOND,
+            block_height: env::block_height(),
+        };
+
+        let initial_storage_usage = env::storage_usage();
+        if self.admin_log.len() < MAX_ADMIN_LOG_ENTRIES {
+            self.admin_log.push(&entry);
+        } else {
+            self.admin_log.replace(self.admin_log_len % MAX_ADMIN_LOG_ENTRIES, &entry);
+        }
+        self.admin_log_len += 1;
+        self.admin_log_storage += env::storage_usage().saturating_sub(initial_storage_usage);
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the admin log entries from index on, the oldest entries kept if from_index was overwritten
+    pub fn get_admin_log(&self, from_index: u64, limit: u64) -> Vec<AdminLogEntry> {
+        let oldest = self.admin_log_len.saturating_sub(self.admin_log.len());
+
+        (from_index.max(oldest)..self.admin_log_len)
+            .take(view_limit(limit))
+            .filter_map(|index| self.admin_log.get(index % MAX_ADMIN_LOG_ENTRIES))
+            .collect()
+    }
+
+    // get the number of privileged actions recorded, including the overwritten ones
+    pub fn get_admin_log_len(&self) -> u64 {
+        self.admin_log_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    #[test]
+    fn admin_actions_are_logged() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+
+        contract.set_min_price(fil(0.01));
+        contract.add_role(account("bob_near"), Role::PriceFeeder);
+        contract.add_role(account("dave_near"), Role::SpManager);
+
+        let log = contract.get_admin_log(0, 10);
+        assert_eq!(3, log.len());
+        assert_eq!("min_price_set", log[0].action);
+        assert_eq!("carol_near", log[0].account_id.as_str());
+        assert_eq!(1_000, log[0].timestamp);
+        assert_eq!("role_added", log[1].action);
+        assert_eq!(1, log[1].items);
+        assert_eq!(contract.get_event_seq(), log[2].seq);
+        assert_eq!(vec![2], contract.get_admin_log(2, 10).into_iter().map(|entry| entry.index).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn role_gated_actions_are_logged() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("bob_near"), Role::PriceFeeder);
+        contract.add_role(account("dave_near"), Role::SpManager);
+        let logged = contract.get_admin_log_len();
+
+        set_context("dave_near", NEAR, 1_000);
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 1, power: gib(10.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.delete_storage_providers(vec!["f02".to_string()]);
+
+        set_context("bob_near", NEAR, 1_000);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 10, ..Default::default() });
+
+        let log = contract.get_admin_log(logged, 10);
+        let actions: Vec<(&str, &str, u64)> = log.iter().map(|entry| (entry.account_id.as_str(), entry.action.as_str(), entry.items)).collect();
+        assert_eq!(
+            vec![
+                ("dave_near", "storage_providers_updated", 2),
+                ("dave_near", "storage_providers_deleted", 1),
+                ("bob_near", "price_per_region_set", 1),
+            ],
+            actions
+        );
+    }
+
+    #[test]
+    fn role_holders_are_logged_for_role_gated_calls_only() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("dave_near"), Role::SpManager);
+        let logged = contract.get_admin_log_len();
+
+        // a role holder is logged for the role-gated calls only
+        set_context("dave_near", NEAR, 1_000);
+        contract.post_storage_request(gib(1.0), 100, 1, fil(0.1));
+        assert_eq!(logged, contract.get_admin_log_len());
+    }
+
+    #[test]
+    fn unprivileged_actions_are_not_logged() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+
+        set_context("client_near", NEAR, 1_000);
+        contract.post_storage_request(gib(1.0), 100, 1, fil(0.1));
+        assert_eq!(0, contract.get_admin_log_len());
+    }
+
+    #[test]
+    fn admin_log_is_bounded() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+
+        for skew in 0..MAX_ADMIN_LOG_ENTRIES + 5 {
+            if skew % 100 == 0 {
+                set_context("carol_near", NEAR, 1_000);
+            }
+            contract.set_max_future_skew(skew);
+        }
+
+        assert_eq!(MAX_ADMIN_LOG_ENTRIES + 5, contract.get_admin_log_len());
+        let log = contract.get_admin_log(0, 2);
+        assert_eq!(5, log[0].index);
+        assert_eq!(6, log[1].index);
+        assert_eq!(MAX_ADMIN_LOG_ENTRIES + 4, contract.get_admin_log(MAX_ADMIN_LOG_ENTRIES + 4, 2)[0].index);
+    }
+}
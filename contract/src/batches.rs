@@ -0,0 +1,180 @@
+/*
+ * Chunked storage provider syncs: a large update is split by the feeder into numbered chunks of at most
+ * MAX_BATCH_ITEMS providers, each applied in its own call, with the progress of the batch tracked on-chain
+ */
+
+use crate::*;
+
+// storage providers accepted per chunk, bounded by the gas of indexing new providers
+pub const MAX_BATCH_ITEMS: usize = 10;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchProgress {
+    batch_id: String,
+    total_chunks: u32,
+    next_chunk: u32,     // index of the next chunk expected
+    processed: u32,      // providers processed by the latest call, 0 for a chunk already applied
+    total_updated: u64,  // providers inserted or updated over the batch
+    total_skipped: u64,  // providers rejected by the price floor over the batch
+    complete: bool,
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // apply chunk chunk_index of total_chunks of a batch, chunks must be sent in order starting at 0
+    // resending an applied chunk is a no-op, so a feeder can retry safely; a completed batch is forgotten
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn update_storage_providers_chunk(
+        &mut self,
+        batch_id: String,
+        chunk_index: u32,
+        total_chunks: u32,
+        storage_providers: Vec<StorageProvider>,
+    ) -> BatchProgress {
+        self.assert_role(Role::SpManager);
+        require!(batch_id.len() <= MAX_ID_LEN, format!("batch id is longer than {} bytes", MAX_ID_LEN));
+        require!(total_chunks > 0, "total_chunks must be greater than 0");
+        require!(
+            storage_providers.len() <= MAX_BATCH_ITEMS,
+            format!("a chunk holds at most {} storage providers", MAX_BATCH_ITEMS)
+        );
+
+        let mut progress = self.batches.get(&batch_id).unwrap_or(BatchProgress {
+            batch_id: batch_id.clone(),
+            total_chunks,
+            next_chunk: 0,
+            processed: 0,
+            total_updated: 0,
+            total_skipped: 0,
+            complete: false,
+        });
+        require!(
+            progress.total_chunks == total_chunks,
+            format!("batch {} has {} chunks, not {}", batch_id, progress.total_chunks, total_chunks)
+        );
+        if chunk_index < progress.next_chunk {
+            progress.processed = 0;
+            return progress;
+        }
+        require!(
+            chunk_index == progress.next_chunk,
+            format!("batch {} expects chunk {}, not {}", batch_id, progress.next_chunk, chunk_index)
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let result = self.internal_update_storage_providers(storage_providers, false);
+
+        progress.processed = result.added + result.updated + result.skipped;
+        progress.total_updated += (result.added + result.updated) as u64;
+        progress.total_skipped += result.skipped as u64;
+        progress.next_chunk += 1;
+        progress.complete = progress.next_chunk == total_chunks;
+        if progress.complete {
+            self.batches.remove(&batch_id);
+            self.emit_event("batch_completed", json!({
+                "batch_id": batch_id,
+                "total_updated": progress.total_updated,
+                "total_skipped": progress.total_skipped,
+            }));
+        } else {
+            self.batches.insert(&batch_id, &progress);
+        }
+        self.refund_deposit(initial_storage_usage);
+
+        progress
+    }
+
+    // drop the progress of an unfinished batch, so its id can start over, the applied chunks stay applied
+    pub fn abandon_batch(&mut self, batch_id: String) {
+        self.assert_role(Role::SpManager);
+
+        if self.batches.remove(&batch_id).is_some() {
+            self.emit_event("batch_abandoned", json!({ "batch_id": batch_id }));
+        }
+    }
+
+    // get the progress of an unfinished batch
+    pub fn get_batch(&self, batch_id: String) -> Option<BatchProgress> {
+        self.batches.get(&batch_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn chunk(from: usize, to: usize) -> Vec<StorageProvider> {
+        (from..to).map(|i| StorageProvider {
+            id: format!("f0{}", i),
+            region: REGION_EUROPE,
+            power: gib(10.0),
+            price: fil(0.1),
+            ..Default::default()
+        }).collect()
+    }
+
+    #[test]
+    fn update_storage_providers_in_chunks() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let batch_id = "sync-1".to_string();
+
+        let progress = contract.update_storage_providers_chunk(batch_id.clone(), 0, 3, chunk(0, 10));
+        assert_eq!(10, progress.processed);
+        assert_eq!(1, progress.next_chunk);
+        assert!(!progress.complete);
+        assert_eq!(Some(progress), contract.get_batch(batch_id.clone()));
+
+        // a retried chunk is not applied twice
+        set_context("carol_near", NEAR, 0);
+        let progress = contract.update_storage_providers_chunk(batch_id.clone(), 0, 3, chunk(0, 10));
+        assert_eq!(0, progress.processed);
+        assert_eq!(10, progress.total_updated);
+
+        set_context("carol_near", NEAR, 0);
+        contract.update_storage_providers_chunk(batch_id.clone(), 1, 3, chunk(10, 20));
+        set_context("carol_near", NEAR, 0);
+        let progress = contract.update_storage_providers_chunk(batch_id.clone(), 2, 3, chunk(20, 25));
+        assert_eq!(5, progress.processed);
+        assert_eq!(25, progress.total_updated);
+        assert!(progress.complete);
+        assert_eq!(None, contract.get_batch(batch_id));
+        assert_eq!(25, contract.get_provider_count());
+    }
+
+    #[test]
+    #[should_panic(expected = "batch sync-1 expects chunk 1, not 2")]
+    fn update_storage_providers_chunk_out_of_order() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.update_storage_providers_chunk("sync-1".to_string(), 0, 3, chunk(0, 1));
+        contract.update_storage_providers_chunk("sync-1".to_string(), 2, 3, chunk(1, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "a chunk holds at most 10 storage providers")]
+    fn update_storage_providers_chunk_too_large() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.update_storage_providers_chunk("sync-1".to_string(), 0, 1, chunk(0, MAX_BATCH_ITEMS + 1));
+    }
+
+    #[test]
+    fn abandon_batch() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.update_storage_providers_chunk("sync-1".to_string(), 0, 2, chunk(0, 1));
+        contract.abandon_batch("sync-1".to_string());
+        assert_eq!(None, contract.get_batch("sync-1".to_string()));
+
+        // the id starts over, with another number of chunks
+        let progress = contract.update_storage_providers_chunk("sync-1".to_string(), 0, 1, chunk(0, 2));
+        assert!(progress.complete);
+    }
+}
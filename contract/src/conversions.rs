@@ -0,0 +1,172 @@
+/*
+ * Conversion of the latest region and global prices, stored in attoFIL/GiB/epoch, to the units and currencies
+ * shown by frontends, using the FIL price of the same entry, and of power, stored in bytes, to GiB, TiB or PiB
+ */
+
+use crate::*;
+
+const DAYS_PER_MONTH: u128 = 30;
+const DAYS_PER_YEAR: u128 = 365;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PriceUnit {
+    GibPerEpoch,
+    TibPerDay,
+    TibPerMonth, // 30 days
+    GibPerYear,  // 365 days
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Currency {
+    Fil,
+    Usd,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PowerUnit {
+    Bytes,
+    Gib,
+    Tib,
+    Pib,
+}
+
+impl PowerUnit {
+    fn bytes(self) -> u128 {
+        match self {
+            PowerUnit::Bytes => 1,
+            PowerUnit::Gib => BYTES_PER_GIB,
+            PowerUnit::Tib => BYTES_PER_TIB,
+            PowerUnit::Pib => BYTES_PER_TIB * 1_024,
+        }
+    }
+}
+
+// convert bytes to the given unit, scaled by 10^18
+fn convert_power(bytes: u128, unit: PowerUnit) -> u128 {
+    bytes.checked_mul(10u128.pow(decimal::PRICE_DECIMALS)).expect("converted power overflows u128") / unit.bytes()
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConvertedPrice {
+    price: U128, // scaled by 10^18, attoFIL or USD
+    unit: PriceUnit,
+    currency: Currency,
+    timestamp: u64, // of the price entry converted
+}
+
+impl PriceUnit {
+    // how many GiB-epochs the unit spans
+    fn gib_epochs(self) -> u128 {
+        match self {
+            PriceUnit::GibPerEpoch => 1,
+            PriceUnit::TibPerDay => GIB_PER_TIB * EPOCHS_PER_DAY,
+            PriceUnit::TibPerMonth => GIB_PER_TIB * EPOCHS_PER_DAY * DAYS_PER_MONTH,
+            PriceUnit::GibPerYear => EPOCHS_PER_DAY * DAYS_PER_YEAR,
+        }
+    }
+}
+
+// convert attoFIL to USD scaled by 10^18, fil_price being USD scaled by 10^18
+pub(crate) fn to_usd(atto_fil: u128, fil_price: u128) -> u128 {
+    atto_fil.checked_mul(fil_price).expect("converted price overflows u128") / 10u128.pow(decimal::PRICE_DECIMALS)
+}
+
+// convert attoFIL/GiB/epoch to the given unit and currency, fil_price being USD scaled by 10^18
+fn convert(price: u128, fil_price: u128, unit: PriceUnit, currency: Currency) -> u128 {
+    let fil = price.checked_mul(unit.gib_epochs()).expect("converted price overflows u128");
+    match currency {
+        Currency::Fil => fil,
+        Currency::Usd => to_usd(fil, fil_price),
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the latest price of a region, or the global price for None, in the given unit and currency
+    // None without a price entry, a price for the region, or a FIL price for USD
+    pub fn get_price_converted(&self, region: Option<u8>, unit: PriceUnit, currency: Currency) -> Option<ConvertedPrice> {
+        let ppr = self.price_per_region.get(&self.latest_timestamp)?;
+        let price = match region {
+            None => ppr.global.0,
+            Some(region) => ppr.prices.get(&region)?.0,
+        };
+        if currency == Currency::Usd && ppr.fil_price.0 == 0 {
+            return None;
+        }
+
+        Some(ConvertedPrice {
+            price: U128(convert(price, ppr.fil_price.0, unit, currency)),
+            unit,
+            currency,
+            timestamp: ppr.timestamp,
+        })
+    }
+
+    // get the network power of the latest price entry in the given unit, scaled by 10^18, None without an entry
+    pub fn get_network_power(&self, unit: PowerUnit) -> Option<U128> {
+        let ppr = self.price_per_region.get(&self.latest_timestamp)?;
+        Some(U128(convert_power(ppr.power.0, unit)))
+    }
+
+    // get the total power of the storage providers per region in the given unit, scaled by 10^18
+    pub fn get_power_per_region_converted(&self, unit: PowerUnit) -> BTreeMap<u8, U128> {
+        self.power_per_region.0
+            .iter()
+            .map(|(region, power)| (*region, U128(convert_power(power.0, unit))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    #[test]
+    fn convert_power_units() {
+        let scale = 10u128.pow(decimal::PRICE_DECIMALS);
+
+        assert_eq!(3 * scale, convert_power(3 * BYTES_PER_GIB, PowerUnit::Gib));
+        assert_eq!(scale / 2, convert_power(BYTES_PER_TIB / 2, PowerUnit::Tib));
+        assert_eq!(scale / 1_024, convert_power(BYTES_PER_TIB, PowerUnit::Pib));
+        assert_eq!(7 * scale, convert_power(7, PowerUnit::Bytes));
+    }
+
+    #[test]
+    fn convert_units_and_currencies() {
+        let price = 10_000_000_000; // 1e-8 FIL/GiB/epoch
+        let fil_price = fil(5.0).0;
+
+        assert_eq!(price, convert(price, fil_price, PriceUnit::GibPerEpoch, Currency::Fil));
+        assert_eq!(price * 1_024 * 2_880, convert(price, fil_price, PriceUnit::TibPerDay, Currency::Fil));
+        // 0.884736 FIL/TiB/month at 5 USD/FIL
+        assert_eq!(fil(4.42368).0, convert(price, fil_price, PriceUnit::TibPerMonth, Currency::Usd));
+        assert_eq!(fil(0.010512).0, convert(price, fil_price, PriceUnit::GibPerYear, Currency::Fil));
+    }
+
+    #[test]
+    fn get_price_converted() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        assert_eq!(None, contract.get_price_converted(None, PriceUnit::TibPerDay, Currency::Fil));
+
+        contract.set_price_per_region(PricePerRegion {
+            prices: vec![(REGION_EUROPE, fil(0.00000001))].into_iter().collect(),
+            global: fil(0.00000002),
+            fil_price: fil(5.0),
+            timestamp: 10,
+            ..Default::default()
+        });
+
+        let result = contract.get_price_converted(Some(REGION_EUROPE), PriceUnit::TibPerMonth, Currency::Usd).unwrap();
+        assert_eq!(fil(4.42368), result.price);
+        assert_eq!(10, result.timestamp);
+        let result = contract.get_price_converted(None, PriceUnit::GibPerYear, Currency::Fil).unwrap();
+        assert_eq!(fil(0.021024), result.price);
+        assert_eq!(None, contract.get_price_converted(Some(REGION_ASIA), PriceUnit::TibPerDay, Currency::Fil));
+    }
+}
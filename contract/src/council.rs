@@ -0,0 +1,318 @@
+/*
+ * Multi-signature ownership: once a council is set, the owner key no longer passes assert_owner. A privileged call
+ * becomes a proposal of a council member, and when threshold members confirmed it the contract calls the method on
+ * itself, which passes assert_owner, with the contract paying the storage of the call. A call that fails is pending
+ * again for the members to retry or revoke. Changing the council drops the pending proposals
+ */
+
+use crate::*;
+use near_sdk::{ext_contract, is_promise_success, Gas};
+
+pub const MAX_COUNCIL_MEMBERS: usize = 10;
+pub const MAX_PENDING_PROPOSALS: u64 = 20;
+pub const GAS_FOR_PROPOSAL: Gas = Gas(100_000_000_000_000);
+const GAS_FOR_ON_PROPOSAL_EXECUTED: Gas = Gas(10_000_000_000_000);
+
+#[ext_contract(ext_self)]
+pub trait ProposalCallbacks {
+    fn on_proposal_executed(&mut self, proposal: Proposal);
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Council {
+    pub members: Vec<AccountId>,
+    pub threshold: u32, // confirmations a proposal needs to be executed
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: AccountId,
+    pub method_name: String,
+    pub args: String, // JSON arguments of the method
+    pub confirmations: Vec<AccountId>,
+    pub created_at: u64, // seconds
+}
+
+impl FilMarket {
+    // fail the call unless the predecessor is a council member
+    fn assert_council_member(&self) -> AccountId {
+        let account_id = env::predecessor_account_id();
+        let is_member = self.council.as_ref().is_some_and(|council| council.members.contains(&account_id));
+
+        require!(is_member, format!("{}: account_id {} is not a council member", errors::ERR_NOT_COUNCIL_MEMBER, account_id));
+        self.mark_privileged(account_id.clone());
+        account_id
+    }
+
+    // execute a proposal once it has enough confirmations, returns whether it was executed
+    // note: the contract pays the storage of the call, see deposit_after_storage
+    fn execute_if_confirmed(&mut self, proposal: &Proposal) -> bool {
+        let threshold = self.council.as_ref().map(|council| council.threshold).unwrap_or(u32::MAX);
+        if (proposal.confirmations.len() as u32) < threshold {
+            return false;
+        }
+
+        self.proposals.remove(&proposal.id);
+        Promise::new(env::current_account_id()).function_call(
+            proposal.method_name.clone(),
+            proposal.args.clone().into_bytes(),
+            0,
+            GAS_FOR_PROPOSAL,
+        ).then(ext_self::on_proposal_executed(proposal.clone(), env::current_account_id(), 0, GAS_FOR_ON_PROPOSAL_EXECUTED));
+
+        true
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // hand ownership to a council of members, threshold of which must confirm a privileged call
+    // once set, the council itself is changed through a proposal
+    pub fn set_council(&mut self, members: Vec<AccountId>, threshold: u32) {
+        self.assert_owner();
+        require!(!members.is_empty() && members.len() <= MAX_COUNCIL_MEMBERS, format!("a council has 1 to {} members", MAX_COUNCIL_MEMBERS));
+        require!(
+            members.iter().collect::<HashSet<_>>().len() == members.len(),
+            "council members must be unique"
+        );
+        require!(
+            threshold > 0 && threshold as usize <= members.len(),
+            format!("threshold must be between 1 and {}", members.len())
+        );
+
+        self.proposals.clear();
+        self.council = Some(Council { members: members.clone(), threshold });
+        self.emit_event("council_set", json!({ "members": members, "threshold": threshold }));
+    }
+
+    // hand ownership back to the owner account
+    pub fn remove_council(&mut self) {
+        self.assert_owner();
+        if self.council.is_none() {
+            return;
+        }
+
+        self.proposals.clear();
+        self.council = None;
+        self.emit_event("council_removed", json!({ "owner": self.owner }));
+    }
+
+    // propose a privileged call, confirmed by the proposer, args is the JSON of the method arguments
+    pub fn propose(&mut self, method_name: String, args: String) -> u64 {
+        let account_id = self.assert_council_member();
+        require!(!method_name.is_empty(), "method_name must not be empty");
+        require!(
+            self.proposals.len() < MAX_PENDING_PROPOSALS,
+            format!("at most {} proposals can be pending", MAX_PENDING_PROPOSALS)
+        );
+
+        let proposal = Proposal {
+            id: self.next_proposal_id,
+            proposer: account_id.clone(),
+            method_name,
+            args,
+            confirmations: vec![account_id],
+            created_at: env::block_timestamp() / NANOSECONDS_PER_SECOND,
+        };
+        self.next_proposal_id += 1;
+
+        self.emit_event("proposal_created", json!({
+            "id": proposal.id,
+            "proposer": proposal.proposer,
+            "method_name": proposal.method_name,
+            "args": proposal.args,
+        }));
+        if !self.execute_if_confirmed(&proposal) {
+            self.proposals.insert(&proposal.id, &proposal);
+        }
+
+        proposal.id
+    }
+
+    // confirm a pending proposal, executing it once threshold members confirmed
+    pub fn confirm(&mut self, id: u64) {
+        let account_id = self.assert_council_member();
+        let mut proposal = self.proposals.get(&id).unwrap_or_else(|| panic!("no pending proposal {}", id));
+        require!(!proposal.confirmations.contains(&account_id), format!("{} already confirmed proposal {}", account_id, id));
+
+        proposal.confirmations.push(account_id.clone());
+        self.emit_event("proposal_confirmed", json!({ "id": id, "account_id": account_id }));
+        if !self.execute_if_confirmed(&proposal) {
+            self.proposals.insert(&id, &proposal);
+        }
+    }
+
+    // withdraw a confirmation, the proposal is dropped once no confirmation is left
+    pub fn revoke_confirmation(&mut self, id: u64) {
+        let account_id = self.assert_council_member();
+        let mut proposal = self.proposals.get(&id).unwrap_or_else(|| panic!("no pending proposal {}", id));
+        require!(proposal.confirmations.contains(&account_id), format!("{} did not confirm proposal {}", account_id, id));
+
+        proposal.confirmations.retain(|member| *member != account_id);
+        if proposal.confirmations.is_empty() {
+            self.proposals.remove(&id);
+            self.emit_event("proposal_dropped", json!({ "id": id }));
+        } else {
+            self.proposals.insert(&id, &proposal);
+            self.emit_event("proposal_confirmation_revoked", json!({ "id": id, "account_id": account_id }));
+        }
+    }
+
+    // report the result of an executed proposal, a failed one is pending again under the council that confirmed it
+    #[private]
+    pub fn on_proposal_executed(&mut self, proposal: Proposal) {
+        if is_promise_success() {
+            self.emit_event("proposal_executed", json!({
+                "id": proposal.id,
+                "method_name": proposal.method_name,
+                "confirmations": proposal.confirmations,
+            }));
+            return;
+        }
+
+        env::log_str(&format!("on_proposal_executed(): proposal {} failed", proposal.id));
+        let council_unchanged = self.council.as_ref()
+            .is_some_and(|council| proposal.confirmations.iter().all(|member| council.members.contains(member)));
+        if council_unchanged {
+            self.proposals.insert(&proposal.id, &proposal);
+        }
+        self.emit_event("proposal_failed", json!({
+            "id": proposal.id,
+            "method_name": proposal.method_name,
+            "restored": council_unchanged,
+        }));
+    }
+
+    pub fn get_council(&self) -> Option<Council> {
+        self.council.clone()
+    }
+
+    pub fn get_proposal(&self, id: u64) -> Option<Proposal> {
+        self.proposals.get(&id)
+    }
+
+    // get the pending proposals, paginated
+    pub fn get_pending_proposals(&self, from_index: u64, limit: u64) -> Vec<Proposal> {
+        self.proposals
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .map(|(_, proposal)| proposal)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, context, fil, set_context};
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, testing_env_with_promise_results};
+    use near_sdk::PromiseResult;
+
+    fn council_contract() -> FilMarket {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+        contract.set_council(vec![account("alice_near"), account("bob_near"), account("dave_near")], 2);
+        contract
+    }
+
+    #[test]
+    fn proposal_executes_at_threshold() {
+        let mut contract = council_contract();
+
+        set_context("alice_near", 0, 0);
+        let id = contract.propose("set_min_price".to_string(), r#"{"min_price":"1000"}"#.to_string());
+        assert_eq!(vec![account("alice_near")], contract.get_pending_proposals(0, 10)[0].confirmations);
+        assert!(get_created_receipts().is_empty());
+
+        set_context("bob_near", 0, 0);
+        contract.confirm(id);
+        assert!(contract.get_proposal(id).is_none());
+
+        let receipts = get_created_receipts();
+        assert_eq!(account("filmarket_near"), receipts[0].receiver_id);
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall { method_name, args, .. } => {
+                assert_eq!("set_min_price", method_name);
+                assert_eq!(br#"{"min_price":"1000"}"#.to_vec(), *args);
+            }
+            _ => panic!("expected a function call"),
+        }
+        match &receipts[1].actions[0] {
+            VmAction::FunctionCall { method_name, .. } => assert_eq!("on_proposal_executed", method_name),
+            _ => panic!("expected a function call"),
+        }
+
+        // the contract calling itself passes assert_owner, and pays the storage the call adds
+        set_context("filmarket_near", 0, 0);
+        contract.set_min_price(U128(1000));
+        assert_eq!(U128(1000), contract.get_min_price());
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 10, ..Default::default() });
+        assert_eq!(fil(0.1), contract.get_latest_price_per_region().global);
+    }
+
+    #[test]
+    fn failed_proposal_is_restored() {
+        let mut contract = council_contract();
+
+        set_context("alice_near", 0, 0);
+        let id = contract.propose("pause".to_string(), "{}".to_string());
+        set_context("bob_near", 0, 0);
+        contract.confirm(id);
+        assert!(contract.get_proposal(id).is_none());
+
+        testing_env_with_promise_results(context("filmarket_near", 0, 0).build(), PromiseResult::Failed);
+        let executed = Proposal {
+            id,
+            proposer: account("alice_near"),
+            method_name: "pause".to_string(),
+            args: "{}".to_string(),
+            confirmations: vec![account("alice_near"), account("bob_near")],
+            created_at: 0,
+        };
+        contract.on_proposal_executed(executed.clone());
+        assert_eq!(Some(executed), contract.get_proposal(id));
+    }
+
+    #[test]
+    fn revoke_confirmation() {
+        let mut contract = council_contract();
+
+        set_context("alice_near", 0, 0);
+        let id = contract.propose("pause".to_string(), "{}".to_string());
+        contract.revoke_confirmation(id);
+        assert!(contract.get_pending_proposals(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn owner_key_is_replaced_by_council() {
+        let mut contract = council_contract();
+
+        set_context("carol_near", 0, 0);
+        contract.set_min_price(fil(0.1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_COUNCIL_MEMBER")]
+    fn propose_by_other_account() {
+        let mut contract = council_contract();
+
+        set_context("carol_near", 0, 0);
+        contract.propose("pause".to_string(), "{}".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "bob_near already confirmed proposal 0")]
+    fn confirm_twice() {
+        let mut contract = council_contract();
+
+        set_context("bob_near", 0, 0);
+        contract.propose("pause".to_string(), "{}".to_string());
+        contract.confirm(0);
+    }
+}
@@ -0,0 +1,439 @@
+/*
+ * Storage deal marketplace: clients post storage requests, storage providers answer with offers and
+ * the client accepting an offer records a deal, which then moves Proposed -> Accepted -> Active -> Completed,
+ * or to Cancelled before it becomes active, or to Refunded when the owner settles a dispute in favor of the client
+ */
+
+use crate::*;
+
+pub const MAX_OFFERS_PER_REQUEST: usize = 50;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageRequest {
+    id: u64,
+    client: AccountId,
+    size: U128,          // bytes
+    duration: u64,       // epochs
+    region: u8,          // see StorageProvider.region
+    max_price: U128,     // attoFIL/GiB/epoch
+    offer_ids: Vec<u64>, // offers received, at most MAX_OFFERS_PER_REQUEST
+    open: bool,          // false once an offer was accepted or the request was cancelled
+    created_at: u64,     // block timestamp in nanoseconds
+}
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Offer {
+    id: u64,
+    request_id: u64,
+    provider_id: String,
+    price: U128,     // attoFIL/GiB/epoch
+    created_at: u64, // block timestamp in nanoseconds
+}
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DealState {
+    Proposed,  // the client accepted an offer, waiting for the storage provider
+    Accepted,  // the storage provider confirmed the deal
+    Active,    // the data is sealed and stored
+    Completed, // the deal ran its duration
+    Cancelled, // cancelled by either side before becoming active
+    Refunded,  // the escrow was refunded to the client in a dispute
+}
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Deal {
+    pub(crate) id: u64,
+    pub(crate) request_id: u64,
+    pub(crate) offer_id: u64,
+    pub(crate) client: AccountId,
+    pub(crate) provider_id: String,
+    pub(crate) size: U128,      // bytes
+    pub(crate) duration: u64,   // epochs
+    pub(crate) price: U128,     // attoFIL/GiB/epoch
+    pub(crate) state: DealState,
+    pub(crate) updated_at: u64, // block timestamp in nanoseconds of the latest state change
+}
+
+impl FilMarket {
+    // fail the call unless the predecessor controls the storage provider, or manages providers on its behalf
+    pub(crate) fn assert_provider_side(&self, provider_id: &str) {
+        let account_id = env::predecessor_account_id();
+        if self.provider_accounts.get(&provider_id.to_string()).as_ref() != Some(&account_id) {
+            self.assert_role(Role::SpManager);
+        }
+    }
+
+    pub(crate) fn expect_deal(&self, deal_id: u64) -> Deal {
+        self.deals.get(&deal_id).unwrap_or_else(|| panic!("deal {} not found", deal_id))
+    }
+
+    // move a deal to the next state, failing unless it is currently in one of the expected states
+    pub(crate) fn transition_deal(&mut self, mut deal: Deal, from: &[DealState], to: DealState) {
+        self.assert_not_paused(Feature::Deals);
+        require!(
+            from.contains(&deal.state),
+            format!("deal {} is {:?}, cannot move to {:?}", deal.id, deal.state, to)
+        );
+
+        let previous = deal.state;
+        deal.state = to;
+        deal.updated_at = env::block_timestamp();
+        self.deals.insert(&deal.id, &deal);
+
+        self.emit_event("deal_state_changed", json!({
+            "deal_id": deal.id,
+            "from": previous,
+            "to": to,
+        }));
+    }
+
+    // record a Proposed deal for an offer on one of the caller's storage requests and close the request
+    pub(crate) fn internal_accept_offer(&mut self, offer_id: u64) -> Deal {
+        self.assert_not_paused(Feature::Deals);
+        let offer = self.offers.get(&offer_id).unwrap_or_else(|| panic!("offer {} not found", offer_id));
+        let request = self.storage_requests.get(&offer.request_id)
+            .unwrap_or_else(|| panic!("storage request {} not found", offer.request_id));
+        require!(request.client == env::predecessor_account_id(), "only the client may accept an offer");
+
+        let id = self.next_deal_id;
+        self.next_deal_id += 1;
+
+        let deal = Deal {
+            id,
+            request_id: request.id,
+            offer_id,
+            client: request.client.clone(),
+            provider_id: offer.provider_id,
+            size: request.size,
+            duration: request.duration,
+            price: offer.price,
+            state: DealState::Proposed,
+            updated_at: env::block_timestamp(),
+        };
+        self.deals.insert(&id, &deal);
+        self.close_storage_request(request);
+        deal
+    }
+
+    // close a storage request and drop its offers, which can no longer be accepted
+    fn close_storage_request(&mut self, mut request: StorageRequest) {
+        for offer_id in request.offer_ids.drain(..) {
+            self.offers.remove(&offer_id);
+        }
+        request.open = false;
+        self.storage_requests.insert(&request.id, &request);
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // post a request to store size bytes for duration epochs in a region at no more than max_price attoFIL/GiB/epoch
+    // the attached deposit must cover the storage of the request, the rest is refunded
+    #[payable]
+    pub fn post_storage_request(&mut self, size: U128, duration: u64, region: u8, max_price: U128) -> u64 {
+        self.assert_not_paused(Feature::Deals);
+        require!(size.0 > 0, "size must be greater than 0");
+        require!(duration > 0, "duration must be greater than 0");
+        require!(self.is_valid_region(region), format!("invalid region {}", region));
+
+        let client = env::predecessor_account_id();
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+
+        let initial_storage_usage = env::storage_usage();
+        let request = StorageRequest {
+            id,
+            client: client.clone(),
+            size,
+            duration,
+            region,
+            max_price,
+            offer_ids: Vec::new(),
+            open: true,
+            created_at: env::block_timestamp(),
+        };
+        self.storage_requests.insert(&id, &request);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("storage_request_posted", json!({
+            "request_id": id,
+            "client": client,
+        }));
+        id
+    }
+
+    // cancel an open storage request, only its client may cancel it
+    pub fn cancel_storage_request(&mut self, request_id: u64) {
+        self.assert_not_paused(Feature::Deals);
+        let request = self.storage_requests.get(&request_id)
+            .unwrap_or_else(|| panic!("storage request {} not found", request_id));
+        require!(request.client == env::predecessor_account_id(), "only the client may cancel a storage request");
+        require!(request.open, format!("storage request {} is not open", request_id));
+
+        self.close_storage_request(request);
+        self.emit_event("storage_request_cancelled", json!({ "request_id": request_id }));
+    }
+
+    // offer to serve an open storage request with a listed storage provider, by its account or an SpManager
+    // the attached deposit must cover the storage of the offer, the rest is refunded
+    #[payable]
+    pub fn post_offer(&mut self, request_id: u64, provider_id: String, price: U128) -> u64 {
+        self.assert_not_paused(Feature::Deals);
+        self.assert_provider_side(&provider_id);
+
+        let mut request = self.storage_requests.get(&request_id)
+            .unwrap_or_else(|| panic!("storage request {} not found", request_id));
+        require!(request.open, format!("storage request {} is not open", request_id));
+        require!(request.offer_ids.len() < MAX_OFFERS_PER_REQUEST, format!("storage request {} has too many offers", request_id));
+        require!(price.0 <= request.max_price.0, "price is above the max price of the storage request");
+
+        let provider = self.storage_providers.get(&provider_id)
+            .unwrap_or_else(|| panic!("storage provider {} not found", provider_id));
+        require!(provider.is_active(), format!("storage provider {} is not active", provider_id));
+        require!(provider.region == request.region, format!("storage provider {} is not in region {}", provider_id, request.region));
+
+        let id = self.next_offer_id;
+        self.next_offer_id += 1;
+
+        let initial_storage_usage = env::storage_usage();
+        let offer = Offer { id, request_id, provider_id: provider_id.clone(), price, created_at: env::block_timestamp() };
+        self.offers.insert(&id, &offer);
+        request.offer_ids.push(id);
+        self.storage_requests.insert(&request_id, &request);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("offer_posted", json!({
+            "offer_id": id,
+            "request_id": request_id,
+            "provider_id": provider_id,
+            "price": price,
+        }));
+        id
+    }
+
+    // accept an offer on one of the caller's storage requests, closing the request and recording a Proposed deal
+    // the attached deposit must cover the storage of the deal, the rest is held in escrow for the storage provider
+    #[payable]
+    pub fn accept_offer(&mut self, offer_id: u64) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        let deal = self.internal_accept_offer(offer_id);
+        self.deal_escrow.insert(&deal.id, &0);
+        let escrow = self.deposit_after_storage(initial_storage_usage);
+        require!(escrow > 0, "must attach a deposit to hold in escrow");
+        self.deal_escrow.insert(&deal.id, &escrow);
+
+        self.emit_event("offer_accepted", json!({
+            "offer_id": offer_id,
+            "deal_id": deal.id,
+            "client": deal.client,
+            "provider_id": deal.provider_id,
+            "escrow": U128(escrow),
+        }));
+        deal.id
+    }
+
+    // confirm a proposed deal on the storage provider side
+    pub fn accept_deal(&mut self, deal_id: u64) {
+        let deal = self.expect_deal(deal_id);
+        self.assert_provider_side(&deal.provider_id);
+        self.transition_deal(deal, &[DealState::Proposed], DealState::Accepted);
+    }
+
+    // mark an accepted deal as active once its data is sealed
+    pub fn activate_deal(&mut self, deal_id: u64) {
+        let deal = self.expect_deal(deal_id);
+        self.assert_provider_side(&deal.provider_id);
+        self.transition_deal(deal, &[DealState::Accepted], DealState::Active);
+    }
+
+    // mark an active deal as completed, counting a successful deal for the storage provider if still listed
+    pub fn complete_deal(&mut self, deal_id: u64) {
+        let deal = self.expect_deal(deal_id);
+        self.assert_provider_side(&deal.provider_id);
+        let provider_id = deal.provider_id.clone();
+        self.transition_deal(deal, &[DealState::Active], DealState::Completed);
+
+        if self.storage_providers.get(&provider_id).is_some() {
+            self.internal_record_reputation(&[(provider_id, ReputationEvent::SuccessfulDeal)]);
+        }
+    }
+
+    // cancel a deal that is not active yet, by its client or its storage provider side, its escrow is refunded to the client
+    pub fn cancel_deal(&mut self, deal_id: u64) {
+        let deal = self.expect_deal(deal_id);
+        if deal.client != env::predecessor_account_id() {
+            self.assert_provider_side(&deal.provider_id);
+        }
+        let client = deal.client.clone();
+        self.transition_deal(deal, &[DealState::Proposed, DealState::Accepted], DealState::Cancelled);
+        self.pay_out_escrow(deal_id, client, "escrow_refunded");
+    }
+
+    pub fn get_storage_request(&self, request_id: u64) -> Option<StorageRequest> {
+        self.storage_requests.get(&request_id)
+    }
+
+    // get the open storage requests, optionally in a region, paginated
+    pub fn get_open_storage_requests(&self, region: Option<u8>, from_index: u64, limit: u64) -> Vec<StorageRequest> {
+        self.storage_requests
+            .values()
+            .filter(|request| request.open && region.is_none_or(|region| request.region == region))
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+
+    // get the offers received by an open storage request
+    pub fn get_offers(&self, request_id: u64) -> Vec<Offer> {
+        self.storage_requests
+            .get(&request_id)
+            .map(|request| request.offer_ids.iter().filter_map(|offer_id| self.offers.get(offer_id)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_deal(&self, deal_id: u64) -> Option<Deal> {
+        self.deals.get(&deal_id)
+    }
+
+    // get the deals of a client, paginated
+    pub fn get_client_deals(&self, client: AccountId, from_index: u64, limit: u64) -> Vec<Deal> {
+        self.deals
+            .values()
+            .filter(|deal| deal.client == client)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+
+    // get the deals of a storage provider, paginated
+    pub fn get_provider_deals(&self, provider_id: String, from_index: u64, limit: u64) -> Vec<Deal> {
+        self.deals
+            .values()
+            .filter(|deal| deal.provider_id == provider_id)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    // a contract listing f01234 in Europe, controlled by miner_near, with an open request by client_near
+    fn market_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+        set_context("carol_near", NEAR, 0);
+        contract.approve_provider_claim("f01234".to_string());
+
+        set_context("client_near", NEAR, 0);
+        assert_eq!(0, contract.post_storage_request(gib(32.0), 518_400, REGION_EUROPE, fil(0.2)));
+        contract
+    }
+
+    #[test]
+    fn deal_lifecycle() {
+        let mut contract = market_contract();
+        assert_eq!(1, contract.get_open_storage_requests(Some(REGION_EUROPE), 0, 10).len());
+        assert!(contract.get_open_storage_requests(Some(REGION_ASIA), 0, 10).is_empty());
+
+        set_context("miner_near", NEAR, 0);
+        let offer_id = contract.post_offer(0, "f01234".to_string(), fil(0.15));
+        assert_eq!(1, contract.get_offers(0).len());
+
+        set_context("client_near", NEAR, 0);
+        let deal_id = contract.accept_offer(offer_id);
+        assert!(!contract.get_storage_request(0).unwrap().open);
+        assert!(contract.get_offers(0).is_empty());
+        assert!(contract.get_open_storage_requests(None, 0, 10).is_empty());
+
+        let deal = contract.get_deal(deal_id).unwrap();
+        assert_eq!(DealState::Proposed, deal.state);
+        assert_eq!(fil(0.15), deal.price);
+        assert_eq!(gib(32.0), deal.size);
+
+        set_context("miner_near", NEAR, 0);
+        contract.accept_deal(deal_id);
+        contract.activate_deal(deal_id);
+        contract.complete_deal(deal_id);
+        assert_eq!(DealState::Completed, contract.get_deal(deal_id).unwrap().state);
+        // a successful deal, uptime and rating still neutral
+        assert_eq!(66, contract.get_storage_providers()[0].reputation);
+
+        assert_eq!(1, contract.get_client_deals(account("client_near"), 0, 10).len());
+        assert_eq!(1, contract.get_provider_deals("f01234".to_string(), 0, 10).len());
+        assert!(contract.get_provider_deals("f05678".to_string(), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn cancel_deal_by_client() {
+        let mut contract = market_contract();
+
+        // the owner posts on behalf of the storage provider
+        set_context("carol_near", NEAR, 0);
+        let offer_id = contract.post_offer(0, "f01234".to_string(), fil(0.2));
+
+        set_context("client_near", NEAR, 0);
+        let deal_id = contract.accept_offer(offer_id);
+        contract.cancel_deal(deal_id);
+        assert_eq!(DealState::Cancelled, contract.get_deal(deal_id).unwrap().state);
+    }
+
+    #[test]
+    #[should_panic(expected = "deal 0 is Active, cannot move to Cancelled")]
+    fn cancel_active_deal() {
+        let mut contract = market_contract();
+
+        set_context("miner_near", NEAR, 0);
+        let offer_id = contract.post_offer(0, "f01234".to_string(), fil(0.2));
+        set_context("client_near", NEAR, 0);
+        let deal_id = contract.accept_offer(offer_id);
+        set_context("miner_near", NEAR, 0);
+        contract.accept_deal(deal_id);
+        contract.activate_deal(deal_id);
+
+        set_context("client_near", NEAR, 0);
+        contract.cancel_deal(deal_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "price is above the max price of the storage request")]
+    fn post_offer_above_max_price() {
+        let mut contract = market_contract();
+
+        set_context("miner_near", NEAR, 0);
+        contract.post_offer(0, "f01234".to_string(), fil(0.3));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn post_offer_for_other_provider() {
+        let mut contract = market_contract();
+
+        set_context("other_near", NEAR, 0);
+        contract.post_offer(0, "f01234".to_string(), fil(0.1));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage request 0 is not open")]
+    fn post_offer_on_cancelled_request() {
+        let mut contract = market_contract();
+
+        set_context("client_near", NEAR, 0);
+        contract.cancel_storage_request(0);
+
+        set_context("miner_near", NEAR, 0);
+        contract.post_offer(0, "f01234".to_string(), fil(0.1));
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * Fixed-point decimal strings
+ */
+
+// decimal places of the fixed-point representation, prices are scaled to attoFIL
+pub const PRICE_DECIMALS: u32 = 18;
+
+// parse a non-negative decimal string such as "0.00013" into an integer scaled by 10^decimals
+// returns None for signs, exponents, empty parts, more than `decimals` fractional digits or overflow
+pub fn parse_decimal(value: &str, decimals: u32) -> Option<u128> {
+    let (integer, fraction) = match value.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (value, ""),
+    };
+
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) || fraction.len() > decimals as usize {
+        return None;
+    }
+    if value.contains('.') && fraction.is_empty() {
+        return None;
+    }
+
+    let scale = 10u128.checked_pow(decimals)?;
+    let fraction_scale = 10u128.checked_pow(decimals - fraction.len() as u32)?;
+
+    let integer: u128 = integer.parse().ok()?;
+    let fraction: u128 = if fraction.is_empty() { 0 } else { fraction.parse().ok()? };
+
+    integer.checked_mul(scale)?.checked_add(fraction.checked_mul(fraction_scale)?)
+}
+
+// convert a float to an integer scaled by 10^decimals through its shortest decimal representation,
+// so 0.00013 becomes exactly 130_000_000_000_000, digits beyond decimals are truncated
+// returns None for negative or non-finite values
+pub fn f64_to_scaled(value: f64, decimals: u32) -> Option<u128> {
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+
+    let repr = value.to_string();
+    match repr.split_once('.') {
+        Some((integer, fraction)) if fraction.len() > decimals as usize => {
+            let fraction = &fraction[..decimals as usize];
+            if fraction.is_empty() {
+                parse_decimal(integer, decimals)
+            } else {
+                parse_decimal(&format!("{}.{}", integer, fraction), decimals)
+            }
+        }
+        _ => parse_decimal(&repr, decimals),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_decimal_exact() {
+        assert_eq!(Some(130_000_000_000_000), parse_decimal("0.00013", PRICE_DECIMALS));
+        assert_eq!(Some(64_245_000_000_000_000_000), parse_decimal("64.245", PRICE_DECIMALS));
+        assert_eq!(Some(2_000_000_000_000_000_000), parse_decimal("2", PRICE_DECIMALS));
+        assert_eq!(Some(1), parse_decimal("0.000000000000000001", PRICE_DECIMALS));
+        assert_eq!(Some(0), parse_decimal("0", PRICE_DECIMALS));
+    }
+
+    #[test]
+    fn parse_decimal_malformed() {
+        for value in ["", ".", "1.", ".5", "-1", "+1", "1e-4", "0.1.2", "1,5", " 1", "abc", "0.0000000000000000001"] {
+            assert_eq!(None, parse_decimal(value, PRICE_DECIMALS), "{}", value);
+        }
+        assert_eq!(None, parse_decimal("340282366920938463464", PRICE_DECIMALS));
+    }
+
+    #[test]
+    fn f64_to_scaled_exact() {
+        assert_eq!(Some(130_000_000_000_000), f64_to_scaled(0.00013, PRICE_DECIMALS));
+        assert_eq!(Some(64_245_000_000_000_000_000), f64_to_scaled(64.245, PRICE_DECIMALS));
+        assert_eq!(Some(3_000_000_000_000_000_000), f64_to_scaled(3.0, PRICE_DECIMALS));
+        assert_eq!(Some(0), f64_to_scaled(1e-20, PRICE_DECIMALS));
+        assert_eq!(Some(1), f64_to_scaled(1.9, 0));
+        assert_eq!(None, f64_to_scaled(-0.1, PRICE_DECIMALS));
+        assert_eq!(None, f64_to_scaled(f64::NAN, PRICE_DECIMALS));
+    }
+}
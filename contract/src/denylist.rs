@@ -0,0 +1,146 @@
+/*
+ * Denylist of storage providers reporting bogus prices: a denied listing is moved out of the provider set, so it
+ * is left out of every view, index and aggregate, and updates for a denied id are skipped until it is allowed again
+ */
+
+use crate::*;
+
+impl FilMarket {
+    pub(crate) fn is_denied(&self, id: &String) -> bool {
+        self.denied_providers.get(id).is_some()
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // hide a storage provider and skip its updates, its listing is kept aside for allow_provider
+    pub fn deny_provider(&mut self, id: String) {
+        self.assert_owner();
+        require!(id.len() <= MAX_ID_LEN, format!("storage provider id is longer than {} bytes", MAX_ID_LEN));
+        let id = self.expect_provider_id(&id);
+        if self.is_denied(&id) {
+            return;
+        }
+
+        let listing = self.storage_providers.remove(&id);
+        if let Some(storage_provider) = &listing {
+            self.reindex_provider(Some(storage_provider), None);
+            self.record_provider_removed(storage_provider);
+        }
+        self.denied_providers.insert(&id, &listing);
+        self.record_active_history();
+
+        self.emit_event("provider_denied", json!({
+            "id": id,
+            "listed": listing.is_some(),
+        }));
+    }
+
+    // lift the denial of a storage provider, restoring its listing if it had one
+    pub fn allow_provider(&mut self, id: String) {
+        self.assert_owner();
+        let id = self.lookup_provider_id(&id);
+
+        let mut listing = match self.denied_providers.remove(&id) {
+            Some(listing) => listing,
+            None => return,
+        };
+        if let Some(storage_provider) = &mut listing {
+            self.touch_provider(storage_provider);
+            self.storage_providers.insert(&id, storage_provider);
+            self.reindex_provider(None, Some(storage_provider));
+            self.clear_provider_removed(&id);
+        }
+        self.record_active_history();
+
+        self.emit_event("provider_allowed", json!({
+            "id": id,
+            "listed": listing.is_some(),
+        }));
+    }
+
+    // get the denied storage provider ids with the listing kept aside, if any, paginated
+    pub fn get_denied_providers(&self, from_index: u64, limit: u64) -> Vec<(String, Option<StorageProvider>)> {
+        self.denied_providers
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn sp_list() -> Vec<StorageProvider> {
+        vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(9.0), ..Default::default() },
+        ]
+    }
+
+    #[test]
+    fn deny_and_allow_provider() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.update_storage_providers(sp_list(), None);
+
+        contract.deny_provider("f02".to_string());
+        assert!(!contract.has_storage_provider("f02".to_string()));
+        assert_eq!(fil(0.1), contract.get_price_summary(None).max);
+        assert_eq!(1, contract.get_storage_providers_by_region(REGION_EUROPE, 0, 10).len());
+        assert_eq!(1, contract.get_cheapest_providers(REGION_EUROPE, 10).len());
+        assert_eq!("f02", contract.get_denied_providers(0, 10)[0].0);
+
+        // updates of a denied provider are skipped
+        let result = contract.update_storage_providers(sp_list(), None);
+        assert_eq!(1, result.updated);
+        assert_eq!(1, result.skipped);
+        assert_eq!(1, contract.get_provider_count());
+
+        contract.allow_provider("f02".to_string());
+        assert_eq!(fil(9.0), contract.get_storage_provider("f02".to_string()).unwrap().price);
+        assert_eq!(2, contract.get_cheapest_providers(REGION_EUROPE, 10).len());
+        assert!(contract.get_denied_providers(0, 10).is_empty());
+    }
+
+    #[test]
+    fn deny_unlisted_provider() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.deny_provider("f01".to_string());
+        assert!(contract.get_denied_providers(0, 10)[0].1.is_none());
+        contract.update_storage_providers(sp_list(), None);
+        assert!(!contract.has_storage_provider("f01".to_string()));
+
+        // allowing an unlisted provider lets its next update in
+        contract.allow_provider("f01".to_string());
+        contract.update_storage_providers(sp_list(), None);
+        assert!(contract.has_storage_provider("f01".to_string()));
+    }
+
+    #[test]
+    fn allow_provider_denied_with_mixed_case_id() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.update_storage_providers(sp_list(), None);
+
+        contract.deny_provider("F02".to_string());
+        contract.allow_provider(" F02".to_string());
+        assert!(contract.get_denied_providers(0, 10).is_empty());
+        assert!(contract.has_storage_provider("f02".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn deny_provider_by_other_account() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        contract.deny_provider("f01".to_string());
+    }
+}
@@ -0,0 +1,28 @@
+/*
+ * Error codes, failed calls panic with a message starting with one of these
+ */
+
+// the predecessor is not the owner of the contract
+pub const ERR_NOT_OWNER: &str = "ERR_NOT_OWNER";
+// the predecessor is not the proposed owner
+pub const ERR_NOT_PENDING_OWNER: &str = "ERR_NOT_PENDING_OWNER";
+// the predecessor is not a member of the council owning the contract
+pub const ERR_NOT_COUNCIL_MEMBER: &str = "ERR_NOT_COUNCIL_MEMBER";
+// the predecessor is not the governance account the configuration was handed to
+pub const ERR_NOT_GOVERNANCE: &str = "ERR_NOT_GOVERNANCE";
+// the predecessor is neither the owner nor holds the role the method requires
+pub const ERR_MISSING_ROLE: &str = "ERR_MISSING_ROLE";
+// the predecessor does not control the storage provider it tries to update
+pub const ERR_NOT_PROVIDER_ACCOUNT: &str = "ERR_NOT_PROVIDER_ACCOUNT";
+// the contract or the feature the method belongs to is paused
+pub const ERR_PAUSED: &str = "ERR_PAUSED";
+// the price feeder does not keep the bond required to submit data
+pub const ERR_FEEDER_NOT_BONDED: &str = "ERR_FEEDER_NOT_BONDED";
+// a price feeder wrote a price directly while price rounds need several observations
+pub const ERR_QUORUM_REQUIRED: &str = "ERR_QUORUM_REQUIRED";
+// the predecessor is not approved by the owner for the action
+pub const ERR_NOT_APPROVED: &str = "ERR_NOT_APPROVED";
+// the oracle batch carries a nonce already used
+pub const ERR_STALE_NONCE: &str = "ERR_STALE_NONCE";
+// the oracle batch is not signed by the oracle key
+pub const ERR_BAD_SIGNATURE: &str = "ERR_BAD_SIGNATURE";
@@ -0,0 +1,179 @@
+/*
+ * Escrow for deals: the client's NEAR deposit or tokens are held when an offer is accepted, refunded when the deal
+ * is cancelled or refunded in a dispute, and released to the storage provider's account once the deal is completed
+ */
+
+use crate::*;
+
+impl FilMarket {
+    // whether a deal still holds NEAR or tokens in escrow
+    fn has_escrow(&self, deal_id: u64) -> bool {
+        self.deal_escrow.get(&deal_id).is_some() || self.deal_token_escrow.get(&deal_id).is_some()
+    }
+
+    // remove the escrow of a deal, transferring its NEAR to account_id and crediting its tokens to the
+    // token balance of account_id, nothing to do without escrow
+    pub(crate) fn pay_out_escrow(&mut self, deal_id: u64, account_id: AccountId, event: &str) {
+        let amount = self.deal_escrow.remove(&deal_id).unwrap_or(0);
+        let token_amount = self.deal_token_escrow.remove(&deal_id).unwrap_or(0);
+        if amount == 0 && token_amount == 0 {
+            return;
+        }
+
+        if token_amount > 0 {
+            self.credit_tokens(&account_id, token_amount);
+        }
+        self.emit_event(event, json!({
+            "deal_id": deal_id,
+            "account_id": account_id,
+            "amount": U128(amount),
+            "token_amount": U128(token_amount),
+        }));
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // release the escrow of a completed deal to the account controlling its storage provider, by the client or the owner
+    pub fn release_escrow(&mut self, deal_id: u64) {
+        self.assert_not_paused(Feature::Deals);
+        let deal = self.deals.get(&deal_id).unwrap_or_else(|| panic!("deal {} not found", deal_id));
+        if deal.client != env::predecessor_account_id() {
+            self.assert_owner();
+        }
+        require!(deal.state == DealState::Completed, format!("deal {} is not completed", deal_id));
+        require!(self.has_escrow(deal_id), format!("deal {} has no escrow", deal_id));
+
+        let account_id = self.provider_accounts.get(&deal.provider_id)
+            .unwrap_or_else(|| panic!("storage provider {} is not claimed by any account", deal.provider_id));
+        self.pay_out_escrow(deal_id, account_id, "escrow_released");
+    }
+
+    // settle a dispute by refunding the escrow of an active or completed deal to its client, the deal is Refunded
+    pub fn refund_escrow(&mut self, deal_id: u64) {
+        self.assert_owner();
+
+        let deal = self.expect_deal(deal_id);
+        require!(self.has_escrow(deal_id), format!("deal {} has no escrow", deal_id));
+
+        let client = deal.client.clone();
+        self.transition_deal(deal, &[DealState::Active, DealState::Completed], DealState::Refunded);
+        self.pay_out_escrow(deal_id, client, "escrow_refunded");
+    }
+
+    // get the yoctoNEAR held in escrow for a deal, 0 once released or refunded
+    pub fn get_deal_escrow(&self, deal_id: u64) -> U128 {
+        U128(self.deal_escrow.get(&deal_id).unwrap_or(0))
+    }
+
+    // get the payment tokens held in escrow for a deal, 0 once released or refunded
+    pub fn get_deal_token_escrow(&self, deal_id: u64) -> U128 {
+        U128(self.deal_token_escrow.get(&deal_id).unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, transfers, NEAR};
+
+    // a contract with a Proposed deal 0 between client_near and f01234, controlled by miner_near
+    fn deal_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+        set_context("carol_near", 0, 0);
+        contract.approve_provider_claim("f01234".to_string());
+
+        set_context("client_near", NEAR, 0);
+        contract.post_storage_request(gib(32.0), 518_400, REGION_EUROPE, fil(0.2));
+        set_context("miner_near", NEAR, 0);
+        let offer_id = contract.post_offer(0, "f01234".to_string(), fil(0.15));
+        set_context("client_near", 5 * NEAR, 0);
+        assert_eq!(0, contract.accept_offer(offer_id));
+        contract
+    }
+
+    #[test]
+    fn release_escrow_of_completed_deal() {
+        let mut contract = deal_contract();
+        let escrow = contract.get_deal_escrow(0).0;
+        assert!(escrow > 4 * NEAR && escrow < 5 * NEAR);
+
+        set_context("miner_near", 0, 0);
+        contract.accept_deal(0);
+        contract.activate_deal(0);
+        contract.complete_deal(0);
+
+        set_context("client_near", 0, 0);
+        contract.release_escrow(0);
+        assert_eq!(vec![(account("miner_near"), escrow)], transfers());
+        assert_eq!(U128(0), contract.get_deal_escrow(0));
+    }
+
+    #[test]
+    fn cancel_deal_refunds_escrow() {
+        let mut contract = deal_contract();
+        let escrow = contract.get_deal_escrow(0).0;
+
+        set_context("miner_near", 0, 0);
+        contract.cancel_deal(0);
+        assert_eq!(vec![(account("client_near"), escrow)], transfers());
+        assert_eq!(U128(0), contract.get_deal_escrow(0));
+    }
+
+    #[test]
+    fn refund_escrow_in_dispute() {
+        let mut contract = deal_contract();
+        let escrow = contract.get_deal_escrow(0).0;
+
+        set_context("miner_near", 0, 0);
+        contract.accept_deal(0);
+        contract.activate_deal(0);
+
+        set_context("carol_near", 0, 0);
+        contract.refund_escrow(0);
+        assert_eq!(vec![(account("client_near"), escrow)], transfers());
+        assert_eq!(DealState::Refunded, contract.get_deal(0).unwrap().state);
+    }
+
+    #[test]
+    #[should_panic(expected = "deal 0 is Proposed, cannot move to Refunded")]
+    fn refund_escrow_of_pending_deal() {
+        let mut contract = deal_contract();
+
+        set_context("carol_near", 0, 0);
+        contract.refund_escrow(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "deal 0 is not completed")]
+    fn release_escrow_of_active_deal() {
+        let mut contract = deal_contract();
+
+        set_context("miner_near", 0, 0);
+        contract.accept_deal(0);
+        contract.activate_deal(0);
+
+        set_context("client_near", 0, 0);
+        contract.release_escrow(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn release_escrow_by_provider() {
+        let mut contract = deal_contract();
+
+        set_context("miner_near", 0, 0);
+        contract.accept_deal(0);
+        contract.activate_deal(0);
+        contract.complete_deal(0);
+        contract.release_escrow(0);
+    }
+}
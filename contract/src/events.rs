@@ -0,0 +1,134 @@
+/*
+ * NEP-297 events
+ */
+
+use crate::*;
+use near_sdk::serde_json::Value;
+
+pub const EVENT_STANDARD: &str = "filmarket";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+// log a NEP-297 event: EVENT_JSON:{"standard", "version", "event", "data"}
+fn log_event(event: &str, data: Value) {
+    let event = json!({
+        "standard": EVENT_STANDARD,
+        "version": EVENT_VERSION,
+        "event": event,
+        "data": [data],
+    });
+
+    env::log_str(&format!("EVENT_JSON:{}", event));
+}
+
+impl FilMarket {
+    // emit an event tagged with the next sequence number, so indexers can detect missed events
+    pub(crate) fn emit_event(&mut self, event: &str, mut data: Value) {
+        self.event_seq += 1;
+        data["seq"] = json!(self.event_seq);
+
+        self.record_admin_action(event, &data);
+        log_event(event, data);
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the sequence number of the latest event, 0 if none was emitted
+    pub fn get_event_seq(&self) -> u64 {
+        self.event_seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    // the (event, data) of every NEP-297 event logged in the current context
+    fn events() -> Vec<(String, Value)> {
+        get_logs()
+            .into_iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:").map(|json| json.to_string()))
+            .map(|json| {
+                let event: Value = near_sdk::serde_json::from_str(&json).unwrap();
+                assert_eq!(EVENT_STANDARD, event["standard"]);
+                assert_eq!(EVENT_VERSION, event["version"]);
+                (event["event"].as_str().unwrap().to_string(), event["data"][0].clone())
+            })
+            .collect()
+    }
+
+    fn last_event_seq() -> u64 {
+        events().last().unwrap().1["seq"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn event_seq_increments() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        assert_eq!(0, contract.get_event_seq());
+
+        contract.set_min_price(fil(0.1));
+        assert_eq!(1, contract.get_event_seq());
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.01), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(2, contract.get_event_seq());
+        assert_eq!(2, last_event_seq());
+
+        contract.propose_owner(account("bob_near"));
+        set_context("bob_near", NEAR, 0);
+        contract.accept_ownership();
+        assert_eq!(4, contract.get_event_seq());
+        assert_eq!(4, last_event_seq());
+    }
+
+    #[test]
+    fn storage_provider_events() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        set_context("carol_near", NEAR, 0);
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(10.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        let (event, data) = events().pop().unwrap();
+        assert_eq!("storage_providers_updated", event);
+        assert_eq!(json!(["f02"]), data["added"]);
+        assert_eq!(json!(["f01"]), data["updated"]);
+
+        set_context("carol_near", NEAR, 0);
+        contract.delete_storage_providers(vec!["f02".to_string(), "f03".to_string()]);
+        let (event, data) = events().pop().unwrap();
+        assert_eq!("storage_providers_deleted", event);
+        assert_eq!(json!(["f02"]), data["removed"]);
+    }
+
+    #[test]
+    fn price_per_region_events() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 10, ..Default::default() });
+        let (event, data) = events().pop().unwrap();
+        assert_eq!("price_per_region_set", event);
+        assert_eq!("340000000000000", data["price_per_region"]["global"]);
+        assert_eq!(10, data["price_per_region"]["timestamp"]);
+
+        set_context("carol_near", NEAR, 0);
+        contract.delete_price_per_region(vec![10, 20]);
+        let (event, data) = events().pop().unwrap();
+        assert_eq!("price_per_region_deleted", event);
+        assert_eq!(json!([10]), data["timestamps"]);
+
+        // nothing changed, nothing to report
+        set_context("carol_near", NEAR, 0);
+        contract.delete_price_per_region(vec![10]);
+        assert!(events().is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * Paginated export of the contract state, to rebuild an off-chain mirror or seed a new deployment. Each provider
+ * comes with the records kept beside its listing and the keys it is indexed under
+ */
+
+use crate::*;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderExport {
+    provider: StorageProvider,
+    account_id: Option<AccountId>,     // account controlling the listing
+    pending_claim: Option<AccountId>,  // account waiting for its claim to be reviewed
+    reputation: Option<Reputation>,
+    history: Vec<ProviderHistoryEntry>, // oldest first
+    normalized_price: U128,             // attoFIL/TiB/day, the price index key
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // export up to limit storage providers starting at from_index, in storage order
+    pub fn export_providers(&self, from_index: u64, limit: u64) -> Vec<ProviderExport> {
+        let values = self.storage_providers.values_as_vector();
+        let end = from_index.saturating_add(view_limit(limit) as u64).min(values.len());
+
+        (from_index..end)
+            .filter_map(|index| values.get(index))
+            .map(|provider| ProviderExport {
+                account_id: self.provider_accounts.get(&provider.id),
+                pending_claim: self.provider_claims.get(&provider.id),
+                reputation: self.reputations.get(&provider.id),
+                history: self.provider_history.get(&provider.id).unwrap_or_default(),
+                normalized_price: U128(normalize_price(provider.price.0, provider.price_unit).unwrap_or(u128::MAX)),
+                provider,
+            })
+            .collect()
+    }
+
+    // export up to limit price entries with timestamp >= from_ts, in ascending order
+    // the next page starts after the timestamp of the last entry
+    pub fn export_prices(&self, from_ts: u64, limit: u64) -> Vec<PricePerRegion> {
+        self.price_per_region
+            .range((Bound::Included(from_ts), Bound::Unbounded))
+            .take(view_limit(limit))
+            .map(|(_, ppr)| ppr)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    #[test]
+    fn export_providers() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_ASIA, power: gib(20.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.submit_reputation_events(vec![("f02".to_string(), ReputationEvent::Fault)]);
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f02".to_string());
+
+        let result = contract.export_providers(1, 10);
+        assert_eq!(1, result.len());
+        let export = &result[0];
+        assert_eq!("f02", export.provider.id);
+        assert_eq!(None, export.account_id);
+        assert_eq!(Some(account("miner_near")), export.pending_claim);
+        assert!(export.reputation.is_some());
+        assert_eq!(1, export.history.len());
+        assert_eq!(contract.get_normalized_price("f02".to_string()), Some(export.normalized_price));
+
+        assert_eq!(2, contract.export_providers(0, 10).len());
+        assert!(contract.export_providers(2, 10).is_empty());
+    }
+
+    #[test]
+    fn export_prices() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        for timestamp in [10, 20, 30] {
+            contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp, ..Default::default() });
+        }
+
+        let page = contract.export_prices(0, 2);
+        assert_eq!(vec![10, 20], page.iter().map(|ppr| ppr.timestamp).collect::<Vec<u64>>());
+        let page = contract.export_prices(page[1].timestamp + 1, 2);
+        assert_eq!(vec![30], page.iter().map(|ppr| ppr.timestamp).collect::<Vec<u64>>());
+    }
+}
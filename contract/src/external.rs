@@ -0,0 +1,64 @@
+/*
+ * Cross-contract interface for contracts consuming the FilMarket prices
+ *
+ * A consumer contract depending on this crate can query the latest global price and
+ * receive it in a callback:
+ *
+ *   ext_fil_market::fetch_latest_global_price(fil_market_account_id, 0, GAS_FOR_FETCH)
+ *       .then(ext_self::on_latest_global_price(env::current_account_id(), 0, GAS_FOR_CALLBACK))
+ *
+ * where the callback reads the price with #[callback] price: U128. Lending protocols reading price feeds
+ * call get_price_data instead and read a #[callback] PriceData.
+ */
+
+use near_sdk::ext_contract;
+
+#[ext_contract(ext_fil_market)]
+pub trait FilMarketOracle {
+    // get the latest global storage price in attoFIL, 0 if no price was set
+    fn fetch_latest_global_price(&self) -> near_sdk::json_types::U128;
+
+    // get the latest fresh prices of the given assets, see the oracle module for the asset ids
+    fn get_price_data(&self, asset_ids: Option<Vec<String>>) -> crate::PriceData;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, context, fil, set_context, NEAR};
+    use crate::{FilMarket, PricePerRegion};
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::get_created_receipts;
+    use near_sdk::{testing_env, Gas};
+
+    #[test]
+    fn fetch_latest_global_price() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        assert_eq!(fil(0.0), contract.fetch_latest_global_price());
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 10, ..Default::default() });
+        assert_eq!(fil(0.00034), contract.fetch_latest_global_price());
+    }
+
+    #[test]
+    fn ext_fil_market_promise() {
+        testing_env!(context("carol_near", 0, 0).current_account_id(account("consumer_near")).build());
+
+        let fil_market = account("filmarket_near");
+        ext_fil_market::fetch_latest_global_price(fil_market.clone(), 0, Gas(5_000_000_000_000));
+
+        let receipts = get_created_receipts();
+        assert_eq!(1, receipts.len());
+        assert_eq!(fil_market, receipts[0].receiver_id);
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall { method_name, deposit, gas, .. } => {
+                assert_eq!("fetch_latest_global_price", method_name);
+                assert_eq!(0, *deposit);
+                assert_eq!(Gas(5_000_000_000_000), *gas);
+            }
+            _ => panic!("expected a function call"),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+/*
+ * Feeder bonds: once the owner sets a bond amount, a price feeder must keep at least that much NEAR bonded to
+ * submit data. The owner or governance slashes the bond of a feeder whose submissions are proven wrong, e.g. its
+ * observations keep landing far from the median of the price rounds, which is counted here as evidence; the
+ * slashed NEAR goes to the fee treasury. An honest feeder withdraws its bond after the unbonding period
+ */
+
+use crate::*;
+
+pub const DEFAULT_FEEDER_UNBONDING_PERIOD: u64 = 7 * 24 * 3600;
+// an observation deviating from the median of its round by more than this is counted as an outlier
+pub const FEEDER_OUTLIER_BPS: u128 = 1_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeederBond {
+    bonded: U128,            // yoctoNEAR
+    unbonding: U128,         // yoctoNEAR, withdrawable after unbonding_until
+    unbonding_until: u64,    // epoch time in seconds
+    slashed: U128,           // yoctoNEAR slashed over the lifetime of the bond
+    outlier_rounds: u32,     // price rounds where the feeder's observation was an outlier
+    last_outlier_round: u64, // timestamp of the latest of those rounds, 0 if none
+}
+
+impl Default for FeederBond {
+    fn default() -> Self {
+        FeederBond {
+            bonded: U128(0),
+            unbonding: U128(0),
+            unbonding_until: 0,
+            slashed: U128(0),
+            outlier_rounds: 0,
+            last_outlier_round: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeederBondConfig {
+    bond: U128,            // yoctoNEAR a feeder must keep bonded, 0 when no bond is required
+    unbonding_period: u64, // seconds
+}
+
+// whether observed deviates from median by more than FEEDER_OUTLIER_BPS
+fn is_outlier(observed: u128, median: u128) -> bool {
+    let deviation = observed.abs_diff(median);
+    deviation.saturating_mul(10_000) > median.saturating_mul(FEEDER_OUTLIER_BPS)
+}
+
+impl FilMarket {
+    // fail the call unless a feeder other than the owner keeps the required bond
+    pub(crate) fn assert_feeder_bonded(&self, account_id: &AccountId) {
+        if self.feeder_bond == 0 || self.is_owner(account_id) {
+            return;
+        }
+
+        let bonded = self.feeder_bonds.get(account_id).map_or(0, |bond| bond.bonded.0);
+        require!(
+            bonded >= self.feeder_bond,
+            format!("{}: feeder {} has {} yoctoNEAR bonded, {} required", errors::ERR_FEEDER_NOT_BONDED, account_id, bonded, self.feeder_bond)
+        );
+    }
+
+    // count the observations of a finalized round whose global price is far from the median, for bonded feeders
+    pub(crate) fn record_feeder_outliers(&mut self, timestamp: u64, observations: &[(AccountId, PricePerRegion)], median: &PricePerRegion) {
+        let mut outliers: Vec<&AccountId> = Vec::new();
+        for (account_id, observation) in observations.iter() {
+            if !is_outlier(observation.global.0, median.global.0) {
+                continue;
+            }
+            if let Some(mut bond) = self.feeder_bonds.get(account_id) {
+                bond.outlier_rounds = bond.outlier_rounds.saturating_add(1);
+                bond.last_outlier_round = timestamp;
+                self.feeder_bonds.insert(account_id, &bond);
+                outliers.push(account_id);
+            }
+        }
+
+        if !outliers.is_empty() {
+            self.emit_event("feeder_outliers", json!({
+                "timestamp": timestamp,
+                "feeders": outliers,
+            }));
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // bond the attached NEAR for the caller as a price feeder, the bond must reach the required amount
+    // the storage staking of a new bond is taken from the attached deposit, the rest is bonded
+    #[payable]
+    pub fn register_feeder(&mut self) -> FeederBond {
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+
+        let mut bond = self.feeder_bonds.get(&account_id).unwrap_or_default();
+        self.feeder_bonds.insert(&account_id, &bond);
+        let deposit = self.deposit_after_storage(initial_storage_usage);
+        require!(deposit > 0, "attach the NEAR to bond on top of the storage staking");
+
+        bond.bonded = U128(bond.bonded.0.checked_add(deposit).expect("bond overflows u128"));
+        require!(
+            bond.bonded.0 >= self.feeder_bond,
+            format!("the feeder bond is {} yoctoNEAR", self.feeder_bond)
+        );
+        self.feeder_bonds.insert(&account_id, &bond);
+
+        self.emit_event("feeder_bonded", json!({
+            "account_id": account_id,
+            "amount": U128(deposit),
+            "bonded": bond.bonded,
+        }));
+        bond
+    }
+
+    // start unbonding amount of the caller's bond, restarting the unbonding period of what is already unbonding
+    pub fn unbond_feeder(&mut self, amount: U128) -> FeederBond {
+        let account_id = env::predecessor_account_id();
+        let mut bond = self.feeder_bonds.get(&account_id)
+            .unwrap_or_else(|| panic!("{} has no feeder bond", account_id));
+        require!(amount.0 > 0 && amount.0 <= bond.bonded.0, format!("{} yoctoNEAR are bonded", bond.bonded.0));
+
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        bond.bonded.0 -= amount.0;
+        bond.unbonding.0 += amount.0;
+        bond.unbonding_until = now.saturating_add(self.feeder_unbonding_period);
+        self.feeder_bonds.insert(&account_id, &bond);
+
+        self.emit_event("feeder_unbonding", json!({
+            "account_id": account_id,
+            "amount": amount,
+            "unbonding_until": bond.unbonding_until,
+        }));
+        bond
+    }
+
+    // withdraw the caller's unbonded NEAR once the unbonding period is over
+    // a bond left empty is removed and the staking of its storage is paid back with the withdrawal
+    pub fn withdraw_feeder_bond(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let mut bond = self.feeder_bonds.get(&account_id)
+            .unwrap_or_else(|| panic!("{} has no feeder bond", account_id));
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        require!(bond.unbonding.0 > 0, "nothing is unbonding");
+        require!(now >= bond.unbonding_until, format!("the bond is unbonding until {}", bond.unbonding_until));
+
+        let amount = bond.unbonding;
+        bond.unbonding = U128(0);
+        let initial_storage_usage = env::storage_usage();
+        if bond.bonded.0 == 0 {
+            self.feeder_bonds.remove(&account_id);
+        } else {
+            self.feeder_bonds.insert(&account_id, &bond);
+        }
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+
+        self.emit_event("feeder_bond_withdrawn", json!({
+            "account_id": account_id,
+            "amount": amount,
+        }));
+        Promise::new(account_id).transfer(amount.0 + env::storage_byte_cost() * Balance::from(freed))
+    }
+
+    // slash up to amount of a feeder's bond, unbonding NEAR included, into the fee treasury, returns the amount slashed
+    pub fn slash_feeder(&mut self, account_id: AccountId, amount: U128, reason: String) -> U128 {
+        self.assert_config_authority();
+        let mut bond = self.feeder_bonds.get(&account_id)
+            .unwrap_or_else(|| panic!("{} has no feeder bond", account_id));
+
+        let from_bonded = amount.0.min(bond.bonded.0);
+        let from_unbonding = (amount.0 - from_bonded).min(bond.unbonding.0);
+        let slashed = from_bonded + from_unbonding;
+        require!(slashed > 0, format!("{} has nothing bonded to slash", account_id));
+
+        bond.bonded.0 -= from_bonded;
+        bond.unbonding.0 -= from_unbonding;
+        bond.slashed = U128(bond.slashed.0.saturating_add(slashed));
+        self.feeder_bonds.insert(&account_id, &bond);
+        self.fee_treasury = self.fee_treasury.checked_add(slashed).expect("fee treasury overflows u128");
+
+        self.emit_event("feeder_slashed", json!({
+            "account_id": account_id,
+            "amount": U128(slashed),
+            "reason": reason,
+        }));
+        U128(slashed)
+    }
+
+    // set the bond a price feeder must keep, 0 to not require one, and the unbonding period in seconds
+    pub fn set_feeder_bond(&mut self, bond: U128, unbonding_period: u64) {
+        self.assert_config_authority();
+
+        self.feeder_bond = bond.0;
+        self.feeder_unbonding_period = unbonding_period;
+        self.emit_event("feeder_bond_set", json!({
+            "bond": bond,
+            "unbonding_period": unbonding_period,
+        }));
+    }
+
+    // get the bond a price feeder must keep and the unbonding period
+    pub fn get_feeder_bond_config(&self) -> FeederBondConfig {
+        FeederBondConfig { bond: U128(self.feeder_bond), unbonding_period: self.feeder_unbonding_period }
+    }
+
+    // get the bond of a feeder, None if it never bonded
+    pub fn get_feeder_bond(&self, account_id: AccountId) -> Option<FeederBond> {
+        self.feeder_bonds.get(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, set_context, transfers, NEAR};
+
+    // carol is the owner, feeder_near holds the PriceFeeder role and 10 NEAR are required
+    fn bonded_contract() -> FilMarket {
+        set_context("carol_near", 0, 1_000);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("feeder_near"), Role::PriceFeeder);
+        contract.set_feeder_bond(U128(10 * NEAR), 100);
+        contract
+    }
+
+    // bond 10 NEAR for the feeder, with up to 0.01 NEAR on top left once the storage staking is taken
+    fn register(contract: &mut FilMarket, feeder: &str) -> Balance {
+        set_context(feeder, 10 * NEAR + NEAR / 100, 1_000);
+        let bonded = contract.register_feeder().bonded.0;
+        assert!((10 * NEAR..=10 * NEAR + NEAR / 100).contains(&bonded));
+        bonded
+    }
+
+    fn observation(global: f64) -> PricePerRegion {
+        PricePerRegion { global: fil(global), timestamp: 900, ..Default::default() }
+    }
+
+    #[test]
+    fn bond_unbond_and_withdraw() {
+        let mut contract = bonded_contract();
+
+        let bonded = register(&mut contract, "feeder_near");
+        contract.set_price_per_region(observation(0.2));
+
+        set_context("feeder_near", 0, 1_000);
+        let bond = contract.unbond_feeder(U128(4 * NEAR));
+        assert_eq!((U128(bonded - 4 * NEAR), U128(4 * NEAR), 1_100), (bond.bonded, bond.unbonding, bond.unbonding_until));
+
+        set_context("feeder_near", 0, 1_100);
+        contract.withdraw_feeder_bond();
+        assert_eq!(U128(0), contract.get_feeder_bond(account("feeder_near")).unwrap().unbonding);
+    }
+
+    #[test]
+    fn emptied_bond_is_removed() {
+        let mut contract = bonded_contract();
+        let bonded = register(&mut contract, "feeder_near");
+
+        set_context("feeder_near", 0, 1_000);
+        contract.unbond_feeder(U128(bonded));
+        set_context("feeder_near", 0, 1_100);
+        contract.withdraw_feeder_bond();
+        assert!(contract.get_feeder_bond(account("feeder_near")).is_none());
+
+        // the storage staking of the bond is paid back with it
+        let withdrawn: Balance = transfers().iter().map(|(_, amount)| amount).sum();
+        assert!(withdrawn > bonded);
+    }
+
+    #[test]
+    #[should_panic(expected = "the feeder bond is 10000000000000000000000000 yoctoNEAR")]
+    fn bond_without_storage_deposit() {
+        let mut contract = bonded_contract();
+
+        // the attached deposit must cover the storage of the bond on top of the bond itself
+        set_context("feeder_near", 10 * NEAR, 1_000);
+        contract.register_feeder();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FEEDER_NOT_BONDED: feeder feeder_near has 60")]
+    fn feeder_below_bond() {
+        let mut contract = bonded_contract();
+        register(&mut contract, "feeder_near");
+
+        set_context("feeder_near", 0, 1_000);
+        contract.unbond_feeder(U128(4 * NEAR));
+        contract.set_price_per_region(observation(0.2));
+    }
+
+    #[test]
+    #[should_panic(expected = "the bond is unbonding until 1150")]
+    fn withdraw_before_unbonding_period() {
+        let mut contract = bonded_contract();
+        register(&mut contract, "feeder_near");
+
+        set_context("feeder_near", 0, 1_050);
+        contract.unbond_feeder(U128(NEAR));
+        contract.withdraw_feeder_bond();
+    }
+
+    #[test]
+    fn slash_outlier_feeder() {
+        let mut contract = bonded_contract();
+        contract.set_feeder_quorum(3);
+        contract.add_role(account("other_near"), Role::PriceFeeder);
+        for (feeder, global) in [("feeder_near", 0.5), ("other_near", 0.2)] {
+            register(&mut contract, feeder);
+            contract.submit_price_observation(observation(global));
+        }
+        set_context("carol_near", NEAR, 1_000);
+        contract.submit_price_observation(observation(0.21));
+
+        let bond = contract.get_feeder_bond(account("feeder_near")).unwrap();
+        let bonded = bond.bonded.0;
+        assert_eq!((1, 900), (bond.outlier_rounds, bond.last_outlier_round));
+        assert_eq!(0, contract.get_feeder_bond(account("other_near")).unwrap().outlier_rounds);
+
+        // the slash takes what is bonded first, then what is unbonding
+        set_context("feeder_near", 0, 1_000);
+        contract.unbond_feeder(U128(8 * NEAR));
+        set_context("carol_near", 0, 1_000);
+        assert_eq!(U128(5 * NEAR), contract.slash_feeder(account("feeder_near"), U128(5 * NEAR), "outlier rounds".to_string()));
+
+        let bond = contract.get_feeder_bond(account("feeder_near")).unwrap();
+        assert_eq!((U128(0), U128(bonded - 5 * NEAR), U128(5 * NEAR)), (bond.bonded, bond.unbonding, bond.slashed));
+        assert_eq!(U128(5 * NEAR), contract.get_fee_treasury().balance);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn slash_by_other_account() {
+        let mut contract = bonded_contract();
+        register(&mut contract, "feeder_near");
+
+        contract.slash_feeder(account("feeder_near"), U128(NEAR), "".to_string());
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * DAO governance of the configuration: once a governance account is set (e.g. a Sputnik DAO), only it can change the
+ * price policy knobs and the feeder set, the owner keeps the other privileged methods and feeders keep pushing data
+ */
+
+use crate::*;
+
+impl FilMarket {
+    // fail the call unless the predecessor is the governance account, or the owner when governance is off
+    pub(crate) fn assert_config_authority(&self) {
+        let account_id = env::predecessor_account_id();
+
+        match &self.governance {
+            Some(governance) => {
+                require!(
+                    account_id == *governance,
+                    format!("{}: account_id {} is not the governance account", errors::ERR_NOT_GOVERNANCE, account_id)
+                );
+                self.mark_privileged(account_id);
+            }
+            None => self.assert_owner(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // hand the configuration to a governance account, None gives it back to the owner
+    // once set, only the governance account can change it
+    pub fn set_governance(&mut self, governance: Option<AccountId>) {
+        self.assert_config_authority();
+        if self.governance == governance {
+            return;
+        }
+
+        self.governance = governance.clone();
+        self.emit_event("governance_set", json!({ "governance": governance }));
+    }
+
+    // get the account governing the configuration, None if the owner does
+    pub fn get_governance(&self) -> Option<AccountId> {
+        self.governance.clone()
+    }
+
+    // get the accounts holding the PriceFeeder role, paginated
+    pub fn get_feeders(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.get_role_holders(Role::PriceFeeder, from_index, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, set_context, NEAR};
+
+    #[test]
+    fn governance_changes_configuration() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+        contract.set_governance(Some(account("dao_near")));
+        assert_eq!(Some(account("dao_near")), contract.get_governance());
+
+        set_context("dao_near", 0, 0);
+        contract.set_price_retention(3_600);
+        contract.set_feeder_quorum(2);
+        contract.set_price_guards(500, true);
+        contract.add_role(account("feeder_near"), Role::PriceFeeder);
+        assert_eq!(3_600, contract.get_price_retention());
+        assert_eq!(2, contract.get_feeder_quorum());
+        assert_eq!(500, contract.get_price_guards().max_deviation_bps);
+        assert_eq!(vec![account("feeder_near")], contract.get_feeders(0, 10));
+
+        // routine data pushes stay with the feeders
+        set_context("feeder_near", NEAR, 0);
+        contract.submit_price_observation(PricePerRegion { timestamp: 10, ..Default::default() });
+        assert_eq!(1, contract.get_price_round(10).len());
+
+        set_context("dao_near", 0, 0);
+        contract.set_governance(None);
+        set_context("carol_near", 0, 0);
+        contract.set_price_retention(0);
+        assert_eq!(0, contract.get_price_retention());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE: account_id carol_near is not the governance account")]
+    fn owner_cannot_change_governed_configuration() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+        contract.set_governance(Some(account("dao_near")));
+
+        contract.set_max_price_age(60);
+    }
+
+    #[test]
+    fn owner_keeps_other_methods() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+        contract.set_governance(Some(account("dao_near")));
+
+        contract.deny_provider("f01".to_string());
+        assert_eq!(1, contract.get_denied_providers(0, 10).len());
+    }
+}
@@ -2,51 +2,572 @@
  * FilMarket contract
  */
 
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Bound;
+
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
-use near_sdk::{env, near_bindgen, PanicOnDefault};
+use near_sdk::collections::{TreeMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, require, AccountId, Balance, BlockHeight, PanicOnDefault, Promise, PublicKey, StorageUsage};
 use near_sdk::serde::Serialize;
 use near_sdk::serde::Deserialize;
+use near_sdk::serde_json::json;
+
+mod active_history;
+mod admin_log;
+mod batches;
+mod conversions;
+mod council;
+mod deals;
+mod decimal;
+mod denylist;
+pub mod errors;
+mod escrow;
+mod events;
+mod feeder_bonds;
+mod export;
+mod governance;
+mod listing_fees;
+pub mod external;
+mod migration;
+mod oracle;
+mod order_book;
+mod ownership;
+mod pause;
+mod price_bands;
+mod price_candidates;
+mod price_guards;
+mod price_rounds;
+mod price_stats;
+mod provider_claims;
+mod provider_history;
+mod provider_ids;
+mod provider_import;
+mod provider_index;
+mod provider_metadata;
+mod provider_status;
+mod provider_sync;
+mod provider_tiers;
+mod quotes;
+mod regions;
+mod reputation;
+mod retention;
+mod retrieval_prices;
+mod reviews;
+mod roles;
+mod signed_batches;
+mod sla;
+mod source_metadata;
+mod staleness;
+mod storage_keys;
+mod subscriptions;
+mod tokens;
+mod twap;
+
+pub use admin_log::AdminLogEntry;
+pub use batches::BatchProgress;
+pub use conversions::{ConvertedPrice, Currency, PowerUnit, PriceUnit};
+pub use council::{Council, Proposal};
+pub use deals::{Deal, DealState, Offer, StorageRequest};
+pub use export::ProviderExport;
+pub use feeder_bonds::{FeederBond, FeederBondConfig};
+pub use listing_fees::{FeeSchedule, FeeTreasury};
+pub use external::ext_fil_market;
+pub use migration::StateVersion;
+pub use oracle::{AssetOptionalPrice, OraclePrice, PriceData};
+pub use order_book::{BookSummary, Order, OrderBook, OrderSide};
+pub use pause::Feature;
+pub use price_bands::{PriceBand, PriceBandStr};
+pub use price_guards::PriceGuards;
+pub use price_stats::PriceStats;
+pub use provider_history::ProviderHistoryEntry;
+pub use provider_ids::FilecoinNetwork;
+pub use provider_import::ProviderRecord;
+pub use provider_metadata::ProviderMetadata;
+pub use provider_status::ProviderStatus;
+pub use provider_sync::{ProviderRemovals, ProviderUpdates};
+pub use provider_tiers::{ProviderTier, TierConfig, TierThresholds};
+pub use quotes::StorageQuote;
+pub use regions::{Region, RegionSummary};
+pub use reputation::{Reputation, ReputationEvent};
+pub use retrieval_prices::RetrievalPrice;
+pub use reviews::{Review, ReviewSummary};
+pub use roles::Role;
+pub use sla::{ProviderSla, SlaReport};
+pub use source_metadata::{ContractSourceMetadata, Standard};
+pub use staleness::{CheckedPrice, PriceStatus};
+pub use subscriptions::Subscription;
+pub use twap::Twap;
+use storage_keys::StorageKey;
+
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+const MAX_ID_LEN: usize = 128;
+const MAX_LABEL_LEN: usize = 64;
+const DEFAULT_MAX_FUTURE_SKEW: u64 = 3_600; // seconds
+const BYTES_PER_GIB: u128 = 1_073_741_824;
+// paginated views return at most this many items whatever limit is requested, callers must
+// advance their cursor by the number of items actually returned
+const MAX_VIEW_LIMIT: u64 = 100;
+// provider sets above this size may not fit in the gas of a single full scan
+const LARGE_PROVIDER_SET: u64 = 2_000;
+// maximum number of region assignments per reassign_regions call
+const MAX_REASSIGN_BATCH: usize = 500;
+
+// regions registered at initialization, more are added with add_region
+const REGION_NORTH_AMERICA: u8 = 1;
+const REGION_EUROPE: u8 = 2;
+const REGION_ASIA: u8 = 3;
+const REGION_OTHER: u8 = 4;
+const REGION_SOUTH_AMERICA: u8 = 5;
+const REGION_AFRICA: u8 = 6;
+
+const PRICE_UNIT_FIL_PER_GIB_PER_EPOCH: u8 = 0;
+const PRICE_UNIT_FIL_PER_TIB_PER_DAY: u8 = 1;
+const EPOCHS_PER_DAY: u128 = 2_880; // 30 second epochs
+const GIB_PER_TIB: u128 = 1_024;
+const BYTES_PER_TIB: u128 = BYTES_PER_GIB * GIB_PER_TIB;
+
+// number of active storage providers per region id
+#[derive(Default, Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActivePerRegion(BTreeMap<u8, u32>);
+
+impl ActivePerRegion {
+    // the active providers of the given region, 0 if none was counted
+    fn get(&self, region: u8) -> u32 {
+        self.0.get(&region).copied().unwrap_or(0)
+    }
+
+    // count one more active provider in the given region
+    fn increment(&mut self, region: u8) {
+        *self.0.entry(region).or_insert(0) += 1;
+    }
+
+    // count one less active provider in the given region, never going below 0
+    fn decrement(&mut self, region: u8) {
+        if let Some(count) = self.0.get_mut(&region) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
 
-#[derive(Default, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+// PricePerRegion with prices as decimal strings, parsed exactly instead of going through JSON floats
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct ActivePerRegion {
-    europe: u32,
-    asia: u32,
-    north_america: u32,
-    other: u32,
+pub struct PricePerRegionStr {
+    prices: BTreeMap<u8, String>, // FIL per region id
+    global: String,               // FIL
+    fil_price: String,            // USD
+    power: U128,                  // network power in bytes
+    timestamp: u64,               // epoch time in seconds
+    #[serde(default)]
+    verified_prices: BTreeMap<u8, String>, // FIL per region id for FIL+ verified deals
+    #[serde(default)]
+    verified_global: Option<String>,       // FIL for FIL+ verified deals
+    #[serde(default)]
+    bands: BTreeMap<u8, PriceBandStr>,     // spread of the provider prices per region id
 }
 
-#[derive(Default, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+// total power in bytes per region id
+#[derive(Default, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PowerPerRegion(BTreeMap<u8, U128>);
+
+impl PowerPerRegion {
+    // add a provider's power to its region
+    fn add(&mut self, region: u8, power: u128) {
+        let total = self.0.entry(region).or_insert(U128(0));
+        total.0 = total.0.checked_add(power).expect("total power overflows u128");
+    }
+
+    // take a provider's power out of its region, never going below 0
+    fn subtract(&mut self, region: u8, power: u128) {
+        if let Some(total) = self.0.get_mut(&region) {
+            total.0 = total.0.saturating_sub(power);
+        }
+    }
+
+    // the power of every region
+    fn total(&self) -> u128 {
+        checked_power_sum(self.0.values().map(|power| power.0))
+    }
+}
+
+// storage providers per region id
+#[derive(Default, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RegionGroups(BTreeMap<u8, Vec<StorageProvider>>);
+
+impl RegionGroups {
+    // the bucket of the given region
+    fn bucket(&mut self, region: u8) -> &mut Vec<StorageProvider> {
+        self.0.entry(region).or_default()
+    }
+}
+
+// prices are fixed-point integers: attoFIL (10^-18 FIL) and USD scaled by 10^18
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct PricePerRegion {
-    europe: f64,        // FIL
-    asia: f64,          // FIL
-    north_america: f64, // FIL
-    other: f64,         // FIL
-    global: f64,        // FIL
-    fil_price: f64,     // USD
-    power: u128,        // network power in TiB
-    timestamp: u64,     // epoch time in seconds
+    prices: BTreeMap<u8, U128>, // attoFIL per region id
+    global: U128,               // attoFIL
+    fil_price: U128,            // USD scaled by 10^18
+    power: U128,                // network power in bytes
+    timestamp: u64,             // epoch time in seconds
+    #[serde(default)]
+    verified_prices: BTreeMap<u8, U128>, // attoFIL per region id for FIL+ verified deals
+    #[serde(default)]
+    verified_global: Option<U128>,       // attoFIL for FIL+ verified deals
+    #[serde(default)]
+    bands: BTreeMap<u8, PriceBand>,      // spread of the provider prices behind the average per region id
+}
+
+impl Default for PricePerRegion {
+    fn default() -> Self {
+        PricePerRegion {
+            prices: BTreeMap::new(),
+            global: U128(0),
+            fil_price: U128(0),
+            power: U128(0),
+            timestamp: 0,
+            verified_prices: BTreeMap::new(),
+            verified_global: None,
+            bands: BTreeMap::new(),
+        }
+    }
 }
 
-#[derive(Default, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct StorageProvider {
     id: String,
-    region: u8,  // "North America":1, "Europe":2, "Asia":3, "Other":4 
-    power: f64,  // GiB
-    price: f64,  // FIL
+    region: u8,  // id of a registered region, see get_regions
+    power: U128, // bytes
+    price: U128, // attoFIL per price_unit
+    label: Option<String>, // human-readable name, at most MAX_LABEL_LEN bytes
+    #[serde(default)]
+    verified: bool, // vetted by the owner, only set through set_provider_verified
+    #[serde(default)]
+    price_unit: u8, // unit of price, FIL/GiB/epoch:0, FIL/TiB/day:1
+    #[serde(default)]
+    last_updated: u64, // block timestamp in nanoseconds of the latest change, set by the contract
+    #[serde(default)]
+    reputation: u32, // 0-100 score computed from reputation events, set by the contract
+    #[serde(default)]
+    metadata: ProviderMetadata, // only set through update_provider_metadata
+    #[serde(default)]
+    verified_price: Option<U128>, // attoFIL per price_unit for FIL+ verified deals, None if not offered
+    #[serde(default)]
+    status: ProviderStatus, // only set through set_provider_status, only Active providers are counted
+    #[serde(default)]
+    retrieval_price: Option<U128>, // attoFIL per GiB retrieved, None if not quoted
+    #[serde(default)]
+    listed_at: u64, // block timestamp in nanoseconds of the first listing, 0 if listed before it was tracked
+    #[serde(default)]
+    tier: ProviderTier, // set by the contract, see provider_tiers
+}
+
+impl Default for StorageProvider {
+    fn default() -> Self {
+        StorageProvider {
+            id: "".to_string(),
+            region: 0,
+            power: U128(0),
+            price: U128(0),
+            label: None,
+            verified: false,
+            price_unit: PRICE_UNIT_FIL_PER_GIB_PER_EPOCH,
+            last_updated: 0,
+            reputation: reputation::NEUTRAL_REPUTATION,
+            metadata: ProviderMetadata::default(),
+            verified_price: None,
+            status: ProviderStatus::Active,
+            retrieval_price: None,
+            listed_at: 0,
+            tier: ProviderTier::Bronze,
+        }
+    }
+}
+
+// the outcome of a batch of storage provider writes, per entry of the batch
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchResult {
+    added: u32,   // entries inserting a new provider
+    updated: u32, // entries updating a stored provider
+    removed: u32, // providers deleted
+    skipped: u32, // entries rejected, one error each
+    errors: Vec<(String, String)>, // ids of the rejected entries with the reason, in batch order
+}
+
+impl BatchResult {
+    fn skip(&mut self, id: &str, reason: String) {
+        self.skipped += 1;
+        self.errors.push((id.to_string(), reason));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceSummary {
+    min: U128,  // attoFIL
+    max: U128,  // attoFIL
+    mean: U128, // attoFIL, rounded down
+    count: u64,
+}
+
+impl PriceSummary {
+    // summarize the given prices in a single pass, all zeros when there are none
+    fn from_prices<I: Iterator<Item = u128>>(prices: I) -> Self {
+        let mut summary = PriceSummary { min: U128(0), max: U128(0), mean: U128(0), count: 0 };
+        let mut total: u128 = 0;
+
+        for price in prices {
+            if summary.count == 0 || price < summary.min.0 {
+                summary.min = U128(price);
+            }
+            if summary.count == 0 || price > summary.max.0 {
+                summary.max = U128(price);
+            }
+            total = total.checked_add(price).expect("total price overflows u128");
+            summary.count += 1;
+        }
+
+        if summary.count > 0 {
+            summary.mean = U128(total / summary.count as u128);
+        }
+
+        summary
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GlobalPriceStats {
+    min: U128,     // attoFIL
+    max: U128,     // attoFIL
+    mean: U128,    // attoFIL, rounded down
+    count: u64,
+    first_ts: u64, // epoch time in seconds
+    last_ts: u64,  // epoch time in seconds
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceHistoryMeta {
+    count: u64,
+    earliest_ts: u64, // epoch time in seconds
+    latest_ts: u64,   // epoch time in seconds
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractConfig {
+    max_view_limit: u64,
+    max_id_len: u32,
+    max_label_len: u32,
+    min_price: U128,      // attoFIL
+    max_future_skew: u64, // seconds
+    price_candidate_deposit: U128, // yoctoNEAR
+    provider_history_depth: u32,
+    max_batch_items: u32,
+    price_retention: u64, // seconds, 0 keeps the whole history
+    feeder_quorum: u32,
+    max_price_age: u64, // seconds
+    paused: bool,
+    paused_features: Vec<Feature>,
+    price_guards: PriceGuards,
+    governance: Option<AccountId>,
+    filecoin_network: FilecoinNetwork,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderPage {
+    storage_providers: Vec<StorageProvider>, // ordered by id
+    next_cursor: Option<String>,             // start_id of the next page, None on the last page
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    storage_usage: StorageUsage, // bytes
+    locked: U128,                // yoctoNEAR staked for the storage
+    available: U128,             // yoctoNEAR of the account balance not staked for storage
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Dashboard {
+    latest_price_per_region: Option<PricePerRegion>,
+    active_per_region: ActivePerRegion,
+    storage_providers_count: u64,
+    latest_timestamp: u64,
+    total_power: U128,              // bytes, of the Active storage providers
+    last_updated: u64,              // epoch time in seconds of the latest price entry or heartbeat, 0 if never
+    price_status: PriceStatus,      // freshness of the latest price, see get_latest_price_checked
+    price_age_seconds: Option<u64>, // since last_updated, None without a price
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSummary {
+    provider_count: u64,
+    price_entry_count: u64,
+    total_power: U128, // bytes
+    latest_timestamp: u64,
+    owner: AccountId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HealthStatus {
+    provider_count: u64,
+    has_prices: bool,
+    latest_price_age_seconds: Option<u64>, // None without prices
+    paused: bool,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct FilMarket {
     storage_providers: UnorderedMap<String, StorageProvider>,
-    price_per_region: UnorderedMap<u64, PricePerRegion>,
+    price_per_region: TreeMap<u64, PricePerRegion>, // ordered by timestamp
     active_per_region: ActivePerRegion,
     latest_timestamp: u64,
-    owner: String,
+    owner: AccountId,
+    min_price: u128, // attoFIL, providers priced below are not stored, 0 disables the check
+    pending_owner: Option<AccountId>,
+    owner_history: Vector<(AccountId, BlockHeight)>, // every owner and the block height it took over
+    max_future_skew: u64, // seconds a price timestamp may be ahead of the block timestamp
+    event_seq: u64, // sequence number of the latest event
+    pending_prices: UnorderedMap<(u64, AccountId), PricePerRegion>, // price candidates by timestamp and submitter
+    roles: UnorderedMap<AccountId, Vec<Role>>, // roles granted by the owner
+    provider_claims: UnorderedMap<String, AccountId>, // pending claims by storage provider id
+    provider_accounts: UnorderedMap<String, AccountId>, // accounts controlling their storage provider listing
+    provider_history: UnorderedMap<String, Vec<ProviderHistoryEntry>>, // latest prices and power per storage provider, oldest first
+    provider_history_depth: u32, // history entries kept per storage provider
+    storage_requests: UnorderedMap<u64, StorageRequest>, // storage requests posted by clients, by id
+    offers: UnorderedMap<u64, Offer>, // offers on open storage requests, by id
+    deals: UnorderedMap<u64, Deal>, // deals recorded by accepted offers, by id
+    next_request_id: u64,
+    next_offer_id: u64,
+    next_deal_id: u64,
+    deal_escrow: UnorderedMap<u64, Balance>, // yoctoNEAR held per deal until released or refunded
+    payment_token: Option<AccountId>, // NEP-141 token accepted for payments, None disables token payments
+    token_balances: UnorderedMap<AccountId, Balance>, // payment tokens deposited or earned, withdrawable by their account
+    deal_token_escrow: UnorderedMap<u64, Balance>, // payment tokens held per deal until released or refunded
+    reputations: UnorderedMap<String, Reputation>, // reputation counters per storage provider, kept when the listing is deleted
+    regions: TreeMap<u8, String>, // registered region names by id
+    region_index: UnorderedMap<u8, UnorderedSet<String>>, // storage provider ids per region
+    price_index: TreeMap<(u8, u128, String), ()>, // storage providers by region and normalized price
+    power_index: TreeMap<(u8, u128, String), ()>, // storage providers by region and decreasing power
+    paused: bool, // every feature is paused
+    paused_features: Vec<Feature>, // features paused one by one
+    batches: UnorderedMap<String, BatchProgress>, // unfinished chunked updates by batch id
+    price_retention: u64, // seconds price entries are kept, 0 keeps the whole history
+    price_rounds: UnorderedMap<u64, Vec<(AccountId, PricePerRegion)>>, // observations of open price rounds by timestamp
+    feeder_quorum: u32, // observations that finalize a price round
+    max_price_age: u64, // seconds the latest price stays fresh
+    last_heartbeat: u64, // epoch time in seconds the latest price was last confirmed, 0 if never
+    subscribers: UnorderedMap<AccountId, Subscription>, // contracts receiving every price entry written
+    denied_providers: UnorderedMap<String, Option<StorageProvider>>, // denied ids with their listing kept aside
+    price_guards: PriceGuards,
+    power_per_region: PowerPerRegion, // maintained with active_per_region on every provider update
+    active_history: TreeMap<u64, ActivePerRegion>, // snapshots of active_per_region by seconds
+    admin_log: Vector<AdminLogEntry>, // ring buffer of the latest privileged actions
+    admin_log_len: u64,               // privileged actions recorded, including the overwritten ones
+    council: Option<Council>,         // owns the contract instead of the owner account when set
+    proposals: UnorderedMap<u64, Proposal>, // pending council proposals
+    next_proposal_id: u64,
+    governance: Option<AccountId>, // changes the configuration instead of the owner when set
+    fee_schedule: FeeSchedule,
+    listing_paid_until: UnorderedMap<String, u64>, // seconds, by storage provider id
+    fee_treasury: Balance,        // listing fees paid in NEAR
+    fee_treasury_tokens: Balance, // listing fees paid in payment tokens
+    orders: UnorderedMap<u64, Order>, // open asks and bids by id
+    order_book: TreeMap<(u8, u8, u128, u64), ()>, // open orders by region, side and best price
+    next_order_id: u64,
+    filecoin_network: FilecoinNetwork, // network of the storage provider id addresses
+    code_hash: Option<near_sdk::json_types::Base58CryptoHash>, // of the deployed code, recorded by the owner
+    removed_providers: UnorderedMap<String, u64>, // block timestamp in nanoseconds of removal, by storage provider id
+    retrieval_prices: TreeMap<u64, RetrievalPrice>, // average retrieval price per region by seconds
+    latest_retrieval_timestamp: u64,
+    provider_sla: UnorderedMap<String, sla::ProviderSlaRecord>, // reported reliability by storage provider id
+    feeder_bonds: UnorderedMap<AccountId, FeederBond>,
+    feeder_bond: Balance,         // yoctoNEAR a price feeder must keep bonded, 0 when no bond is required
+    feeder_unbonding_period: u64, // seconds
+    tier_config: TierConfig,
+    reviews: UnorderedMap<String, Vec<Review>>, // client reviews by storage provider id, oldest first
+    last_review_at: UnorderedMap<AccountId, u64>, // epoch time in seconds of the latest unverified review
+    review_deposit: Balance, // yoctoNEAR held by an unverified review
+    provider_ids: TreeMap<String, ()>, // ids of the listed storage providers in order, for cursor pagination
+    updated_index: TreeMap<(u64, String), ()>, // listed storage providers by last_updated and id
+    removed_index: TreeMap<(u64, String), ()>, // removed storage providers by removal timestamp and id
+    oracle_key: Option<PublicKey>, // ed25519 key signing the oracle batches
+    oracle_nonce: u64,             // nonce of the latest oracle batch accepted
+    approved_subscribers: UnorderedSet<AccountId>, // contracts the owner allowed to subscribe
+    #[borsh_skip]
+    admin_log_storage: StorageUsage, // bytes the admin log grew by in this call, paid by the contract
+    #[borsh_skip]
+    privileged_caller: Cell<Option<AccountId>>, // the predecessor once it passed a privileged check in this call
+}
+
+impl FilMarket {
+    // require the attached deposit to cover the storage added since initial_storage_usage, returns the rest
+    // note: the admin log is not charged to the caller, and the contract pays the storage of the calls it makes on
+    // itself, as executed council proposals are
+    fn deposit_after_storage(&mut self, initial_storage_usage: StorageUsage) -> Balance {
+        if env::predecessor_account_id() == env::current_account_id() {
+            self.admin_log_storage = 0;
+            return env::attached_deposit();
+        }
+
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage).saturating_sub(self.admin_log_storage);
+        self.admin_log_storage = 0;
+        let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+        let attached_deposit = env::attached_deposit();
+
+        require!(
+            attached_deposit >= required_cost,
+            format!("must attach {} yoctoNEAR to cover storage of {} bytes", required_cost, storage_used)
+        );
+
+        attached_deposit - required_cost
+    }
+
+    // require the attached deposit to cover the storage added since initial_storage_usage and refund the rest
+    // the staking of the storage freed stays with the contract, the rows were paid by whoever added them
+    fn refund_deposit(&mut self, initial_storage_usage: StorageUsage) {
+        let refund = self.deposit_after_storage(initial_storage_usage);
+        if refund > 0 && env::predecessor_account_id() != env::current_account_id() {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+}
+
+// convert a price in the given unit to attoFIL/TiB/day, None for an unknown unit
+fn normalize_price(price: u128, price_unit: u8) -> Option<u128> {
+    match price_unit {
+        PRICE_UNIT_FIL_PER_GIB_PER_EPOCH => {
+            Some(price.checked_mul(GIB_PER_TIB * EPOCHS_PER_DAY).expect("normalized price overflows u128"))
+        }
+        PRICE_UNIT_FIL_PER_TIB_PER_DAY => Some(price),
+        _ => None,
+    }
+}
+
+// clamp a requested page size to MAX_VIEW_LIMIT
+fn view_limit(limit: u64) -> usize {
+    limit.min(MAX_VIEW_LIMIT) as usize
+}
+
+// sum power values, panicking instead of wrapping around on overflow
+fn checked_power_sum<I: Iterator<Item = u128>>(mut powers: I) -> u128 {
+    powers
+        .try_fold(0u128, |total, power| total.checked_add(power))
+        .expect("total power overflows u128")
 }
 
 #[near_bindgen]
@@ -54,295 +575,2491 @@ impl FilMarket {
     #[init]
     pub fn new() -> Self {
         assert!(!env::state_exists(), "The contract is already initialized");
-        Self {
-            storage_providers: UnorderedMap::new(b"a".to_vec()),
-            price_per_region: UnorderedMap::new(b"b".to_vec()),
-            active_per_region: ActivePerRegion {
-                europe: 0, 
-                asia: 0, 
-                north_america: 0, 
-                other: 0
-            },
+        migration::write_state_version(migration::CURRENT_STATE_VERSION);
+        let mut this = Self {
+            storage_providers: UnorderedMap::new(StorageKey::StorageProviders),
+            price_per_region: TreeMap::new(StorageKey::PricePerRegion),
+            active_per_region: ActivePerRegion::default(),
             latest_timestamp: 0,
-            owner: env::predecessor_account_id().to_string(),
-        }
+            owner: env::predecessor_account_id(),
+            min_price: 0,
+            pending_owner: None,
+            owner_history: Vector::new(StorageKey::OwnerHistory),
+            max_future_skew: DEFAULT_MAX_FUTURE_SKEW,
+            event_seq: 0,
+            pending_prices: UnorderedMap::new(StorageKey::PendingPrices),
+            roles: UnorderedMap::new(StorageKey::Roles),
+            provider_claims: UnorderedMap::new(StorageKey::ProviderClaims),
+            provider_accounts: UnorderedMap::new(StorageKey::ProviderAccounts),
+            provider_history: UnorderedMap::new(StorageKey::ProviderHistory),
+            provider_history_depth: provider_history::DEFAULT_PROVIDER_HISTORY_DEPTH,
+            storage_requests: UnorderedMap::new(StorageKey::StorageRequests),
+            offers: UnorderedMap::new(StorageKey::Offers),
+            deals: UnorderedMap::new(StorageKey::Deals),
+            next_request_id: 0,
+            next_offer_id: 0,
+            next_deal_id: 0,
+            deal_escrow: UnorderedMap::new(StorageKey::DealEscrow),
+            payment_token: None,
+            token_balances: UnorderedMap::new(StorageKey::TokenBalances),
+            deal_token_escrow: UnorderedMap::new(StorageKey::DealTokenEscrow),
+            reputations: UnorderedMap::new(StorageKey::Reputations),
+            regions: regions::default_regions(),
+            region_index: UnorderedMap::new(StorageKey::RegionIndex),
+            price_index: TreeMap::new(StorageKey::PriceIndex),
+            power_index: TreeMap::new(StorageKey::PowerIndex),
+            paused: false,
+            paused_features: Vec::new(),
+            batches: UnorderedMap::new(StorageKey::Batches),
+            price_retention: 0,
+            price_rounds: UnorderedMap::new(StorageKey::PriceRounds),
+            feeder_quorum: price_rounds::DEFAULT_FEEDER_QUORUM,
+            max_price_age: staleness::DEFAULT_MAX_PRICE_AGE,
+            last_heartbeat: 0,
+            subscribers: UnorderedMap::new(StorageKey::Subscribers),
+            denied_providers: UnorderedMap::new(StorageKey::DeniedProviders),
+            price_guards: PriceGuards::default(),
+            power_per_region: PowerPerRegion::default(),
+            active_history: TreeMap::new(StorageKey::ActiveHistory),
+            admin_log: Vector::new(StorageKey::AdminLog),
+            admin_log_len: 0,
+            council: None,
+            proposals: UnorderedMap::new(StorageKey::Proposals),
+            next_proposal_id: 0,
+            governance: None,
+            fee_schedule: FeeSchedule::default(),
+            listing_paid_until: UnorderedMap::new(StorageKey::ListingPaidUntil),
+            fee_treasury: 0,
+            fee_treasury_tokens: 0,
+            orders: UnorderedMap::new(StorageKey::Orders),
+            order_book: TreeMap::new(StorageKey::OrderBook),
+            next_order_id: 0,
+            filecoin_network: FilecoinNetwork::Mainnet,
+            code_hash: None,
+            removed_providers: UnorderedMap::new(StorageKey::RemovedProviders),
+            retrieval_prices: TreeMap::new(StorageKey::RetrievalPrices),
+            latest_retrieval_timestamp: 0,
+            provider_sla: UnorderedMap::new(StorageKey::ProviderSla),
+            feeder_bonds: UnorderedMap::new(StorageKey::FeederBonds),
+            feeder_bond: 0,
+            feeder_unbonding_period: feeder_bonds::DEFAULT_FEEDER_UNBONDING_PERIOD,
+            tier_config: TierConfig::default(),
+            reviews: UnorderedMap::new(StorageKey::Reviews),
+            last_review_at: UnorderedMap::new(StorageKey::LastReviewAt),
+            review_deposit: reviews::DEFAULT_REVIEW_DEPOSIT,
+            provider_ids: TreeMap::new(StorageKey::ProviderIds),
+            updated_index: TreeMap::new(StorageKey::UpdatedIndex),
+            removed_index: TreeMap::new(StorageKey::RemovedIndex),
+            oracle_key: None,
+            oracle_nonce: 0,
+            approved_subscribers: UnorderedSet::new(StorageKey::ApprovedSubscribers),
+            admin_log_storage: 0,
+            privileged_caller: Cell::new(None),
+        };
+
+        this.owner_history.push(&(env::predecessor_account_id(), env::block_height()));
+        this
+    }
+
+    // add or update storage providers, returns the number of entries added, updated and skipped with the skip reasons
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    // duplicated ids within the batch are logged and the last entry wins, unless reject_duplicates is set
+    // large syncs should go through update_storage_providers_chunk, a call only fits a few dozen new providers
+    #[payable]
+    pub fn update_storage_providers(&mut self, storage_providers: Vec<StorageProvider>, reject_duplicates: Option<bool>) -> BatchResult {
+        self.assert_role(Role::SpManager);
+
+        let initial_storage_usage = env::storage_usage();
+        let result = self.internal_update_storage_providers(storage_providers, reject_duplicates.unwrap_or(false));
+        self.refund_deposit(initial_storage_usage);
+
+        result
     }
 
-    // add or update storage providers
-    pub fn update_storage_providers(&mut self, storage_providers: Vec<StorageProvider>) {
+    fn internal_update_storage_providers(&mut self, mut storage_providers: Vec<StorageProvider>, reject_duplicates: bool) -> BatchResult {
+        self.assert_not_paused(Feature::Providers);
         let account_id = env::predecessor_account_id();
+        let mut result = BatchResult::default();
+        let mut skipped_ids: Vec<String> = Vec::new();
+        let mut added_ids: Vec<String> = Vec::new();
+        let mut updated_ids: Vec<String> = Vec::new();
+
+        env::log_str(&format!("update_storage_providers(): account_id {} storage providers {}", account_id, storage_providers.len()));
 
-        if account_id.to_string() != self.owner {
-            env::log_str(&format!("update_storage_providers(): account_id {} is not owner", account_id));
-            return;
+        for sp in storage_providers.iter() {
+            require!(sp.id.len() <= MAX_ID_LEN, format!("storage provider id is longer than {} bytes", MAX_ID_LEN));
         }
 
-        env::log_str(&format!("update_storage_providers(): account_id {} storage providers {}", account_id, storage_providers.len()));
+        // malformed ids are reported and skipped instead of failing the whole batch
+        storage_providers.retain_mut(|sp| match self.normalize_provider_id(&sp.id) {
+            Ok(id) => {
+                sp.id = id;
+                true
+            }
+            Err(reason) => {
+                env::log_str(&format!("update_storage_providers(): storage provider id {} is malformed, {}", sp.id, reason));
+                result.skip(&sp.id, reason);
+                false
+            }
+        });
 
+        let mut ids = HashSet::new();
         for sp in storage_providers.iter() {
-            let empty_sp = StorageProvider {
-                id: "".to_string(),
-                region: 0 as u8,
-                power: 0.0 as f64,
-                price: 0.0 as f64,
-            };
+            require!(self.is_valid_region(sp.region), format!("invalid region {} for {}", sp.region, sp.id));
+            require!(normalize_price(sp.price.0, sp.price_unit).is_some(), format!("invalid price unit {} for {}", sp.price_unit, sp.id));
+            if !ids.insert(&sp.id) {
+                require!(!reject_duplicates, format!("storage provider {} is duplicated in the batch", sp.id));
+                env::log_str(&format!("update_storage_providers(): storage provider {} is duplicated, the last entry wins", sp.id));
+            }
+        }
+
+        for sp in storage_providers.iter() {
+            if let Some(label) = &sp.label {
+                require!(label.len() <= MAX_LABEL_LEN, format!("label of {} is longer than {} bytes", sp.id, MAX_LABEL_LEN));
+            }
+
+            if self.min_price > 0 && sp.price.0 < self.min_price {
+                skipped_ids.push(sp.id.clone());
+                result.skip(&sp.id, "price is below the min price".to_string());
+                continue;
+            }
+
+            if self.is_denied(&sp.id) {
+                env::log_str(&format!("update_storage_providers(): storage provider {} is denied", sp.id));
+                result.skip(&sp.id, "storage provider is denied".to_string());
+                continue;
+            }
 
-            let mut storage_provider = self.storage_providers.get(&sp.id).unwrap_or(empty_sp);
+            let mut storage_provider = self.storage_providers.get(&sp.id).unwrap_or_default();
+            let previous = if storage_provider.id.is_empty() { None } else { Some(storage_provider.clone()) };
             if storage_provider.id.is_empty() {
                 storage_provider.id = sp.id.clone();
-                storage_provider.region = sp.region.clone();
-            } 
+                storage_provider.reputation = self.reputation_score(&sp.id);
+                storage_provider.listed_at = env::block_timestamp();
+                self.clear_provider_removed(&sp.id);
+                added_ids.push(sp.id.clone());
+                result.added += 1;
+            } else {
+                if !updated_ids.contains(&sp.id) && !added_ids.contains(&sp.id) {
+                    updated_ids.push(sp.id.clone());
+                }
+                result.updated += 1;
+            }
 
-            storage_provider.power = sp.power.clone();
-            storage_provider.price = sp.price.clone();
+            storage_provider.region = sp.region;
+            storage_provider.power = sp.power;
+            storage_provider.price = sp.price;
+            storage_provider.price_unit = sp.price_unit;
+            storage_provider.verified_price = sp.verified_price;
+            storage_provider.retrieval_price = sp.retrieval_price;
 
+            // keep the existing label unless a new one is given
+            if sp.label.is_some() {
+                storage_provider.label = sp.label.clone();
+            }
+
+            storage_provider.tier = self.compute_tier(&storage_provider);
+            self.touch_provider(&mut storage_provider);
             self.storage_providers.insert(&storage_provider.id, &storage_provider);
+            self.reindex_provider(previous.as_ref(), Some(&storage_provider));
+            self.record_provider_history(&storage_provider);
+        }
+        self.record_active_history();
+
+        if !added_ids.is_empty() || !updated_ids.is_empty() {
+            self.emit_event("storage_providers_updated", json!({
+                "added": added_ids,
+                "updated": updated_ids,
+            }));
+        }
+
+        if !skipped_ids.is_empty() {
+            self.emit_event("storage_providers_skipped", json!({
+                "ids": skipped_ids,
+                "min_price": U128(self.min_price),
+            }));
         }
+
+        result
     }
 
-    // delete the given storage providers
-    pub fn delete_storage_providers(&mut self, storage_providers: Vec<String>) {
+    // delete the given storage providers, returns the number of providers removed and the ids skipped
+    // the storage staking of the removed providers stays with the contract, the attached deposit is refunded
+    #[payable]
+    pub fn delete_storage_providers(&mut self, storage_providers: Vec<String>) -> BatchResult {
+        self.assert_role(Role::SpManager);
+        self.assert_not_paused(Feature::Providers);
         let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
 
-        if account_id.to_string() != self.owner {
-            env::log_str(&format!("delete_storage_providers(): account_id {} is not owner", account_id));
-            return;
+        let ids: Vec<String> = storage_providers.iter().map(|id| self.lookup_provider_id(id)).collect();
+        let mut result = BatchResult::default();
+        let mut removed_ids: Vec<&String> = Vec::new();
+        for iter in ids.iter() {
+            if let Some(storage_provider) = self.storage_providers.remove(iter) {
+                self.reindex_provider(Some(&storage_provider), None);
+                self.provider_history.remove(iter);
+                self.provider_sla.remove(iter);
+                self.record_provider_removed(&storage_provider);
+                removed_ids.push(iter);
+            } else {
+                result.skip(iter, "storage provider not found".to_string());
+            }
         }
+        self.record_active_history();
+        self.refund_deposit(initial_storage_usage);
+
+        env::log_str(&format!("delete_storage_providers(): account_id {} storage providers {}", account_id, storage_providers.len()));
 
-        for iter in storage_providers.iter() {
-            self.storage_providers.remove(iter);
+        result.removed = removed_ids.len() as u32;
+        if !removed_ids.is_empty() {
+            self.emit_event("storage_providers_deleted", json!({ "removed": removed_ids }));
         }
 
-        env::log_str(&format!("delete_storage_providers(): account_id {} storage providers {}", account_id, storage_providers.len()));
+        result
     }
 
-    // get the storage provider's list
-    pub fn get_storage_providers(&self) -> Vec<StorageProvider> {
-        let storage_providers = self.storage_providers.values_as_vector().to_vec();
-        return storage_providers;
+    // set the minimum price a storage provider must have to be stored, 0 disables the check
+    pub fn set_min_price(&mut self, min_price: U128) {
+        self.assert_config_authority();
+
+        self.min_price = min_price.0;
+        self.emit_event("min_price_set", json!({ "min_price": min_price }));
     }
 
-    // set the total of active storage providers per region
-    pub fn set_active_per_region(&mut self, active_per_region: ActivePerRegion) {
-        let account_id = env::predecessor_account_id();
+    // get the minimum storage provider price
+    pub fn get_min_price(&self) -> U128 {
+        U128(self.min_price)
+    }
+
+    // update only the given fields of an existing storage provider
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn patch_provider(&mut self, id: String, power: Option<U128>, price: Option<U128>, region: Option<u8>) {
+        self.assert_role(Role::SpManager);
+        self.assert_not_paused(Feature::Providers);
+        let initial_storage_usage = env::storage_usage();
+        let id = self.lookup_provider_id(&id);
 
-        if account_id.to_string() != self.owner {
-            env::log_str(&format!("set_active_per_region(): account_id {} is not owner", account_id));
-            return;
+        let mut storage_provider = self.storage_providers.get(&id)
+            .unwrap_or_else(|| panic!("storage provider {} not found", id));
+        let previous = storage_provider.clone();
+
+        if let Some(power) = power {
+            storage_provider.power = power;
+        }
+        if let Some(price) = price {
+            storage_provider.price = price;
+        }
+        if let Some(region) = region {
+            require!(self.is_valid_region(region), format!("invalid region {}", region));
+            storage_provider.region = region;
         }
 
-        self.active_per_region = active_per_region;
+        storage_provider.tier = self.compute_tier(&storage_provider);
+        self.touch_provider(&mut storage_provider);
+        self.storage_providers.insert(&id, &storage_provider);
+        self.reindex_provider(Some(&previous), Some(&storage_provider));
+        self.record_provider_history(&storage_provider);
+        self.record_active_history();
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("storage_provider_patched", json!({
+            "id": id,
+            "region": storage_provider.region,
+            "power": storage_provider.power,
+            "price": storage_provider.price,
+        }));
     }
 
-    // get the total of active storage providers per region
-    pub fn get_active_per_region(&self) -> ActivePerRegion {
-        let active_per_region = ActivePerRegion {
-            europe: self.active_per_region.europe, 
-            asia: self.active_per_region.asia, 
-            north_america: self.active_per_region.north_america, 
-            other: self.active_per_region.other
-        };
+    // move existing storage providers to new regions, leaving power and price untouched
+    // the active count of each moved provider is moved from its old region to the new one
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn reassign_regions(&mut self, assignments: Vec<(String, u8)>) {
+        self.assert_role(Role::SpManager);
+        self.assert_not_paused(Feature::Providers);
+        let initial_storage_usage = env::storage_usage();
 
-        return active_per_region;
-    }
+        require!(assignments.len() <= MAX_REASSIGN_BATCH, format!("at most {} assignments per call", MAX_REASSIGN_BATCH));
 
-    // set the average storage price per region
-    pub fn set_price_per_region(&mut self, price_per_region: PricePerRegion) {
-        let account_id = env::predecessor_account_id();
+        let mut moved: Vec<&(String, u8)> = Vec::new();
+        let assignments: Vec<(String, u8)> = assignments
+            .into_iter()
+            .map(|(id, region)| (self.lookup_provider_id(&id), region))
+            .collect();
+        for assignment in assignments.iter() {
+            let (id, region) = assignment;
+            require!(self.is_valid_region(*region), format!("invalid region {}", region));
 
-        if account_id.to_string() != self.owner {
-            env::log_str(&format!("set_price_per_region(): account_id {} is not owner", account_id));
-            return;
+            let mut storage_provider = self.storage_providers.get(id)
+                .unwrap_or_else(|| panic!("storage provider {} not found", id));
+
+            if storage_provider.region == *region {
+                continue;
+            }
+
+            let previous = storage_provider.clone();
+            storage_provider.region = *region;
+            self.touch_provider(&mut storage_provider);
+            self.storage_providers.insert(id, &storage_provider);
+            self.reindex_provider(Some(&previous), Some(&storage_provider));
+            moved.push(assignment);
         }
+        self.record_active_history();
+        self.refund_deposit(initial_storage_usage);
 
-        let empty_ppr = PricePerRegion {
-            europe: 0.0 as f64,
-            asia: 0.0 as f64,
-            north_america: 0.0 as f64,
-            other: 0.0 as f64,
-            global: 0.0 as f64,
-            fil_price: 0.0 as f64,
-            power: 0 as u128,
-            timestamp: 0 as u64,
-        };
+        if !moved.is_empty() {
+            self.emit_event("regions_reassigned", json!({ "assignments": moved }));
+        }
+    }
 
-        let mut ppr = self.price_per_region.get(&price_per_region.timestamp).unwrap_or(empty_ppr);
-        if ppr.timestamp == 0 {
-            ppr.timestamp = price_per_region.timestamp;
+    // mark the given storage providers as verified or not
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn set_provider_verified(&mut self, ids: Vec<String>, verified: bool) {
+        self.assert_role(Role::SpManager);
+        self.assert_not_paused(Feature::Providers);
+        let initial_storage_usage = env::storage_usage();
+
+        let ids: Vec<String> = ids.iter().map(|id| self.lookup_provider_id(id)).collect();
+        let mut changed_ids: Vec<&String> = Vec::new();
+        for id in ids.iter() {
+            match self.storage_providers.get(id) {
+                Some(mut storage_provider) => {
+                    storage_provider.verified = verified;
+                    self.touch_provider(&mut storage_provider);
+                    self.storage_providers.insert(id, &storage_provider);
+                    changed_ids.push(id);
+                }
+                None => env::log_str(&format!("set_provider_verified(): storage provider {} not found", id)),
+            }
         }
+        self.refund_deposit(initial_storage_usage);
 
-        ppr.europe = price_per_region.europe;
-        ppr.asia = price_per_region.asia;
-        ppr.north_america = price_per_region.north_america;
-        ppr.other = price_per_region.other;
-        ppr.global = price_per_region.global;
-        ppr.fil_price = price_per_region.fil_price;
-        ppr.power = price_per_region.power;
+        if !changed_ids.is_empty() {
+            self.emit_event("storage_providers_verified", json!({
+                "ids": changed_ids,
+                "verified": verified,
+            }));
+        }
+    }
 
-        self.price_per_region.insert(&ppr.timestamp, &ppr);
-        self.latest_timestamp = price_per_region.timestamp;
+    // get the verified storage providers, paginated
+    pub fn get_verified_providers(&self, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        self.storage_providers
+            .values_as_vector()
+            .iter()
+            .filter(|sp| sp.verified)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
     }
 
-    // get the average storage price per region
-    pub fn get_price_per_region_list(&self) -> Vec<PricePerRegion> {
-        let ppr = self.price_per_region.values_as_vector().to_vec();
-        return ppr;
+    // get the storage providers whose id starts with prefix, paginated
+    pub fn get_providers_by_id_prefix(&self, prefix: String, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        require!(!prefix.is_empty(), "prefix must not be empty");
+
+        self.storage_providers
+            .keys_as_vector()
+            .iter()
+            .filter(|id| id.starts_with(&prefix))
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .filter_map(|id| self.storage_providers.get(&id))
+            .collect()
     }
 
-    // get the latest storage price per region
-    pub fn get_latest_price_per_region(&self) ->PricePerRegion {
-        let empty_ppr = PricePerRegion {
-            europe: 0.0 as f64,
-            asia: 0.0 as f64,
-            north_america: 0.0 as f64,
-            other: 0.0 as f64,
-            global: 0.0 as f64,
-            fil_price: 0.0 as f64,
-            power: 0 as u128,
-            timestamp: 0 as u64,
-        };
+    // get the storage provider's list
+    pub fn get_storage_providers(&self) -> Vec<StorageProvider> {
+        self.storage_providers.values_as_vector().to_vec()
+    }
 
-        let ppr = self.price_per_region.get(&self.latest_timestamp).unwrap_or(empty_ppr);
-        return ppr;
+    // get a single storage provider by id
+    pub fn get_storage_provider(&self, id: String) -> Option<StorageProvider> {
+        self.storage_providers.get(&self.lookup_provider_id(&id))
     }
 
-    // delete the given timestamps
-    pub fn delete_price_per_region(&mut self, timestamps: Vec<u64>) {
-        let account_id = env::predecessor_account_id();
+    // whether a storage provider is listed
+    pub fn has_storage_provider(&self, id: String) -> bool {
+        self.storage_providers.get(&self.lookup_provider_id(&id)).is_some()
+    }
 
-        if account_id.to_string() != self.owner {
-            env::log_str(&format!("set_price_per_region(): account_id {} is not owner", account_id));
-            return;
-        }
-    
-        for iter in timestamps.iter() {
-            self.price_per_region.remove(iter);
+    // get up to limit storage providers starting at from_index, only the requested page is read
+    pub fn get_storage_providers_paged(&self, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        let values = self.storage_providers.values_as_vector();
+        let end = from_index.saturating_add(view_limit(limit) as u64).min(values.len());
+
+        (from_index..end).filter_map(|index| values.get(index)).collect()
+    }
+
+    // get up to limit storage providers ordered by id, starting at start_id (inclusive) or at the first id
+    // unlike index-based pages, a cursor stays valid when providers are added or removed between calls
+    pub fn get_storage_providers_from(&self, start_id: Option<String>, limit: u64) -> ProviderPage {
+        let mut ids: Vec<String> = self.provider_ids
+            .range((Bound::Included(start_id.unwrap_or_default()), Bound::Unbounded))
+            .take(view_limit(limit) + 1)
+            .map(|(id, _)| id)
+            .collect();
+        let next_cursor = if ids.len() > view_limit(limit) { ids.pop() } else { None };
+
+        ProviderPage {
+            storage_providers: ids.iter().filter_map(|id| self.storage_providers.get(id)).collect(),
+            next_cursor,
         }
-    
-         env::log_str(&format!("delete_price_per_region(): account_id {} entries {}", account_id, timestamps.len()));
     }
-}
 
-/*
- * To run from contract directory:
- * cargo test -- --nocapture
- */
-#[cfg(test)]
+    // get the number of storage providers
+    pub fn get_storage_providers_count(&self) -> u64 {
+        self.storage_providers.len()
+    }
 
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::{testing_env, VMContext, AccountId};
+    // get a storage provider's price in attoFIL/TiB/day, whatever unit it was reported in
+    pub fn get_normalized_price(&self, id: String) -> Option<U128> {
+        let sp = self.storage_providers.get(&id)?;
+        normalize_price(sp.price.0, sp.price_unit).map(U128)
+    }
 
-    fn carol() -> AccountId {
-        AccountId::new_unchecked("carol_near".to_string())
+    // get the storage providers priced within [min_price, max_price], optionally with at least min_reputation, paginated
+    pub fn get_providers_by_price_range(&self, min_price: U128, max_price: U128, from_index: u64, limit: u64, min_reputation: Option<u32>) -> Vec<StorageProvider> {
+        require!(min_price.0 <= max_price.0, "min_price must not be greater than max_price");
+
+        self.storage_providers
+            .values_as_vector()
+            .iter()
+            .filter(|sp| sp.price.0 >= min_price.0 && sp.price.0 <= max_price.0)
+            .filter(|sp| sp.reputation >= min_reputation.unwrap_or(0))
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
     }
 
-    fn get_context() -> VMContext {
-        VMContextBuilder::new()
-            .predecessor_account_id(carol())
-            .attached_deposit(10000)
-            .build()
+    // get the storage providers sorted by price (ties broken by power, largest first), paginated
+    // note: the whole provider set is loaded and sorted on every call, which is O(n log n),
+    // so this is meant for moderate-sized sets
+    pub fn get_storage_providers_sorted(&self, descending: bool, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        let mut storage_providers = self.storage_providers.values_as_vector().to_vec();
+
+        storage_providers.sort_by(|a, b| {
+            let by_price = if descending {
+                b.price.0.cmp(&a.price.0)
+            } else {
+                a.price.0.cmp(&b.price.0)
+            };
+            by_price.then_with(|| b.power.0.cmp(&a.power.0))
+        });
+
+        storage_providers
+            .into_iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
     }
 
-    #[test]
-    fn set_then_get_remove_storage_providers() {
-        let context = get_context();
-        enum Regions {
-            NorthAmerica = 1,
-            Europe = 2,
-            Asia = 3,
-            Other = 4,
-        }
+    // get the total power of the Active storage providers in bytes
+    pub fn get_total_power(&self) -> U128 {
+        U128(checked_power_sum(self.storage_providers.values_as_vector().iter().filter(|sp| sp.is_active()).map(|sp| sp.power.0)))
+    }
 
-        testing_env!(context);
-        let mut contract = FilMarket::new();
-        let sp_list = vec![
-            StorageProvider {
-                id: "id1".to_string(),
-                region: Regions::Europe as u8,
-                power: 24.64,
-                price: 0.46
-            },
-            StorageProvider {
-                id: "id2".to_string(),
-                region: Regions::Asia as u8,
-                power: 5693.0,
-                price: 0.6778
-            },
-            StorageProvider {
-                id: "id3".to_string(),
-                region: Regions::NorthAmerica as u8,
-                power: 54.64,
-                price: 0.43
-            },
-            StorageProvider {
-                id: "id4".to_string(),
-                region: Regions::Other as u8,
-                power: 454.64,
-                price: 0.143
-            },
-        ];
+    // get the min, max and mean price over the Active storage providers, optionally with at least min_reputation
+    pub fn get_price_summary(&self, min_reputation: Option<u32>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.reputation >= min_reputation.unwrap_or(0))
+                .map(|sp| sp.price.0)
+        )
+    }
 
-        contract.update_storage_providers(sp_list);
-        contract.delete_storage_providers(vec!["id4".to_string()]);
-        let result = contract.get_storage_providers();
+    // get the min, max and mean price over the Active storage providers of a region, optionally with at least min_reputation
+    pub fn get_price_summary_by_region(&self, region: u8, min_reputation: Option<u32>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.region == region && sp.reputation >= min_reputation.unwrap_or(0))
+                .map(|sp| sp.price.0)
+        )
+    }
 
-        assert_eq!(3, result.len());
-        assert_eq!("id1".to_string(), result[0].id);
-        assert_eq!("id2".to_string(), result[1].id);
-        assert_eq!("id3".to_string(), result[2].id);
+    // get the min, max and mean verified deal price over the Active storage providers offering verified deals,
+    // optionally with at least min_reputation
+    pub fn get_verified_price_summary(&self, min_reputation: Option<u32>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.reputation >= min_reputation.unwrap_or(0))
+                .filter_map(|sp| sp.verified_price.map(|price| price.0))
+        )
     }
 
-    #[test]
-    fn set_then_get_active_per_region() {
-        let context = get_context();
-        testing_env!(context);
-        let mut contract = FilMarket::new();
+    // get the min, max and mean verified deal price over the Active storage providers of a region offering verified deals,
+    // optionally with at least min_reputation
+    pub fn get_verified_price_summary_by_region(&self, region: u8, min_reputation: Option<u32>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.region == region && sp.reputation >= min_reputation.unwrap_or(0))
+                .filter_map(|sp| sp.verified_price.map(|price| price.0))
+        )
+    }
 
-        let active_per_region = ActivePerRegion {
-            europe: 3,
-            asia: 24,
-            north_america: 12,
-            other: 45,
-        };
+    // get the storage providers bucketed by region, at most limit_per_region (clamped to MAX_VIEW_LIMIT) each
+    // note: this scans the whole provider set in a single pass, so gas grows with the number of providers
+    pub fn get_providers_grouped_by_region(&self, limit_per_region: u64) -> RegionGroups {
+        let limit = view_limit(limit_per_region);
+        let mut groups = RegionGroups::default();
 
-        contract.set_active_per_region(active_per_region);
-        let result = contract.get_active_per_region();
+        for sp in self.storage_providers.values_as_vector().iter() {
+            let bucket = groups.bucket(sp.region);
+            if bucket.len() < limit {
+                bucket.push(sp);
+            }
+        }
 
-        assert_eq!(3, result.europe);
-        assert_eq!(24, result.asia);
-        assert_eq!(12, result.north_america);
-        assert_eq!(45, result.other);
+        groups
     }
 
-    #[test]
-    fn set_then_get_price_per_region() {
-        let context = get_context();
-        testing_env!(context);
-        let mut contract = FilMarket::new();
+    // get the total power of the storage providers per region, summed over the Active providers
+    pub fn get_power_per_region_computed(&self) -> PowerPerRegion {
+        let mut power_per_region = PowerPerRegion::default();
+        for sp in self.storage_providers.values_as_vector().iter().filter(|sp| sp.is_active()) {
+            power_per_region.add(sp.region, sp.power.0);
+        }
 
-        let price_per_region = PricePerRegion {
-            europe: 0.00013,
-            asia: 0.0004,
-            north_america: 0.0002,
-            other: 0.00005,
-            global: 0.00034,
-            fil_price: 64.245,
-            power: 1024,
-            timestamp: 1,
-        };
+        power_per_region
+    }
 
-        contract.set_price_per_region(price_per_region);
-        let result = contract.get_price_per_region_list();
+    // get the total power of the storage providers per region, maintained on every provider update
+    pub fn get_power_per_region(&self) -> PowerPerRegion {
+        self.power_per_region.clone()
+    }
 
-        assert_eq!(0.00013, result[0].europe);
-        assert_eq!(0.0004, result[0].asia);
-        assert_eq!(0.0002, result[0].north_america);
-        assert_eq!(0.00005, result[0].other);
-        assert_eq!(0.00034, result[0].global);
-        assert_eq!(64.245, result[0].fil_price);
-        assert_eq!(1024, result[0].power);
-        assert_eq!(1, result[0].timestamp);
+    // deprecated: the counts are derived from the stored providers and maintained on every provider update,
+    // the given counts are ignored, kept so existing callers don't fail
+    pub fn set_active_per_region(&mut self, active_per_region: ActivePerRegion) {
+        env::log_str(&format!(
+            "set_active_per_region(): deprecated, ignored {} regions, the counts are maintained on every provider update",
+            active_per_region.0.len()
+        ));
+    }
+
+    // recount the Active storage providers and the power per region from the stored providers, to repair counts
+    // note: this scans every provider, very large sets may exceed the gas of a single call
+    pub fn recompute_active_per_region(&mut self) {
+        self.assert_role(Role::SpManager);
+        self.assert_not_paused(Feature::Providers);
+
+        if self.storage_providers.len() > LARGE_PROVIDER_SET {
+            env::log_str(&format!("recompute_active_per_region(): warning, scanning {} storage providers", self.storage_providers.len()));
+        }
+
+        let mut active_per_region = ActivePerRegion::default();
+        for sp in self.storage_providers.values_as_vector().iter().filter(|sp| sp.is_active()) {
+            active_per_region.increment(sp.region);
+        }
+        self.power_per_region = self.get_power_per_region_computed();
+
+        self.emit_event("active_per_region_set", json!({ "active_per_region": active_per_region }));
+        self.active_per_region = active_per_region;
+        self.record_active_history();
+    }
+
+    // get the total of active storage providers per region
+    pub fn get_active_per_region(&self) -> ActivePerRegion {
+        self.active_per_region.clone()
+    }
+
+    // get the total of active storage providers across all regions
+    pub fn get_active_per_region_total(&self) -> u32 {
+        self.active_per_region.0
+            .values()
+            .try_fold(0u32, |total, count| total.checked_add(*count))
+            .expect("total of active storage providers overflows u32")
+    }
+
+    // set the average storage price per region
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn set_price_per_region(&mut self, price_per_region: PricePerRegion) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_direct_price_write();
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_set_price_per_region(price_per_region);
+        self.refund_deposit(initial_storage_usage);
+    }
+
+    // set the average storage price per region from decimal strings such as "0.00013"
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn set_price_per_region_str(&mut self, price_per_region: PricePerRegionStr) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_direct_price_write();
+
+        let parse = |field: &str, value: &str| {
+            let scaled = decimal::parse_decimal(value, decimal::PRICE_DECIMALS)
+                .unwrap_or_else(|| panic!("invalid decimal {} for {}", value, field));
+            U128(scaled)
+        };
+
+        let ppr = PricePerRegion {
+            prices: price_per_region.prices
+                .iter()
+                .map(|(region, price)| (*region, parse(&format!("region {}", region), price)))
+                .collect(),
+            global: parse("global", &price_per_region.global),
+            fil_price: parse("fil_price", &price_per_region.fil_price),
+            power: price_per_region.power,
+            timestamp: price_per_region.timestamp,
+            verified_prices: price_per_region.verified_prices
+                .iter()
+                .map(|(region, price)| (*region, parse(&format!("verified region {}", region), price)))
+                .collect(),
+            verified_global: price_per_region.verified_global.map(|price| parse("verified_global", &price)),
+            bands: price_per_region.bands
+                .iter()
+                .map(|(region, band)| (*region, band.parse(*region, parse)))
+                .collect(),
+        };
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_set_price_per_region(ppr);
+        self.refund_deposit(initial_storage_usage);
+    }
+
+    // reject a zero timestamp or one too far ahead of the block timestamp
+    fn assert_valid_price_timestamp(&self, timestamp: u64) {
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        require!(timestamp != 0, "timestamp must not be 0");
+        require!(
+            timestamp <= now.saturating_add(self.max_future_skew),
+            format!("timestamp {} is more than {} seconds in the future", timestamp, self.max_future_skew)
+        );
+    }
+
+    fn internal_set_price_per_region(&mut self, price_per_region: PricePerRegion) {
+        self.assert_not_paused(Feature::Prices);
+        self.assert_valid_price_timestamp(price_per_region.timestamp);
+        self.assert_price_guards(&price_per_region);
+
+        let ppr = self.insert_price_entry(price_per_region);
+
+        self.emit_event("price_per_region_set", json!({ "price_per_region": ppr }));
+        // an entry older than the latest one is history, subscribers only follow the latest price
+        if ppr.timestamp == self.latest_timestamp {
+            self.notify_subscribers(&ppr);
+        }
+    }
+
+    // fail the call if a price entry has a price for a region that doesn't exist
+    fn assert_valid_price_regions(&self, price_per_region: &PricePerRegion) {
+        for region in price_per_region.prices.keys().chain(price_per_region.verified_prices.keys()) {
+            require!(self.is_valid_region(*region), format!("invalid region {}", region));
+        }
+    }
+
+    // store a price entry, merged into the entry of the same timestamp, moving latest_timestamp only forward
+    fn insert_price_entry(&mut self, price_per_region: PricePerRegion) -> PricePerRegion {
+        self.assert_valid_price_regions(&price_per_region);
+        price_bands::assert_price_bands(&price_per_region);
+
+        let mut ppr = self.price_per_region.get(&price_per_region.timestamp).unwrap_or_default();
+        if ppr.timestamp == 0 {
+            ppr.timestamp = price_per_region.timestamp;
+        }
+
+        ppr.prices = price_per_region.prices;
+        ppr.global = price_per_region.global;
+        ppr.fil_price = price_per_region.fil_price;
+        ppr.power = price_per_region.power;
+        ppr.verified_prices = price_per_region.verified_prices;
+        ppr.verified_global = price_per_region.verified_global;
+        ppr.bands = price_per_region.bands;
+
+        self.auto_prune_price_history();
+        self.price_per_region.insert(&ppr.timestamp, &ppr);
+        self.latest_timestamp = self.latest_timestamp.max(ppr.timestamp);
+
+        ppr
+    }
+
+    // insert a historical price entry, older than the latest one, without moving the latest price or notifying
+    // the subscribers; the out of order guard doesn't apply, the deviation guard does
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn backfill_price_per_region(&mut self, price_per_region: PricePerRegion) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_direct_price_write();
+        self.assert_not_paused(Feature::Prices);
+        let initial_storage_usage = env::storage_usage();
+
+        let timestamp = price_per_region.timestamp;
+        require!(timestamp != 0, "timestamp must not be 0");
+        require!(
+            timestamp < self.latest_timestamp,
+            format!("timestamp {} is not older than the latest entry {}, use set_price_per_region", timestamp, self.latest_timestamp)
+        );
+        if let Some(cutoff_ts) = self.retention_cutoff() {
+            require!(timestamp >= cutoff_ts, format!("timestamp {} is past the price retention", timestamp));
+        }
+        self.assert_price_deviation(&price_per_region);
+
+        let ppr = self.insert_price_entry(price_per_region);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("price_per_region_backfilled", json!({ "price_per_region": ppr }));
+    }
+
+    // set how many seconds a price timestamp may be ahead of the block timestamp
+    pub fn set_max_future_skew(&mut self, max_future_skew: u64) {
+        self.assert_config_authority();
+
+        self.max_future_skew = max_future_skew;
+        self.emit_event("max_future_skew_set", json!({ "max_future_skew": max_future_skew }));
+    }
+
+    // get how many seconds a price timestamp may be ahead of the block timestamp
+    pub fn get_max_future_skew(&self) -> u64 {
+        self.max_future_skew
+    }
+
+    // get the average storage price per region, ordered by timestamp
+    pub fn get_price_per_region_list(&self) -> Vec<PricePerRegion> {
+        self.price_per_region.iter().map(|(_, ppr)| ppr).collect()
+    }
+
+    // get up to limit price entries with from_timestamp <= timestamp <= to_timestamp, in ascending order
+    pub fn get_price_history(&self, from_timestamp: u64, to_timestamp: u64, limit: u64) -> Vec<PricePerRegion> {
+        require!(from_timestamp <= to_timestamp, "from_timestamp must not be greater than to_timestamp");
+
+        self.price_per_region
+            .range((Bound::Included(from_timestamp), Bound::Included(to_timestamp)))
+            .take(view_limit(limit))
+            .map(|(_, ppr)| ppr)
+            .collect()
+    }
+
+    // get the n latest price entries, in ascending order
+    pub fn get_latest_n_prices(&self, n: u64) -> Vec<PricePerRegion> {
+        let mut prices: Vec<PricePerRegion> = self.price_per_region
+            .iter_rev()
+            .take(view_limit(n))
+            .map(|(_, ppr)| ppr)
+            .collect();
+
+        prices.reverse();
+        prices
+    }
+
+    // get the price entry as of target_ts, the one with the greatest timestamp <= target_ts
+    pub fn get_price_nearest(&self, target_ts: u64) -> Option<PricePerRegion> {
+        let timestamp = self.price_per_region.floor_key(&target_ts)?;
+        self.price_per_region.get(&timestamp)
+    }
+
+    // get the price entry in effect at timestamp, e.g. to price a deal as of its start
+    // the lookup is a search of the timestamp-ordered price tree, logarithmic in the number of entries
+    pub fn get_price_at(&self, timestamp: u64) -> Option<PricePerRegion> {
+        self.get_price_nearest(timestamp)
+    }
+
+    // get the number of price entries and their earliest and latest timestamps
+    pub fn get_price_history_meta(&self) -> PriceHistoryMeta {
+        PriceHistoryMeta {
+            count: self.price_per_region.len(),
+            earliest_ts: self.price_per_region.min().unwrap_or(0),
+            latest_ts: self.price_per_region.max().unwrap_or(0),
+        }
+    }
+
+    // get the min, max and mean global price over the whole price history
+    pub fn get_global_price_stats(&self) -> GlobalPriceStats {
+        let summary = PriceSummary::from_prices(self.price_per_region.iter().map(|(_, ppr)| ppr.global.0));
+
+        GlobalPriceStats {
+            min: summary.min,
+            max: summary.max,
+            mean: summary.mean,
+            count: summary.count,
+            first_ts: self.price_per_region.min().unwrap_or(0),
+            last_ts: self.price_per_region.max().unwrap_or(0),
+        }
+    }
+
+    // get the latest storage price per region, the entry with the greatest timestamp
+    pub fn get_latest_price_per_region(&self) ->PricePerRegion {
+        self.price_per_region.get(&self.latest_timestamp).unwrap_or_default()
+    }
+
+    // get the latest global storage price, also callable from other contracts through ext_fil_market
+    pub fn fetch_latest_global_price(&self) -> U128 {
+        self.get_latest_price_per_region().global
+    }
+
+    // get the latest storage price per region if it is not older than max_age_seconds
+    pub fn get_latest_price_if_fresh(&self, max_age_seconds: u64) -> Option<PricePerRegion> {
+        let ppr = self.price_per_region.get(&self.latest_timestamp)?;
+
+        // block_timestamp is in nanoseconds, price timestamps are in seconds
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        let age = now.saturating_sub(ppr.timestamp);
+
+        if age <= max_age_seconds {
+            Some(ppr)
+        } else {
+            None
+        }
+    }
+
+    // delete up to limit entries older than cutoff_ts, returns the number of entries removed
+    // call again until it returns 0 to prune everything before cutoff_ts
+    // the storage staking of the removed entries stays with the contract, the attached deposit is refunded
+    #[payable]
+    pub fn delete_price_per_region_before(&mut self, cutoff_ts: u64, limit: u64) -> u64 {
+        self.assert_owner();
+        let account_id = env::predecessor_account_id();
+
+        let initial_storage_usage = env::storage_usage();
+        let timestamps = self.internal_delete_prices_before(cutoff_ts, limit);
+        self.refund_deposit(initial_storage_usage);
+        env::log_str(&format!("delete_price_per_region_before(): account_id {} entries {}", account_id, timestamps.len()));
+
+        timestamps.len() as u64
+    }
+
+    // get the operational limits and configuration of the contract
+    pub fn get_config(&self) -> ContractConfig {
+        ContractConfig {
+            max_view_limit: MAX_VIEW_LIMIT,
+            max_id_len: MAX_ID_LEN as u32,
+            max_label_len: MAX_LABEL_LEN as u32,
+            min_price: U128(self.min_price),
+            max_future_skew: self.max_future_skew,
+            price_candidate_deposit: U128(price_candidates::PRICE_CANDIDATE_DEPOSIT),
+            provider_history_depth: self.provider_history_depth,
+            max_batch_items: batches::MAX_BATCH_ITEMS as u32,
+            price_retention: self.price_retention,
+            feeder_quorum: self.feeder_quorum,
+            max_price_age: self.max_price_age,
+            paused: self.paused,
+            paused_features: self.paused_features.clone(),
+            price_guards: self.price_guards.clone(),
+            governance: self.governance.clone(),
+            filecoin_network: self.filecoin_network,
+        }
+    }
+
+    // get the storage used by the contract and how much of its balance it locks
+    pub fn storage_balance(&self) -> StorageBalance {
+        let storage_usage = env::storage_usage();
+        let locked = env::storage_byte_cost() * Balance::from(storage_usage);
+
+        StorageBalance {
+            storage_usage,
+            locked: U128(locked),
+            available: U128(env::account_balance().saturating_sub(locked)),
+        }
+    }
+
+    // get the latest price, active providers, total power, provider count and price freshness in a single call
+    pub fn get_dashboard(&self) -> Dashboard {
+        let checked = self.get_latest_price_checked();
+        let last_updated = checked.price.as_ref().map(|ppr| ppr.timestamp.max(self.last_heartbeat)).unwrap_or(0);
+
+        Dashboard {
+            latest_price_per_region: checked.price,
+            active_per_region: self.get_active_per_region(),
+            storage_providers_count: self.storage_providers.len(),
+            latest_timestamp: self.latest_timestamp,
+            total_power: U128(self.power_per_region.total()),
+            last_updated,
+            price_status: checked.status,
+            price_age_seconds: checked.age_seconds,
+        }
+    }
+
+    // get the number of storage providers
+    pub fn get_provider_count(&self) -> u64 {
+        self.storage_providers.len()
+    }
+
+    // get the number of price entries
+    pub fn get_price_entry_count(&self) -> u64 {
+        self.price_per_region.len()
+    }
+
+    // get the counts, total power, latest timestamp and owner in a single call
+    // note: the total power reads every storage provider, as get_total_power does
+    pub fn get_summary(&self) -> ContractSummary {
+        ContractSummary {
+            provider_count: self.get_provider_count(),
+            price_entry_count: self.get_price_entry_count(),
+            total_power: self.get_total_power(),
+            latest_timestamp: self.latest_timestamp,
+            owner: self.owner.clone(),
+        }
+    }
+
+    // get the population and freshness signals for monitoring in one call
+    pub fn get_health(&self) -> HealthStatus {
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        let latest = self.price_per_region.get(&self.latest_timestamp);
+
+        HealthStatus {
+            provider_count: self.storage_providers.len(),
+            has_prices: latest.is_some(),
+            latest_price_age_seconds: latest.map(|ppr| now.saturating_sub(ppr.timestamp)),
+            paused: self.paused,
+        }
+    }
+
+    // delete the given timestamps, returns the number of entries removed
+    // the storage staking of the removed entries stays with the contract, the attached deposit is refunded
+    #[payable]
+    pub fn delete_price_per_region(&mut self, timestamps: Vec<u64>) -> u32 {
+        self.assert_owner();
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+
+        let mut removed: Vec<u64> = Vec::new();
+        for iter in timestamps.iter() {
+            if self.price_per_region.remove(iter).is_some() {
+                removed.push(*iter);
+            }
+        }
+
+        self.latest_timestamp = self.price_per_region.max().unwrap_or(0);
+        self.refund_deposit(initial_storage_usage);
+
+        env::log_str(&format!("delete_price_per_region(): account_id {} entries {}", account_id, timestamps.len()));
+
+        let count = removed.len() as u32;
+        if count > 0 {
+            self.emit_event("price_per_region_deleted", json!({ "timestamps": removed }));
+        }
+
+        count
+    }
+}
+
+/*
+ * To run from contract directory:
+ * cargo test -- --nocapture
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, VMContext, AccountId};
+
+    pub(crate) const NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+    pub(crate) fn account(name: &str) -> AccountId {
+        AccountId::new_unchecked(name.to_string())
+    }
+
+    // a call from predecessor to the filmarket_near contract, attaching deposit yoctoNEAR at now seconds
+    pub(crate) fn context(predecessor: &str, deposit: Balance, now: u64) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(account("filmarket_near"))
+            .predecessor_account_id(account(predecessor))
+            .attached_deposit(deposit)
+            .block_timestamp(now * NANOSECONDS_PER_SECOND);
+        builder
+    }
+
+    pub(crate) fn set_context(predecessor: &str, deposit: Balance, now: u64) {
+        testing_env!(context(predecessor, deposit, now).build());
+    }
+
+    // FIL or USD to fixed-point, exact for the decimal literal as written
+    pub(crate) fn fil(value: f64) -> U128 {
+        U128(decimal::f64_to_scaled(value, decimal::PRICE_DECIMALS).unwrap())
+    }
+
+    // GiB to bytes
+    pub(crate) fn gib(value: f64) -> U128 {
+        U128((value * BYTES_PER_GIB as f64) as u128)
+    }
+
+    // the (receiver, amount) of every transfer created in the current context
+    pub(crate) fn transfers() -> Vec<(AccountId, Balance)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id.clone();
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    // per-region values keyed by region id
+    fn by_region<T>(values: Vec<(u8, T)>) -> BTreeMap<u8, T> {
+        values.into_iter().collect()
+    }
+
+    fn carol() -> AccountId {
+        account("carol_near")
+    }
+
+    fn get_context() -> VMContext {
+        context("carol_near", NEAR, 0).build()
+    }
+
+    #[test]
+    fn set_then_get_remove_storage_providers() {
+        let context = get_context();
+        enum Regions {
+            NorthAmerica = 1,
+            Europe = 2,
+            Asia = 3,
+            Other = 4,
+            SouthAmerica = 5,
+            Africa = 6,
+        }
+
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider {
+                id: "f01".to_string(),
+                region: Regions::Europe as u8,
+                power: gib(24.64),
+                price: fil(0.46),
+                ..Default::default()
+            },
+            StorageProvider {
+                id: "f02".to_string(),
+                region: Regions::Asia as u8,
+                power: gib(5693.0),
+                price: fil(0.6778),
+                ..Default::default()
+            },
+            StorageProvider {
+                id: "f03".to_string(),
+                region: Regions::NorthAmerica as u8,
+                power: gib(54.64),
+                price: fil(0.43),
+                ..Default::default()
+            },
+            StorageProvider {
+                id: "f04".to_string(),
+                region: Regions::Other as u8,
+                power: gib(454.64),
+                price: fil(0.143),
+                ..Default::default()
+            },
+            StorageProvider {
+                id: "f05".to_string(),
+                region: Regions::SouthAmerica as u8,
+                power: gib(74.5),
+                price: fil(0.31),
+                ..Default::default()
+            },
+            StorageProvider {
+                id: "f06".to_string(),
+                region: Regions::Africa as u8,
+                power: gib(12.25),
+                price: fil(0.52),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(6, contract.update_storage_providers(sp_list, None).added);
+        let result = contract.delete_storage_providers(vec!["f04".to_string(), "f07".to_string()]);
+        assert_eq!((1, 1), (result.removed, result.skipped));
+        assert_eq!(vec![("f07".to_string(), "storage provider not found".to_string())], result.errors);
+        let result = contract.get_storage_providers();
+
+        assert_eq!(5, result.len());
+        assert_eq!("f01".to_string(), result[0].id);
+        assert_eq!("f02".to_string(), result[1].id);
+        assert_eq!("f03".to_string(), result[2].id);
+        assert_eq!(Regions::Africa as u8, result[3].region);
+        assert_eq!(Regions::SouthAmerica as u8, result[4].region);
+    }
+
+    #[test]
+    #[should_panic(expected = "yoctoNEAR to cover storage")]
+    fn update_storage_providers_without_deposit() {
+        let mut context = get_context();
+        context.attached_deposit = 0;
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    fn update_storage_providers_existing_without_deposit() {
+        let context = get_context();
+        testing_env!(context.clone());
+        let mut contract = FilMarket::new();
+        contract.set_provider_history_depth(1);
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        // updating an existing provider whose history is full doesn't grow the storage
+        let mut context = context;
+        context.attached_deposit = 0;
+        testing_env!(context);
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(20.0), price: fil(0.4), ..Default::default() }];
+        assert_eq!(1, contract.update_storage_providers(sp_list, None).updated);
+    }
+
+    #[test]
+    #[should_panic(expected = "must attach")]
+    fn set_price_per_region_without_deposit() {
+        let mut context = get_context();
+        context.attached_deposit = 0;
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 10, ..Default::default() });
+    }
+
+    #[test]
+    fn delete_storage_providers_keeps_freed_storage() {
+        let context = get_context();
+        testing_env!(context.clone());
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let mut context = context;
+        context.attached_deposit = 0;
+        testing_env!(context);
+        let storage_usage = contract.storage_balance().storage_usage;
+        contract.delete_storage_providers(vec!["f01".to_string()]);
+
+        // the staking of the deleted rows is not paid to the caller
+        assert!(storage_usage > contract.storage_balance().storage_usage);
+        assert!(get_created_receipts().is_empty());
+    }
+
+    #[test]
+    fn update_storage_providers_duplicated_last_wins() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.4), ..Default::default() },
+        ];
+
+        let result = contract.update_storage_providers(sp_list, None);
+        assert_eq!((1, 1), (result.added, result.updated));
+
+        let result = contract.get_storage_providers();
+        assert_eq!(1, result.len());
+        assert_eq!(fil(0.4), result[0].price);
+        assert!(get_logs().iter().any(|log| log.contains("storage provider f01 is duplicated")));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider f01 is duplicated in the batch")]
+    fn update_storage_providers_duplicated_rejected() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.4), ..Default::default() },
+        ];
+
+        contract.update_storage_providers(sp_list, Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider id is longer than 128 bytes")]
+    fn update_storage_providers_rejects_long_id() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01234".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f0".repeat(MAX_ID_LEN), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    fn update_storage_providers_changes_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_ASIA, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_storage_providers();
+        assert_eq!(1, result.len());
+        assert_eq!(REGION_ASIA, result[0].region);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid region 0 for f01")]
+    fn update_storage_providers_invalid_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 0, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE: account_id bob_near does not have role SpManager")]
+    fn update_storage_providers_without_role() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn delete_storage_providers_without_role() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        contract.delete_storage_providers(vec!["f01".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE: account_id bob_near does not have role PriceFeeder")]
+    fn set_price_per_region_without_role() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 10, ..Default::default() });
+    }
+
+    #[test]
+    fn update_storage_providers_min_price() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_min_price(fil(0.1));
+        assert_eq!(fil(0.1), contract.get_min_price());
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.01), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: 3, power: gib(30.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_OTHER, power: gib(40.0), price: fil(0.0), ..Default::default() },
+        ];
+
+        let result = contract.update_storage_providers(sp_list, None);
+        assert_eq!(2, result.added);
+        assert_eq!(2, result.skipped);
+        assert_eq!(("f02".to_string(), "price is below the min price".to_string()), result.errors[0]);
+
+        let ids: Vec<String> = contract.get_storage_providers().iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f01", "f03"], ids);
+        assert!(get_logs().iter().any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"storage_providers_skipped\"") && log.contains("[\"f02\",\"f04\"]")));
+
+        // a zero floor disables the check
+        contract.set_min_price(fil(0.0));
+        let sp_list = vec![StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.01), ..Default::default() }];
+        let result = contract.update_storage_providers(sp_list, None);
+        assert_eq!(1, result.added);
+        assert_eq!(0, result.skipped);
+    }
+
+    #[test]
+    fn update_storage_providers_keeps_label() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.update_storage_providers(vec![StorageProvider {
+            id: "f01234".to_string(),
+            region: 2,
+            power: gib(10.0),
+            price: fil(0.2),
+            label: Some("Acme Storage".to_string()),
+            ..Default::default()
+        }], None);
+
+        contract.update_storage_providers(vec![StorageProvider {
+            id: "f01234".to_string(),
+            region: 2,
+            power: gib(25.0),
+            price: fil(0.2),
+            ..Default::default()
+        }], None);
+
+        let result = contract.get_storage_providers();
+        assert_eq!(gib(25.0), result[0].power);
+        assert_eq!(Some("Acme Storage".to_string()), result[0].label);
+    }
+
+    #[test]
+    #[should_panic(expected = "label of f01234 is longer than 64 bytes")]
+    fn update_storage_providers_rejects_long_label() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.update_storage_providers(vec![StorageProvider {
+            id: "f01234".to_string(),
+            region: 2,
+            power: gib(10.0),
+            price: fil(0.2),
+            label: Some("x".repeat(MAX_LABEL_LEN + 1)),
+            ..Default::default()
+        }], None);
+    }
+
+    #[test]
+    fn patch_provider_price() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        contract.patch_provider("f01".to_string(), None, Some(fil(0.25)), None);
+
+        let result = contract.get_storage_providers();
+        assert_eq!(fil(0.25), result[0].price);
+        assert_eq!(gib(10.0), result[0].power);
+        assert_eq!(REGION_EUROPE, result[0].region);
+
+        contract.patch_provider("f01".to_string(), Some(gib(12.5)), None, Some(REGION_AFRICA));
+
+        let result = contract.get_storage_providers();
+        assert_eq!(fil(0.25), result[0].price);
+        assert_eq!(gib(12.5), result[0].power);
+        assert_eq!(REGION_AFRICA, result[0].region);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid region 7")]
+    fn patch_provider_invalid_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        contract.patch_provider("f01".to_string(), None, None, Some(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider f01 not found")]
+    fn patch_provider_not_found() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.patch_provider("f01".to_string(), None, Some(fil(0.25)), None);
+    }
+
+    #[test]
+    fn set_provider_verified() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: 3, power: gib(30.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        assert!(contract.get_verified_providers(0, 10).is_empty());
+
+        contract.set_provider_verified(vec!["f01".to_string(), "f03".to_string(), "f04".to_string()], true);
+
+        // a price update doesn't reset the flag, even if the incoming entry claims otherwise
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.5), verified: false, ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_verified_providers(0, 10);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f01", "f03"], ids);
+        assert_eq!(fil(0.5), result[0].price);
+
+        let result = contract.get_verified_providers(1, 10);
+        assert_eq!("f03".to_string(), result[0].id);
+
+        contract.set_provider_verified(vec!["f01".to_string()], false);
+        let result = contract.get_verified_providers(0, 10);
+        assert_eq!(1, result.len());
+        assert_eq!("f03".to_string(), result[0].id);
+    }
+
+    #[test]
+    fn get_providers_by_id_prefix() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01234".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02345".to_string(), region: 2, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f012345".to_string(), region: 3, power: gib(30.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f01299".to_string(), region: REGION_OTHER, power: gib(40.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_providers_by_id_prefix("f0123".to_string(), 0, 10);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f01234", "f012345"], ids);
+
+        let result = contract.get_providers_by_id_prefix("f012".to_string(), 1, 1);
+        assert_eq!(1, result.len());
+        assert_eq!("f012345".to_string(), result[0].id);
+
+        assert!(contract.get_providers_by_id_prefix("f03".to_string(), 0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "prefix must not be empty")]
+    fn get_providers_by_empty_id_prefix() {
+        let context = get_context();
+        testing_env!(context);
+        let contract = FilMarket::new();
+
+        contract.get_providers_by_id_prefix("".to_string(), 0, 10);
+    }
+
+    #[test]
+    fn get_normalized_price() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.000_000_1), ..Default::default() },
+            StorageProvider {
+                id: "f02".to_string(),
+                region: 1,
+                power: gib(10.0),
+                price: fil(0.5),
+                price_unit: PRICE_UNIT_FIL_PER_TIB_PER_DAY,
+                ..Default::default()
+            },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        // 1e-7 FIL/GiB/epoch * 1024 GiB/TiB * 2880 epochs/day
+        assert_eq!(Some(fil(0.294_912)), contract.get_normalized_price("f01".to_string()));
+
+        assert_eq!(Some(fil(0.5)), contract.get_normalized_price("f02".to_string()));
+        assert_eq!(None, contract.get_normalized_price("f03".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid price unit 7 for f01")]
+    fn update_storage_providers_invalid_price_unit() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.5), price_unit: 7, ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    fn get_storage_providers_paged() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = (0..5)
+            .map(|i| StorageProvider { id: format!("f0{}", i), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() })
+            .collect();
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(5, contract.get_storage_providers_count());
+
+        let page: Vec<String> = contract.get_storage_providers_paged(0, 2).into_iter().map(|sp| sp.id).collect();
+        assert_eq!(vec!["f00", "f01"], page);
+        let page: Vec<String> = contract.get_storage_providers_paged(4, 2).into_iter().map(|sp| sp.id).collect();
+        assert_eq!(vec!["f04"], page);
+        assert!(contract.get_storage_providers_paged(5, 2).is_empty());
+        assert!(contract.get_storage_providers_paged(u64::MAX, u64::MAX).is_empty());
+        assert_eq!(5, contract.get_storage_providers_paged(0, u64::MAX).len());
+    }
+
+    #[test]
+    fn get_storage_providers_from_cursor() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let ids = |page: &ProviderPage| page.storage_providers.iter().map(|sp| sp.id.clone()).collect::<Vec<String>>();
+        let sp_list = ["f05", "f01", "f04", "f02"].iter()
+            .map(|id| StorageProvider { id: id.to_string(), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() })
+            .collect();
+        contract.update_storage_providers(sp_list, None);
+
+        let page = contract.get_storage_providers_from(None, 2);
+        assert_eq!(vec!["f01", "f02"], ids(&page));
+        assert_eq!(Some("f04".to_string()), page.next_cursor);
+
+        // providers added and removed before the cursor don't shift the next page
+        contract.delete_storage_providers(vec!["f01".to_string()]);
+        let sp_list = vec![StorageProvider { id: "f00".to_string(), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let page = contract.get_storage_providers_from(page.next_cursor, 2);
+        assert_eq!(vec!["f04", "f05"], ids(&page));
+        assert_eq!(None, page.next_cursor);
+
+        assert_eq!(vec!["f04", "f05"], ids(&contract.get_storage_providers_from(Some("f03".to_string()), 10)));
+    }
+
+    #[test]
+    fn get_providers_by_price_range() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let prices = [0.05, 0.1, 0.25, 0.4, 0.5, 0.75];
+        let sp_list = prices.iter().enumerate().map(|(i, price)| StorageProvider {
+            id: format!("f0{}", i),
+            region: 1,
+            power: gib(10.0),
+            price: fil(*price),
+            ..Default::default()
+        }).collect();
+
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_providers_by_price_range(fil(0.1), fil(0.5), 0, 10, None);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f01", "f02", "f03", "f04"], ids);
+
+        let result = contract.get_providers_by_price_range(fil(0.1), fil(0.5), 1, 2, None);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f02", "f03"], ids);
+
+        assert!(contract.get_providers_by_price_range(fil(0.8), fil(1.0), 0, 10, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "min_price must not be greater than max_price")]
+    fn get_providers_by_price_range_inverted_band() {
+        let context = get_context();
+        testing_env!(context);
+        let contract = FilMarket::new();
+
+        contract.get_providers_by_price_range(fil(0.5), fil(0.1), 0, 10, None);
+    }
+
+    #[test]
+    fn get_storage_providers_sorted() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: 3, power: gib(30.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_OTHER, power: gib(40.0), price: fil(0.2), ..Default::default() },
+        ];
+
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_storage_providers_sorted(false, 0, 10);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f02", "f04", "f03", "f01"], ids);
+
+        let result = contract.get_storage_providers_sorted(true, 0, 10);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f01", "f04", "f03", "f02"], ids);
+
+        let result = contract.get_storage_providers_sorted(true, 1, 2);
+        let ids: Vec<String> = result.iter().map(|sp| sp.id.clone()).collect();
+        assert_eq!(vec!["f04", "f03"], ids);
+    }
+
+    #[test]
+    fn paginated_views_are_clamped() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let count = MAX_VIEW_LIMIT + 5;
+        let sp_list: Vec<StorageProvider> = (0..count).map(|i| StorageProvider {
+            id: format!("f0{}", i),
+            region: REGION_EUROPE,
+            power: gib(10.0),
+            price: fil(0.1),
+            ..Default::default()
+        }).collect();
+        // listed in batches that fit the gas of a call, each in a fresh context as separate transactions would be
+        for batch in sp_list.chunks(batches::MAX_BATCH_ITEMS) {
+            testing_env!(get_context());
+            contract.update_storage_providers(batch.to_vec(), None);
+            testing_env!(get_context());
+            contract.set_provider_verified(batch.iter().map(|sp| sp.id.clone()).collect(), true);
+        }
+
+        testing_env!(get_context());
+        let max = MAX_VIEW_LIMIT as usize;
+        assert_eq!(max, contract.get_providers_by_price_range(fil(0.0), fil(1.0), 0, u64::MAX, None).len());
+        assert_eq!(max, contract.get_storage_providers_sorted(false, 0, count).len());
+        assert_eq!(max, contract.get_verified_providers(0, count).len());
+        assert_eq!(max, contract.get_providers_by_id_prefix("f0".to_string(), 0, count).len());
+
+        // the next page holds the remainder
+        assert_eq!(5, contract.get_verified_providers(MAX_VIEW_LIMIT, count).len());
+    }
+
+    #[test]
+    fn get_total_power() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        assert_eq!(U128(0), contract.get_total_power());
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(1.5), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(1024.0), price: fil(0.1), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        assert_eq!(U128(1_101_122_240_512), contract.get_total_power());
+    }
+
+    #[test]
+    fn checked_power_sum_near_max() {
+        assert_eq!(u128::MAX, checked_power_sum(vec![u128::MAX - 1, 1].into_iter()));
+        assert_eq!(0, checked_power_sum(std::iter::empty()));
+    }
+
+    #[test]
+    #[should_panic(expected = "total power overflows u128")]
+    fn get_total_power_overflow() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        // each provider alone is representable, but their sum is past u128::MAX
+        let power = U128(u128::MAX / 3 * 2);
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power, price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power, price: fil(0.1), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        contract.get_total_power();
+    }
+
+    #[test]
+    fn get_storage_provider() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_storage_provider("f01".to_string()).unwrap();
+        assert_eq!(fil(0.3), result.price);
+        assert!(contract.has_storage_provider("f01".to_string()));
+        assert!(contract.get_storage_provider("f02".to_string()).is_none());
+        assert!(!contract.has_storage_provider("f02".to_string()));
+    }
+
+    #[test]
+    fn get_price_summary() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.5), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_price_summary(None);
+        assert_eq!(fil(0.1), result.min);
+        assert_eq!(fil(0.5), result.max);
+        assert_eq!(fil(0.3), result.mean);
+        assert_eq!(3, result.count);
+
+        let result = contract.get_price_summary_by_region(REGION_EUROPE, None);
+        assert_eq!(fil(0.1), result.min);
+        assert_eq!(fil(0.3), result.max);
+        assert_eq!(fil(0.2), result.mean);
+        assert_eq!(2, result.count);
+    }
+
+    #[test]
+    fn get_verified_price_summary() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), verified_price: Some(fil(0.1)), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.5), verified_price: Some(fil(0.3)), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_verified_price_summary(None);
+        assert_eq!(fil(0.1), result.min);
+        assert_eq!(fil(0.2), result.mean);
+        assert_eq!(2, result.count);
+
+        let result = contract.get_verified_price_summary_by_region(REGION_EUROPE, None);
+        assert_eq!(fil(0.1), result.max);
+        assert_eq!(1, result.count);
+        // the regular market still counts every provider
+        assert_eq!(2, contract.get_price_summary_by_region(REGION_EUROPE, None).count);
+    }
+
+    #[test]
+    fn get_price_summary_empty() {
+        let context = get_context();
+        testing_env!(context);
+        let contract = FilMarket::new();
+
+        for result in [contract.get_price_summary(None), contract.get_price_summary_by_region(REGION_AFRICA, None)] {
+            assert_eq!(fil(0.0), result.min);
+            assert_eq!(fil(0.0), result.max);
+            assert_eq!(fil(0.0), result.mean);
+            assert_eq!(0, result.count);
+        }
+    }
+
+    #[test]
+    fn get_providers_grouped_by_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_ASIA, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_EUROPE, power: gib(30.0), price: fil(0.5), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_EUROPE, power: gib(40.0), price: fil(0.5), ..Default::default() },
+            StorageProvider { id: "f05".to_string(), region: REGION_NORTH_AMERICA, power: gib(50.0), price: fil(0.5), ..Default::default() },
+            StorageProvider { id: "f06".to_string(), region: REGION_OTHER, power: gib(60.0), price: fil(0.5), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let ids = |bucket: &Vec<StorageProvider>| bucket.iter().map(|sp| sp.id.clone()).collect::<Vec<String>>();
+
+        let result = contract.get_providers_grouped_by_region(2);
+        assert_eq!(vec!["f01", "f03"], ids(&result.0[&REGION_EUROPE]));
+        assert_eq!(vec!["f02"], ids(&result.0[&REGION_ASIA]));
+        assert_eq!(vec!["f05"], ids(&result.0[&REGION_NORTH_AMERICA]));
+        assert!(!result.0.contains_key(&REGION_SOUTH_AMERICA));
+        assert!(!result.0.contains_key(&REGION_AFRICA));
+        assert_eq!(vec!["f06"], ids(&result.0[&REGION_OTHER]));
+
+        let result = contract.get_providers_grouped_by_region(10);
+        assert_eq!(vec!["f01", "f03", "f04"], ids(&result.0[&REGION_EUROPE]));
+    }
+
+    #[test]
+    fn get_power_per_region_computed() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.5), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.25), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(1024.0), price: fil(0.5), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_SOUTH_AMERICA, power: gib(64.0), price: fil(0.5), ..Default::default() },
+            StorageProvider { id: "f05".to_string(), region: REGION_OTHER, power: gib(2.0), price: fil(0.5), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_power_per_region_computed();
+        assert_eq!(gib(30.75), result.0[&REGION_EUROPE]);
+        assert_eq!(gib(1024.0), result.0[&REGION_ASIA]);
+        assert!(!result.0.contains_key(&REGION_NORTH_AMERICA));
+        assert_eq!(gib(64.0), result.0[&REGION_SOUTH_AMERICA]);
+        assert!(!result.0.contains_key(&REGION_AFRICA));
+        assert_eq!(gib(2.0), result.0[&REGION_OTHER]);
+    }
+
+    #[test]
+    fn active_and_power_per_region_follow_updates() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.5), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.patch_provider("f01".to_string(), Some(gib(15.0)), None, Some(REGION_ASIA));
+        contract.delete_storage_providers(vec!["f02".to_string()]);
+
+        let active = contract.get_active_per_region();
+        assert_eq!(0, active.get(REGION_EUROPE));
+        assert_eq!(2, active.get(REGION_ASIA));
+        let power = contract.get_power_per_region();
+        assert_eq!(U128(0), power.0[&REGION_EUROPE]);
+        assert_eq!(gib(45.0), power.0[&REGION_ASIA]);
+        assert_eq!(gib(45.0), contract.get_power_per_region_computed().0[&REGION_ASIA]);
+    }
+
+    #[test]
+    fn set_active_per_region_is_ignored() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.set_active_per_region(ActivePerRegion(by_region(vec![(REGION_EUROPE, 3), (REGION_ASIA, 24)])));
+
+        let result = contract.get_active_per_region();
+        assert_eq!(1, result.get(REGION_EUROPE));
+        assert_eq!(0, result.get(REGION_ASIA));
+        assert!(contract.get_active_history(0, u64::MAX, 10).iter().all(|(_, active)| active.get(REGION_ASIA) == 0));
+    }
+
+    #[test]
+    fn reassign_regions() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.recompute_active_per_region();
+
+        contract.reassign_regions(vec![
+            ("f01".to_string(), REGION_AFRICA),
+            ("f02".to_string(), REGION_EUROPE),
+            ("f03".to_string(), REGION_SOUTH_AMERICA),
+        ]);
+
+        let result = contract.get_storage_providers();
+        let sp1 = result.iter().find(|sp| sp.id == "f01").unwrap();
+        assert_eq!(REGION_AFRICA, sp1.region);
+        assert_eq!(gib(10.0), sp1.power);
+        assert_eq!(fil(0.3), sp1.price);
+        assert_eq!(REGION_EUROPE, result.iter().find(|sp| sp.id == "f02").unwrap().region);
+        assert_eq!(REGION_SOUTH_AMERICA, result.iter().find(|sp| sp.id == "f03").unwrap().region);
+
+        let result = contract.get_active_per_region();
+        assert_eq!(1, result.get(REGION_EUROPE));
+        assert_eq!(0, result.get(REGION_ASIA));
+        assert_eq!(1, result.get(REGION_SOUTH_AMERICA));
+        assert_eq!(1, result.get(REGION_AFRICA));
+
+        // the incremental counts match a full recount
+        contract.recompute_active_per_region();
+        let recomputed = contract.get_active_per_region();
+        assert_eq!(result.get(REGION_EUROPE), recomputed.get(REGION_EUROPE));
+        assert_eq!(result.get(REGION_SOUTH_AMERICA), recomputed.get(REGION_SOUTH_AMERICA));
+        assert_eq!(result.get(REGION_AFRICA), recomputed.get(REGION_AFRICA));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider f02 not found")]
+    fn reassign_regions_unknown_id() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        contract.reassign_regions(vec![("f01".to_string(), REGION_ASIA), ("f02".to_string(), REGION_ASIA)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 500 assignments per call")]
+    fn reassign_regions_oversized_batch() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let assignments = (0..=MAX_REASSIGN_BATCH).map(|i| (format!("f0{}", i), REGION_ASIA)).collect();
+        contract.reassign_regions(assignments);
+    }
+
+    #[test]
+    fn recompute_active_per_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.active_per_region = ActivePerRegion(by_region(vec![(REGION_EUROPE, 10), (REGION_ASIA, 10)]));
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_NORTH_AMERICA, power: gib(30.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_AFRICA, power: gib(40.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f05".to_string(), region: REGION_OTHER, power: gib(40.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.recompute_active_per_region();
+
+        let result = contract.get_active_per_region();
+        assert_eq!(2, result.get(REGION_EUROPE));
+        assert_eq!(0, result.get(REGION_ASIA));
+        assert_eq!(1, result.get(REGION_NORTH_AMERICA));
+        assert_eq!(0, result.get(REGION_SOUTH_AMERICA));
+        assert_eq!(1, result.get(REGION_AFRICA));
+        assert_eq!(1, result.get(REGION_OTHER));
+    }
+
+    #[test]
+    fn get_active_per_region_total() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        assert_eq!(0, contract.get_active_per_region_total());
+
+        contract.active_per_region = ActivePerRegion(by_region(vec![
+            (REGION_EUROPE, u32::MAX - 20),
+            (REGION_ASIA, 4),
+            (REGION_NORTH_AMERICA, 5),
+            (REGION_SOUTH_AMERICA, 3),
+            (REGION_AFRICA, 2),
+            (REGION_OTHER, 6),
+        ]));
+
+        assert_eq!(u32::MAX, contract.get_active_per_region_total());
+    }
+
+    #[test]
+    #[should_panic(expected = "total of active storage providers overflows u32")]
+    fn get_active_per_region_total_overflow() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.active_per_region = ActivePerRegion(by_region(vec![
+            (REGION_EUROPE, u32::MAX - 20),
+            (REGION_ASIA, 21),
+        ]));
+
+        contract.get_active_per_region_total();
+    }
+
+    #[test]
+    fn set_then_get_price_per_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let price_per_region = PricePerRegion {
+            prices: by_region(vec![
+                (REGION_EUROPE, fil(0.00013)),
+                (REGION_ASIA, fil(0.0004)),
+                (REGION_NORTH_AMERICA, fil(0.0002)),
+                (REGION_SOUTH_AMERICA, fil(0.00025)),
+                (REGION_AFRICA, fil(0.00015)),
+                (REGION_OTHER, fil(0.00005)),
+            ]),
+            global: fil(0.00034),
+            fil_price: fil(64.245),
+            power: U128(1024 * BYTES_PER_TIB),
+            timestamp: 1,
+            verified_prices: by_region(vec![(REGION_EUROPE, fil(0.0001))]),
+            verified_global: Some(fil(0.0003)),
+            bands: BTreeMap::new(),
+        };
+
+        contract.set_price_per_region(price_per_region);
+        let result = contract.get_price_per_region_list();
+
+        assert_eq!(fil(0.00013), result[0].prices[&REGION_EUROPE]);
+        assert_eq!(fil(0.0004), result[0].prices[&REGION_ASIA]);
+        assert_eq!(fil(0.0002), result[0].prices[&REGION_NORTH_AMERICA]);
+        assert_eq!(fil(0.00025), result[0].prices[&REGION_SOUTH_AMERICA]);
+        assert_eq!(fil(0.00015), result[0].prices[&REGION_AFRICA]);
+        assert_eq!(fil(0.00005), result[0].prices[&REGION_OTHER]);
+        assert_eq!(fil(0.00034), result[0].global);
+        assert_eq!(fil(64.245), result[0].fil_price);
+        assert_eq!(U128(1024 * BYTES_PER_TIB), result[0].power);
+        assert_eq!(1, result[0].timestamp);
+        assert_eq!(fil(0.0001), result[0].verified_prices[&REGION_EUROPE]);
+        assert_eq!(Some(fil(0.0003)), result[0].verified_global);
+
+        assert_eq!(1, contract.delete_price_per_region(vec![1, 2]));
+        assert!(contract.get_price_per_region_list().is_empty());
+    }
+
+    fn price_per_region_str(europe: &str) -> PricePerRegionStr {
+        PricePerRegionStr {
+            prices: by_region(vec![
+                (REGION_EUROPE, europe.to_string()),
+                (REGION_ASIA, "0.0004".to_string()),
+                (REGION_NORTH_AMERICA, "0.0002".to_string()),
+                (REGION_SOUTH_AMERICA, "0.00025".to_string()),
+                (REGION_AFRICA, "0.00015".to_string()),
+                (REGION_OTHER, "0.00005".to_string()),
+            ]),
+            global: "0.00034".to_string(),
+            fil_price: "64.245".to_string(),
+            power: U128(1024 * BYTES_PER_TIB),
+            timestamp: 1,
+            verified_prices: by_region(vec![(REGION_EUROPE, "0.0001".to_string())]),
+            verified_global: Some("0.0003".to_string()),
+            bands: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn set_price_per_region_str() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_price_per_region_str(price_per_region_str("0.00013"));
+        let result = contract.get_latest_price_per_region();
+
+        assert_eq!(U128(130_000_000_000_000), result.prices[&REGION_EUROPE]);
+        assert_eq!(fil(0.0004), result.prices[&REGION_ASIA]);
+        assert_eq!(fil(0.00005), result.prices[&REGION_OTHER]);
+        assert_eq!(fil(0.00034), result.global);
+        assert_eq!(fil(64.245), result.fil_price);
+        assert_eq!(U128(1024 * BYTES_PER_TIB), result.power);
+        assert_eq!(1, result.timestamp);
+        assert_eq!(fil(0.0001), result.verified_prices[&REGION_EUROPE]);
+        assert_eq!(Some(fil(0.0003)), result.verified_global);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid decimal 0,00013 for region 2")]
+    fn set_price_per_region_str_malformed() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_price_per_region_str(price_per_region_str("0,00013"));
+    }
+
+    #[test]
+    #[should_panic(expected = "timestamp must not be 0")]
+    fn set_price_per_region_zero_timestamp() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.0003), timestamp: 0, ..Default::default() });
+    }
+
+    #[test]
+    fn set_price_per_region_future_skew() {
+        let mut context = get_context();
+        context.block_timestamp = 1_000 * NANOSECONDS_PER_SECOND;
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        assert_eq!(DEFAULT_MAX_FUTURE_SKEW, contract.get_max_future_skew());
+        contract.set_max_future_skew(60);
+        assert_eq!(60, contract.get_max_future_skew());
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.0003), timestamp: 1_060, ..Default::default() });
+        assert_eq!(1_060, contract.get_latest_price_per_region().timestamp);
+    }
+
+    #[test]
+    #[should_panic(expected = "timestamp 1061 is more than 60 seconds in the future")]
+    fn set_price_per_region_far_future() {
+        let mut context = get_context();
+        context.block_timestamp = 1_000 * NANOSECONDS_PER_SECOND;
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_max_future_skew(60);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.0003), timestamp: 1_061, ..Default::default() });
+    }
+
+    #[test]
+    fn price_per_region_ordered_by_timestamp() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        for timestamp in [30, 10, 50, 20] {
+            contract.set_price_per_region(PricePerRegion { global: U128(timestamp as u128), timestamp, ..Default::default() });
+        }
+
+        let timestamps: Vec<u64> = contract.get_price_per_region_list().iter().map(|ppr| ppr.timestamp).collect();
+        assert_eq!(vec![10, 20, 30, 50], timestamps);
+
+        // inserting an older entry doesn't rewind the latest one
+        let latest = contract.get_latest_price_per_region();
+        assert_eq!(50, latest.timestamp);
+        assert_eq!(U128(50), latest.global);
+        assert_eq!(50, contract.get_dashboard().latest_timestamp);
+
+        // deleting the latest entry falls back to the greatest remaining timestamp
+        contract.delete_price_per_region(vec![50]);
+        assert_eq!(30, contract.get_latest_price_per_region().timestamp);
+        assert_eq!(30, contract.get_dashboard().latest_timestamp);
+
+        contract.delete_price_per_region(vec![10, 20, 30]);
+        assert_eq!(0, contract.get_latest_price_per_region().timestamp);
+        assert_eq!(0, contract.get_dashboard().latest_timestamp);
+    }
+
+    #[test]
+    fn backfill_price_per_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+        contract.set_price_guards(0, true);
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.5), timestamp: 50, ..Default::default() });
+        contract.backfill_price_per_region(PricePerRegion { global: fil(0.3), timestamp: 30, ..Default::default() });
+
+        assert_eq!(50, contract.get_latest_price_per_region().timestamp);
+        assert_eq!(fil(0.3), contract.get_price_at(30).unwrap().global);
+        assert!(get_logs().iter().any(|log| log.contains("\"price_per_region_backfilled\"")));
+    }
+
+    #[test]
+    #[should_panic(expected = "timestamp 50 is not older than the latest entry 50, use set_price_per_region")]
+    fn backfill_latest_price_per_region() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        contract.set_price_per_region(PricePerRegion { global: fil(0.5), timestamp: 50, ..Default::default() });
+        contract.backfill_price_per_region(PricePerRegion { global: fil(0.3), timestamp: 50, ..Default::default() });
+    }
+
+    #[test]
+    fn delete_price_per_region_before() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        for timestamp in [10, 20, 30, 40, 50] {
+            contract.set_price_per_region(PricePerRegion { global: fil(0.0003), timestamp, ..Default::default() });
+        }
+
+        assert_eq!(2, contract.delete_price_per_region_before(35, 2));
+        assert_eq!(1, contract.delete_price_per_region_before(35, 2));
+        assert_eq!(0, contract.delete_price_per_region_before(35, 2));
+
+        let timestamps: Vec<u64> = contract.get_price_per_region_list().iter().map(|ppr| ppr.timestamp).collect();
+        assert_eq!(vec![40, 50], timestamps);
+        assert_eq!(50, contract.get_latest_price_per_region().timestamp);
+
+        // pruning the latest entry resets it
+        assert_eq!(2, contract.delete_price_per_region_before(100, 10));
+        assert_eq!(0, contract.get_price_history_meta().count);
+        assert_eq!(0, contract.get_dashboard().latest_timestamp);
+    }
+
+    #[test]
+    fn get_price_history_meta() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let result = contract.get_price_history_meta();
+        assert_eq!(0, result.count);
+        assert_eq!(0, result.earliest_ts);
+        assert_eq!(0, result.latest_ts);
+
+        for timestamp in [200, 100, 300] {
+            contract.set_price_per_region(PricePerRegion { global: fil(0.0003), timestamp, ..Default::default() });
+        }
+
+        let result = contract.get_price_history_meta();
+        assert_eq!(3, result.count);
+        assert_eq!(100, result.earliest_ts);
+        assert_eq!(300, result.latest_ts);
+    }
+
+    #[test]
+    fn get_global_price_stats() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let result = contract.get_global_price_stats();
+        assert_eq!(0, result.count);
+        assert_eq!(fil(0.0), result.mean);
+        assert_eq!(0, result.first_ts);
+
+        for (timestamp, global) in [(30, 0.0004), (10, 0.0001), (20, 0.0007)] {
+            contract.set_price_per_region(PricePerRegion { global: fil(global), timestamp, ..Default::default() });
+        }
+
+        let result = contract.get_global_price_stats();
+        assert_eq!(fil(0.0001), result.min);
+        assert_eq!(fil(0.0007), result.max);
+        assert_eq!(fil(0.0004), result.mean);
+        assert_eq!(3, result.count);
+        assert_eq!(10, result.first_ts);
+        assert_eq!(30, result.last_ts);
+    }
+
+    #[test]
+    fn get_config() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let result = contract.get_config();
+        assert_eq!(MAX_VIEW_LIMIT, result.max_view_limit);
+        assert_eq!(128, result.max_id_len);
+        assert_eq!(64, result.max_label_len);
+        assert_eq!(fil(0.0), result.min_price);
+        assert_eq!(DEFAULT_MAX_FUTURE_SKEW, result.max_future_skew);
+        assert_eq!(U128(price_candidates::PRICE_CANDIDATE_DEPOSIT), result.price_candidate_deposit);
+        assert_eq!(provider_history::DEFAULT_PROVIDER_HISTORY_DEPTH, result.provider_history_depth);
+        assert_eq!(batches::MAX_BATCH_ITEMS as u32, result.max_batch_items);
+        assert_eq!(0, result.price_retention);
+        assert_eq!(price_rounds::DEFAULT_FEEDER_QUORUM, result.feeder_quorum);
+        assert_eq!(staleness::DEFAULT_MAX_PRICE_AGE, result.max_price_age);
+        assert!(!result.paused);
+        assert!(result.paused_features.is_empty());
+        assert_eq!(PriceGuards::default(), result.price_guards);
+        assert_eq!(None, result.governance);
+
+        contract.set_min_price(fil(0.05));
+        contract.set_max_future_skew(60);
+
+        let result = contract.get_config();
+        assert_eq!(fil(0.05), result.min_price);
+        assert_eq!(60, result.max_future_skew);
+    }
+
+    #[test]
+    fn get_summary() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let result = contract.get_summary();
+        assert_eq!(0, result.provider_count);
+        assert_eq!(0, result.price_entry_count);
+        assert_eq!(carol(), result.owner);
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.1), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 10, ..Default::default() });
+        contract.set_price_per_region(PricePerRegion { global: fil(0.2), timestamp: 20, ..Default::default() });
+
+        assert_eq!(2, contract.get_provider_count());
+        assert_eq!(2, contract.get_price_entry_count());
+        let result = contract.get_summary();
+        assert_eq!(2, result.provider_count);
+        assert_eq!(2, result.price_entry_count);
+        assert_eq!(gib(30.0), result.total_power);
+        assert_eq!(20, result.latest_timestamp);
+    }
+
+    #[test]
+    fn get_dashboard() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let result = contract.get_dashboard();
+        assert!(result.latest_price_per_region.is_none());
+        assert_eq!(0, result.storage_providers_count);
+        assert_eq!(0, result.latest_timestamp);
+        assert_eq!(PriceStatus::Missing, result.price_status);
+        assert_eq!(None, result.price_age_seconds);
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.1), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.active_per_region = ActivePerRegion(by_region(vec![
+            (REGION_EUROPE, 1),
+            (REGION_ASIA, 2),
+            (REGION_NORTH_AMERICA, 3),
+            (REGION_SOUTH_AMERICA, 5),
+            (REGION_AFRICA, 6),
+            (REGION_OTHER, 4),
+        ]));
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 10, ..Default::default() });
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00035), timestamp: 20, ..Default::default() });
+
+        let result = contract.get_dashboard();
+        let latest = result.latest_price_per_region.unwrap();
+        assert_eq!(20, latest.timestamp);
+        assert_eq!(fil(0.00035), latest.global);
+        assert_eq!(1, result.active_per_region.get(REGION_EUROPE));
+        assert_eq!(2, result.active_per_region.get(REGION_ASIA));
+        assert_eq!(3, result.active_per_region.get(REGION_NORTH_AMERICA));
+        assert_eq!(5, result.active_per_region.get(REGION_SOUTH_AMERICA));
+        assert_eq!(6, result.active_per_region.get(REGION_AFRICA));
+        assert_eq!(4, result.active_per_region.get(REGION_OTHER));
+        assert_eq!(2, result.storage_providers_count);
+        assert_eq!(20, result.latest_timestamp);
+        assert_eq!(gib(30.0), result.total_power);
+        assert_eq!(20, result.last_updated);
+        assert_eq!(PriceStatus::Fresh, result.price_status);
+    }
+
+    #[test]
+    fn get_price_history() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        for timestamp in [40, 10, 30, 20, 50] {
+            contract.set_price_per_region(PricePerRegion { global: U128(timestamp as u128), timestamp, ..Default::default() });
+        }
+
+        let timestamps = |prices: Vec<PricePerRegion>| prices.iter().map(|ppr| ppr.timestamp).collect::<Vec<u64>>();
+        assert_eq!(vec![20, 30, 40], timestamps(contract.get_price_history(15, 40, 10)));
+        assert_eq!(vec![10, 20], timestamps(contract.get_price_history(0, u64::MAX, 2)));
+        assert_eq!(vec![30], timestamps(contract.get_price_history(30, 30, 10)));
+        assert!(contract.get_price_history(51, 60, 10).is_empty());
+
+        assert_eq!(vec![40, 50], timestamps(contract.get_latest_n_prices(2)));
+        assert_eq!(vec![10, 20, 30, 40, 50], timestamps(contract.get_latest_n_prices(10)));
+        assert!(contract.get_latest_n_prices(0).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "from_timestamp must not be greater than to_timestamp")]
+    fn get_price_history_inverted_range() {
+        let context = get_context();
+        testing_env!(context);
+        let contract = FilMarket::new();
+
+        contract.get_price_history(20, 10, 10);
+    }
+
+    #[test]
+    fn get_price_nearest() {
+        let context = get_context();
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        for (timestamp, global) in [(10, 0.1), (20, 0.2), (30, 0.3)] {
+            contract.set_price_per_region(PricePerRegion { global: fil(global), timestamp, ..Default::default() });
+        }
+
+        let result = contract.get_price_nearest(25).unwrap();
+        assert_eq!(20, result.timestamp);
+        assert_eq!(fil(0.2), result.global);
+        assert_eq!(30, contract.get_price_nearest(30).unwrap().timestamp);
+        assert_eq!(30, contract.get_price_nearest(u64::MAX).unwrap().timestamp);
+        assert!(contract.get_price_nearest(5).is_none());
+
+        assert_eq!(10, contract.get_price_at(19).unwrap().timestamp);
+        assert!(contract.get_price_at(9).is_none());
+    }
+
+    #[test]
+    fn get_health() {
+        let mut context = get_context();
+        context.block_timestamp = 1_000 * NANOSECONDS_PER_SECOND;
+        testing_env!(context);
+        let mut contract = FilMarket::new();
+
+        let result = contract.get_health();
+        assert_eq!(0, result.provider_count);
+        assert!(!result.has_prices);
+        assert_eq!(None, result.latest_price_age_seconds);
+        assert!(!result.paused);
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: 2, power: gib(20.0), price: fil(0.1), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 900, ..Default::default() });
+
+        let result = contract.get_health();
+        assert_eq!(2, result.provider_count);
+        assert!(result.has_prices);
+        assert_eq!(Some(100), result.latest_price_age_seconds);
+        assert!(!result.paused);
+    }
+
+    #[test]
+    fn get_latest_price_if_fresh() {
+        let mut context = get_context();
+        context.block_timestamp = 1_000 * NANOSECONDS_PER_SECOND;
+        testing_env!(context.clone());
+        let mut contract = FilMarket::new();
+
+        let price_per_region = PricePerRegion {
+            global: fil(0.00034),
+            timestamp: 900,
+            ..Default::default()
+        };
+
+        contract.set_price_per_region(price_per_region);
+
+        let result = contract.get_latest_price_if_fresh(100);
+        assert_eq!(900, result.unwrap().timestamp);
+
+        // the feed stalled: one hour later the same entry is stale
+        context.block_timestamp = 4_600 * NANOSECONDS_PER_SECOND;
+        testing_env!(context);
+        assert!(contract.get_latest_price_if_fresh(100).is_none());
+        assert!(contract.get_latest_price_if_fresh(3_700).is_some());
+    }
+
+    #[test]
+    fn get_latest_price_if_fresh_without_prices() {
+        let context = get_context();
+        testing_env!(context);
+        let contract = FilMarket::new();
+
+        assert!(contract.get_latest_price_if_fresh(u64::MAX).is_none());
     }
 }
@@ -0,0 +1,243 @@
+/*
+ * Listing fees for self-registered storage providers: before register_provider accepts a listing, its account pays
+ * the listing fee in NEAR or in payment tokens from its token balance. With a period the fee pays for that many
+ * seconds of listing, otherwise once for good. Fees accumulate in a treasury the owner withdraws from
+ */
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeSchedule {
+    pub listing_fee: U128,               // yoctoNEAR, 0 disables the fee in NEAR
+    pub token_listing_fee: Option<U128>, // payment tokens, None if the fee can't be paid in tokens
+    pub period: u64,                     // seconds paid for by a fee, 0 for a one-time fee
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self { listing_fee: U128(0), token_listing_fee: None, period: 0 }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeTreasury {
+    pub balance: U128,       // yoctoNEAR
+    pub token_balance: U128, // payment tokens
+}
+
+impl FilMarket {
+    // whether self-registration costs a fee
+    fn has_listing_fee(&self) -> bool {
+        self.fee_schedule.listing_fee.0 > 0 || self.fee_schedule.token_listing_fee.is_some()
+    }
+
+    // fail the call unless the listing of the storage provider is paid for, when a fee is set
+    pub(crate) fn assert_listing_paid(&self, id: &String) {
+        if !self.has_listing_fee() {
+            return;
+        }
+
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        require!(
+            self.listing_paid_until.get(id).is_some_and(|paid_until| paid_until > now),
+            format!("the listing fee of storage provider {} is not paid", id)
+        );
+    }
+
+    // extend the paid listing of a storage provider by one fee
+    fn extend_listing(&mut self, id: &String) -> u64 {
+        let paid_until = match self.fee_schedule.period {
+            0 => u64::MAX,
+            period => {
+                let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+                self.listing_paid_until.get(id).unwrap_or(0).max(now).saturating_add(period)
+            }
+        };
+
+        self.listing_paid_until.insert(id, &paid_until);
+        paid_until
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the listing fee in NEAR and optionally in payment tokens, and the seconds a fee pays for (0 for one-time)
+    pub fn set_fee_schedule(&mut self, listing_fee: U128, token_listing_fee: Option<U128>, period: u64) {
+        self.assert_config_authority();
+
+        self.fee_schedule = FeeSchedule { listing_fee, token_listing_fee, period };
+        self.emit_event("fee_schedule_set", json!({ "fee_schedule": self.fee_schedule }));
+    }
+
+    // pay the listing fee of a storage provider controlled by the caller in NEAR
+    // the attached deposit must cover the fee and the storage of the payment, the rest is refunded
+    #[payable]
+    pub fn pay_listing_fee(&mut self, id: String) -> u64 {
+        self.assert_not_paused(Feature::Providers);
+        self.assert_provider_account(&id);
+        let fee = self.fee_schedule.listing_fee.0;
+        require!(fee > 0, "no listing fee in NEAR is set");
+
+        let initial_storage_usage = env::storage_usage();
+        let paid_until = self.extend_listing(&id);
+        let deposit = self.deposit_after_storage(initial_storage_usage);
+        require!(deposit >= fee, format!("must attach {} yoctoNEAR for the listing fee", fee));
+
+        self.fee_treasury = self.fee_treasury.checked_add(fee).expect("fee treasury overflows u128");
+        if deposit > fee {
+            Promise::new(env::predecessor_account_id()).transfer(deposit - fee);
+        }
+
+        self.emit_event("listing_fee_paid", json!({
+            "id": id,
+            "fee": U128(fee),
+            "paid_until": paid_until,
+        }));
+        paid_until
+    }
+
+    // pay the listing fee of a storage provider controlled by the caller from its payment token balance
+    // the attached deposit must cover the storage of the payment, the rest is refunded
+    #[payable]
+    pub fn pay_listing_fee_with_tokens(&mut self, id: String) -> u64 {
+        self.assert_not_paused(Feature::Providers);
+        self.assert_provider_account(&id);
+        let fee = self.fee_schedule.token_listing_fee.expect("no listing fee in tokens is set").0;
+
+        let initial_storage_usage = env::storage_usage();
+        self.debit_tokens(&env::predecessor_account_id(), fee);
+        let paid_until = self.extend_listing(&id);
+        self.refund_deposit(initial_storage_usage);
+
+        self.fee_treasury_tokens = self.fee_treasury_tokens.checked_add(fee).expect("fee treasury overflows u128");
+        self.emit_event("listing_fee_paid", json!({
+            "id": id,
+            "token_fee": U128(fee),
+            "paid_until": paid_until,
+        }));
+        paid_until
+    }
+
+    // transfer NEAR fees to receiver_id
+    pub fn withdraw_fees(&mut self, receiver_id: AccountId, amount: U128) -> Promise {
+        self.assert_owner();
+        require!(amount.0 > 0 && amount.0 <= self.fee_treasury, format!("the fee treasury holds {} yoctoNEAR", self.fee_treasury));
+
+        self.fee_treasury -= amount.0;
+        self.emit_event("fees_withdrawn", json!({
+            "receiver_id": receiver_id,
+            "amount": amount,
+        }));
+        Promise::new(receiver_id).transfer(amount.0)
+    }
+
+    // move token fees to the token balance of receiver_id, withdrawn from there with withdraw_tokens
+    pub fn withdraw_token_fees(&mut self, receiver_id: AccountId, amount: U128) {
+        self.assert_owner();
+        require!(
+            amount.0 > 0 && amount.0 <= self.fee_treasury_tokens,
+            format!("the fee treasury holds {} payment tokens", self.fee_treasury_tokens)
+        );
+
+        self.fee_treasury_tokens -= amount.0;
+        self.credit_tokens(&receiver_id, amount.0);
+        self.emit_event("fees_withdrawn", json!({
+            "receiver_id": receiver_id,
+            "token_amount": amount,
+        }));
+    }
+
+    pub fn get_fee_schedule(&self) -> FeeSchedule {
+        self.fee_schedule.clone()
+    }
+
+    pub fn get_fee_treasury(&self) -> FeeTreasury {
+        FeeTreasury { balance: U128(self.fee_treasury), token_balance: U128(self.fee_treasury_tokens) }
+    }
+
+    // get until when the listing of a storage provider is paid, in seconds, None if it never was
+    pub fn get_listing_paid_until(&self, id: String) -> Option<u64> {
+        self.listing_paid_until.get(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    fn provider() -> StorageProvider {
+        StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }
+    }
+
+    fn claimed_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+
+        set_context("miner_near", NEAR, 100);
+        contract.claim_provider("f01234".to_string());
+        set_context("carol_near", NEAR, 100);
+        contract.approve_provider_claim("f01234".to_string());
+        contract
+    }
+
+    #[test]
+    fn recurring_listing_fee() {
+        let mut contract = claimed_contract();
+        contract.set_fee_schedule(U128(NEAR / 10), None, 1_000);
+
+        set_context("miner_near", NEAR, 100);
+        assert_eq!(1_100, contract.pay_listing_fee("f01234".to_string()));
+        assert_eq!(2_100, contract.pay_listing_fee("f01234".to_string()));
+        contract.register_provider(provider());
+        assert_eq!(U128(NEAR / 5), contract.get_fee_treasury().balance);
+
+        set_context("carol_near", NEAR, 100);
+        contract.withdraw_fees(account("treasury_near"), U128(NEAR / 5));
+        assert_eq!(U128(0), contract.get_fee_treasury().balance);
+    }
+
+    #[test]
+    #[should_panic(expected = "the listing fee of storage provider f01234 is not paid")]
+    fn register_provider_after_listing_expired() {
+        let mut contract = claimed_contract();
+        contract.set_fee_schedule(U128(NEAR / 10), None, 1_000);
+
+        set_context("miner_near", NEAR, 100);
+        contract.pay_listing_fee("f01234".to_string());
+
+        set_context("miner_near", NEAR, 1_100);
+        contract.register_provider(provider());
+    }
+
+    #[test]
+    fn listing_fee_in_tokens() {
+        let mut contract = claimed_contract();
+        contract.set_payment_token(Some(account("token_near")));
+        contract.set_fee_schedule(U128(0), Some(U128(500)), 0);
+
+        set_context("token_near", NEAR, 100);
+        contract.ft_on_transfer(account("miner_near"), U128(800), String::new());
+
+        set_context("miner_near", NEAR, 100);
+        assert_eq!(u64::MAX, contract.pay_listing_fee_with_tokens("f01234".to_string()));
+        contract.register_provider(provider());
+        assert_eq!(U128(300), contract.get_token_balance(account("miner_near")));
+
+        set_context("carol_near", NEAR, 100);
+        contract.withdraw_token_fees(account("treasury_near"), U128(500));
+        assert_eq!(U128(500), contract.get_token_balance(account("treasury_near")));
+        assert_eq!(U128(0), contract.get_fee_treasury().token_balance);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn withdraw_fees_by_other_account() {
+        let mut contract = claimed_contract();
+
+        set_context("miner_near", NEAR, 100);
+        contract.withdraw_fees(account("miner_near"), U128(1));
+    }
+}
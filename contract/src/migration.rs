@@ -0,0 +1,355 @@
+/*
+ * Versioned state: the layout version is stored under its own key next to the contract state, so migrate() knows
+ * how to read the state it converts. A schema change adds a StateVersion and a conversion from the previous one
+ */
+
+use crate::*;
+use crate::provider_ids::parse_id_address;
+
+// storage key of the state version, separate from the state so it can be read whatever the layout
+const STATE_VERSION_KEY: &[u8] = b"VERSION";
+
+// layouts of the contract state, in deployment order
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum StateVersion {
+    V1, // the original layout with float prices and fixed regions, stored without a version
+    V2, // the layout of this release, written by new() and by the conversion from V1
+}
+
+// the layout written by this code
+pub const CURRENT_STATE_VERSION: StateVersion = StateVersion::V2;
+
+// the version of the stored state, V1 if none was written
+fn read_state_version() -> StateVersion {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|bytes| StateVersion::try_from_slice(&bytes).expect("failed to read the state version"))
+        .unwrap_or(StateVersion::V1)
+}
+
+pub(crate) fn write_state_version(version: StateVersion) {
+    env::storage_write(STATE_VERSION_KEY, &version.try_to_vec().expect("failed to write the state version"));
+}
+
+// ActivePerRegion as stored before regions moved to a registry
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+pub struct ActivePerRegionV1 {
+    europe: u32,
+    asia: u32,
+    north_america: u32,
+    other: u32,
+}
+
+// PricePerRegion as stored before regions moved to a registry and prices moved to fixed-point
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+pub struct PricePerRegionV1 {
+    europe: f64,
+    asia: f64,
+    north_america: f64,
+    other: f64,
+    global: f64,
+    fil_price: f64,
+    power: u128,
+    timestamp: u64,
+}
+
+// StorageProvider as stored before the label, verified, price_unit and last_updated fields were added
+// and before power and price moved to fixed-point integers
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageProviderV1 {
+    id: String,
+    region: u8,
+    power: f64,
+    price: f64,
+}
+
+// FilMarket as stored before the migration
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FilMarketV1 {
+    storage_providers: UnorderedMap<String, StorageProviderV1>,
+    price_per_region: UnorderedMap<u64, PricePerRegionV1>,
+    active_per_region: ActivePerRegionV1,
+    latest_timestamp: u64,
+    owner: String,
+}
+
+// convert a FIL or USD float to fixed-point, negative or non-finite values become 0
+fn fil_to_scaled(value: f64) -> u128 {
+    decimal::f64_to_scaled(value, decimal::PRICE_DECIMALS).unwrap_or(0)
+}
+
+// convert a GiB float to bytes, exact since a GiB is a power of 2, negative or non-finite values become 0
+fn gib_to_bytes(power: f64) -> u128 {
+    (power * BYTES_PER_GIB as f64) as u128
+}
+
+// convert a network power in TiB to bytes
+fn tib_to_bytes(power: u128) -> u128 {
+    power.checked_mul(BYTES_PER_TIB).expect("network power in bytes overflows u128")
+}
+
+impl FilMarket {
+    // convert the V1 state to the current layout
+    // note: every storage provider and price entry is rewritten, so the gas cost grows with the stored data
+    // storage provider ids are normalized as mainnet ids, the malformed ones and the duplicates are logged and dropped
+    fn migrate_from_v1() -> Self {
+        let mut old: FilMarketV1 = env::state_read().expect("failed to read the contract state");
+
+        let old_storage_providers = old.storage_providers.values_as_vector().to_vec();
+        old.storage_providers.clear();
+
+        let mut storage_providers = UnorderedMap::new(StorageKey::StorageProviders);
+        for sp in old_storage_providers.into_iter() {
+            let id = match parse_id_address(&sp.id, FilecoinNetwork::Mainnet) {
+                Ok(id) => id,
+                Err(reason) => {
+                    env::log_str(&format!("migrate(): dropped storage provider {}: {}", sp.id, reason));
+                    continue;
+                }
+            };
+            if storage_providers.get(&id).is_some() {
+                env::log_str(&format!("migrate(): dropped storage provider {}: duplicate of {}", sp.id, id));
+                continue;
+            }
+
+            let storage_provider = StorageProvider {
+                id,
+                region: sp.region,
+                power: U128(gib_to_bytes(sp.power)),
+                price: U128(fil_to_scaled(sp.price)),
+                label: None,
+                verified: false,
+                price_unit: PRICE_UNIT_FIL_PER_GIB_PER_EPOCH,
+                last_updated: env::block_timestamp(),
+                reputation: reputation::NEUTRAL_REPUTATION,
+                metadata: ProviderMetadata::default(),
+                verified_price: None,
+                status: ProviderStatus::Active,
+                retrieval_price: None,
+                listed_at: 0,
+                tier: ProviderTier::Bronze,
+            };
+
+            storage_providers.insert(&storage_provider.id, &storage_provider);
+        }
+
+        let old_price_per_region = old.price_per_region.values_as_vector().to_vec();
+        old.price_per_region.clear();
+
+        let mut price_per_region = TreeMap::new(StorageKey::PricePerRegion);
+        for ppr in old_price_per_region.into_iter() {
+            let prices = vec![
+                (REGION_EUROPE, ppr.europe),
+                (REGION_ASIA, ppr.asia),
+                (REGION_NORTH_AMERICA, ppr.north_america),
+                (REGION_OTHER, ppr.other),
+            ];
+            let ppr = PricePerRegion {
+                prices: prices.into_iter().map(|(region, price)| (region, U128(fil_to_scaled(price)))).collect(),
+                global: U128(fil_to_scaled(ppr.global)),
+                fil_price: U128(fil_to_scaled(ppr.fil_price)),
+                power: U128(tib_to_bytes(ppr.power)),
+                timestamp: ppr.timestamp,
+                verified_prices: BTreeMap::new(),
+                verified_global: None,
+                bands: BTreeMap::new(),
+            };
+
+            price_per_region.insert(&ppr.timestamp, &ppr);
+        }
+
+        env::log_str(&format!("migrate(): storage providers {} price entries {}", storage_providers.len(), price_per_region.len()));
+
+        // the latest entry is the greatest timestamp, whatever was set last
+        let latest_timestamp = price_per_region.max().unwrap_or(0);
+
+        let mut owner_history = Vector::new(StorageKey::OwnerHistory);
+        let owner = AccountId::new_unchecked(old.owner);
+        owner_history.push(&(owner.clone(), env::block_height()));
+
+        let mut this = Self {
+            storage_providers,
+            price_per_region,
+            active_per_region: ActivePerRegion::default(), // counted from the providers by reindex_provider
+            latest_timestamp,
+            owner,
+            min_price: 0,
+            pending_owner: None,
+            owner_history,
+            max_future_skew: DEFAULT_MAX_FUTURE_SKEW,
+            event_seq: 0,
+            pending_prices: UnorderedMap::new(StorageKey::PendingPrices),
+            roles: UnorderedMap::new(StorageKey::Roles),
+            provider_claims: UnorderedMap::new(StorageKey::ProviderClaims),
+            provider_accounts: UnorderedMap::new(StorageKey::ProviderAccounts),
+            provider_history: UnorderedMap::new(StorageKey::ProviderHistory),
+            provider_history_depth: provider_history::DEFAULT_PROVIDER_HISTORY_DEPTH,
+            storage_requests: UnorderedMap::new(StorageKey::StorageRequests),
+            offers: UnorderedMap::new(StorageKey::Offers),
+            deals: UnorderedMap::new(StorageKey::Deals),
+            next_request_id: 0,
+            next_offer_id: 0,
+            next_deal_id: 0,
+            deal_escrow: UnorderedMap::new(StorageKey::DealEscrow),
+            payment_token: None,
+            token_balances: UnorderedMap::new(StorageKey::TokenBalances),
+            deal_token_escrow: UnorderedMap::new(StorageKey::DealTokenEscrow),
+            reputations: UnorderedMap::new(StorageKey::Reputations),
+            regions: regions::default_regions(),
+            region_index: UnorderedMap::new(StorageKey::RegionIndex),
+            price_index: TreeMap::new(StorageKey::PriceIndex),
+            power_index: TreeMap::new(StorageKey::PowerIndex),
+            paused: false,
+            paused_features: Vec::new(),
+            batches: UnorderedMap::new(StorageKey::Batches),
+            price_retention: 0,
+            price_rounds: UnorderedMap::new(StorageKey::PriceRounds),
+            feeder_quorum: price_rounds::DEFAULT_FEEDER_QUORUM,
+            max_price_age: staleness::DEFAULT_MAX_PRICE_AGE,
+            last_heartbeat: 0,
+            subscribers: UnorderedMap::new(StorageKey::Subscribers),
+            denied_providers: UnorderedMap::new(StorageKey::DeniedProviders),
+            price_guards: PriceGuards::default(),
+            power_per_region: PowerPerRegion::default(),
+            active_history: TreeMap::new(StorageKey::ActiveHistory),
+            admin_log: Vector::new(StorageKey::AdminLog),
+            admin_log_len: 0,
+            council: None,
+            proposals: UnorderedMap::new(StorageKey::Proposals),
+            next_proposal_id: 0,
+            governance: None,
+            fee_schedule: FeeSchedule::default(),
+            listing_paid_until: UnorderedMap::new(StorageKey::ListingPaidUntil),
+            fee_treasury: 0,
+            fee_treasury_tokens: 0,
+            orders: UnorderedMap::new(StorageKey::Orders),
+            order_book: TreeMap::new(StorageKey::OrderBook),
+            next_order_id: 0,
+            filecoin_network: FilecoinNetwork::Mainnet,
+            code_hash: None,
+            removed_providers: UnorderedMap::new(StorageKey::RemovedProviders),
+            retrieval_prices: TreeMap::new(StorageKey::RetrievalPrices),
+            latest_retrieval_timestamp: 0,
+            provider_sla: UnorderedMap::new(StorageKey::ProviderSla),
+            feeder_bonds: UnorderedMap::new(StorageKey::FeederBonds),
+            feeder_bond: 0,
+            feeder_unbonding_period: feeder_bonds::DEFAULT_FEEDER_UNBONDING_PERIOD,
+            tier_config: TierConfig::default(),
+            reviews: UnorderedMap::new(StorageKey::Reviews),
+            last_review_at: UnorderedMap::new(StorageKey::LastReviewAt),
+            review_deposit: reviews::DEFAULT_REVIEW_DEPOSIT,
+            provider_ids: TreeMap::new(StorageKey::ProviderIds),
+            updated_index: TreeMap::new(StorageKey::UpdatedIndex),
+            removed_index: TreeMap::new(StorageKey::RemovedIndex),
+            oracle_key: None,
+            oracle_nonce: 0,
+            approved_subscribers: UnorderedSet::new(StorageKey::ApprovedSubscribers),
+            admin_log_storage: 0,
+            privileged_caller: Cell::new(None),
+        };
+
+        for sp in this.storage_providers.values_as_vector().to_vec() {
+            this.reindex_provider(None, Some(&sp));
+            this.updated_index.insert(&(sp.last_updated, sp.id.clone()), &());
+        }
+
+        this
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // migrate the state of the previously deployed contract to the current layout, called right after deploying
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let version = read_state_version();
+        let this = match version {
+            StateVersion::V1 => Self::migrate_from_v1(),
+            StateVersion::V2 => panic!("the state is already at version {:?}", version),
+        };
+
+        write_state_version(CURRENT_STATE_VERSION);
+        env::log_str(&format!("migrate(): state version {:?} to {:?}", version, CURRENT_STATE_VERSION));
+        this
+    }
+
+    // get the layout version of the stored state
+    pub fn get_version(&self) -> StateVersion {
+        read_state_version()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, set_context};
+
+    #[test]
+    fn migrate_state() {
+        set_context("carol_near", 0, 0);
+
+        let mut old = FilMarketV1 {
+            storage_providers: UnorderedMap::new(StorageKey::StorageProviders),
+            price_per_region: UnorderedMap::new(b"b".to_vec()),
+            active_per_region: ActivePerRegionV1 { europe: 1, asia: 2, north_america: 3, other: 4 },
+            latest_timestamp: 20,
+            owner: "carol_near".to_string(),
+        };
+        for (id, price) in [("f01", 0.1), ("F02 ", 0.2), ("f1abc", 0.3), ("F01", 0.4)] {
+            let sp = StorageProviderV1 { id: id.to_string(), region: 2, power: 10.0, price };
+            old.storage_providers.insert(&sp.id, &sp);
+        }
+        for timestamp in [10, 20] {
+            let ppr = PricePerRegionV1 { europe: 0.1, other: 0.4, global: 0.2, power: 3, timestamp, ..Default::default() };
+            old.price_per_region.insert(&timestamp, &ppr);
+        }
+        env::state_write(&old);
+
+        let contract = FilMarket::migrate();
+        assert_eq!(CURRENT_STATE_VERSION, contract.get_version());
+        let result = contract.get_storage_providers();
+
+        // the malformed id and the duplicate of f01 are dropped
+        assert_eq!(2, result.len());
+        assert_eq!("f01".to_string(), result[0].id);
+        assert_eq!(U128(100_000_000_000_000_000), result[0].price);
+        assert_eq!("f02".to_string(), result[1].id);
+        assert_eq!(U128(200_000_000_000_000_000), result[1].price);
+        assert_eq!(U128(10 * BYTES_PER_GIB), result[1].power);
+        assert_eq!(None, result[1].label);
+        assert!(!result[1].verified);
+        assert_eq!(account("carol_near"), contract.owner);
+        assert_eq!(1, contract.get_owner_history().len());
+
+        let latest = contract.get_latest_price_per_region();
+        assert_eq!(20, latest.timestamp);
+        assert_eq!(fil(0.1), latest.prices[&REGION_EUROPE]);
+        assert!(!latest.prices.contains_key(&REGION_SOUTH_AMERICA));
+        assert!(!latest.prices.contains_key(&REGION_AFRICA));
+        assert_eq!(fil(0.4), latest.prices[&REGION_OTHER]);
+        assert_eq!(U128(3 * BYTES_PER_TIB), latest.power);
+        assert_eq!(2, contract.get_price_per_region_list().len());
+        assert_eq!(6, contract.get_regions().len());
+
+        assert_eq!(2, contract.get_storage_providers_by_region(REGION_EUROPE, 0, 10).len());
+
+        // the stored counts are replaced by the counts of the migrated providers
+        let active = contract.get_active_per_region();
+        assert_eq!(2, active.get(REGION_EUROPE));
+        assert_eq!(0, active.get(REGION_NORTH_AMERICA));
+        assert_eq!(0, active.get(REGION_OTHER));
+        assert_eq!(U128(20 * BYTES_PER_GIB), contract.get_power_per_region().0[&REGION_EUROPE]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the state is already at version V2")]
+    fn migrate_current_state() {
+        set_context("carol_near", 0, 0);
+
+        let contract = FilMarket::new();
+        assert_eq!(StateVersion::V2, contract.get_version());
+        env::state_write(&contract);
+        FilMarket::migrate();
+    }
+}
@@ -0,0 +1,152 @@
+/*
+ * Price oracle interface in the shape NEAR lending protocols read price feeds: a price is a multiplier with its
+ * decimals, and get_price_data returns the prices of several assets at once, leaving out the stale ones
+ *
+ * Asset ids:
+ *
+ *   fil                         USD per FIL
+ *   storage                     FIL per GiB per epoch, global price
+ *   storage:<region>            FIL per GiB per epoch in a region, e.g. storage:2
+ *   storage-verified            FIL per GiB per epoch for FIL+ verified deals, global price
+ *   storage-verified:<region>   FIL per GiB per epoch for FIL+ verified deals in a region
+ */
+
+use crate::*;
+
+pub const ASSET_FIL: &str = "fil";
+pub const ASSET_STORAGE: &str = "storage";
+pub const ASSET_STORAGE_VERIFIED: &str = "storage-verified";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OraclePrice {
+    multiplier: U128, // the price is multiplier / 10^decimals
+    decimals: u8,
+    timestamp: u64,   // epoch time in seconds of the price entry, or of the heartbeat confirming it
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetOptionalPrice {
+    asset_id: String,
+    price: Option<OraclePrice>, // None for an unknown asset, or a missing or stale price
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceData {
+    timestamp: u64,            // block timestamp in nanoseconds of the query
+    recency_duration_sec: u32, // seconds a price stays fresh, see get_max_price_age
+    prices: Vec<AssetOptionalPrice>,
+}
+
+// the price of an asset in a price entry, None for an unknown asset or a price missing from the entry
+fn asset_price(ppr: &PricePerRegion, asset_id: &str) -> Option<U128> {
+    if asset_id == ASSET_FIL {
+        return if ppr.fil_price.0 == 0 { None } else { Some(ppr.fil_price) };
+    }
+
+    let (asset, region) = match asset_id.split_once(':') {
+        Some((asset, region)) => (asset, Some(region.parse::<u8>().ok()?)),
+        None => (asset_id, None),
+    };
+    match (asset, region) {
+        (ASSET_STORAGE, None) => Some(ppr.global),
+        (ASSET_STORAGE, Some(region)) => ppr.prices.get(&region).copied(),
+        (ASSET_STORAGE_VERIFIED, None) => ppr.verified_global,
+        (ASSET_STORAGE_VERIFIED, Some(region)) => ppr.verified_prices.get(&region).copied(),
+        _ => None,
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the latest price of an asset, stale or not, None for an unknown asset or without a price
+    pub fn get_price(&self, asset_id: String) -> Option<OraclePrice> {
+        let ppr = self.price_per_region.get(&self.latest_timestamp)?;
+
+        Some(OraclePrice {
+            multiplier: asset_price(&ppr, &asset_id)?,
+            decimals: decimal::PRICE_DECIMALS as u8,
+            timestamp: ppr.timestamp.max(self.last_heartbeat),
+        })
+    }
+
+    // get the latest fresh prices of the given assets, by default fil and the storage prices of every region
+    pub fn get_price_data(&self, asset_ids: Option<Vec<String>>) -> PriceData {
+        let asset_ids = asset_ids.unwrap_or_else(|| {
+            let mut asset_ids = vec![ASSET_FIL.to_string(), ASSET_STORAGE.to_string(), ASSET_STORAGE_VERIFIED.to_string()];
+            for (region, _) in self.regions.iter() {
+                asset_ids.push(format!("{}:{}", ASSET_STORAGE, region));
+                asset_ids.push(format!("{}:{}", ASSET_STORAGE_VERIFIED, region));
+            }
+            asset_ids
+        });
+        let fresh = self.is_price_fresh();
+
+        PriceData {
+            timestamp: env::block_timestamp(),
+            recency_duration_sec: self.max_price_age.min(u32::MAX as u64) as u32,
+            prices: asset_ids
+                .into_iter()
+                .map(|asset_id| AssetOptionalPrice {
+                    price: if fresh { self.get_price(asset_id.clone()) } else { None },
+                    asset_id,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    #[test]
+    fn get_price_by_asset() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        assert_eq!(None, contract.get_price(ASSET_STORAGE.to_string()));
+
+        contract.set_price_per_region(PricePerRegion {
+            prices: vec![(REGION_EUROPE, fil(0.3))].into_iter().collect(),
+            global: fil(0.2),
+            fil_price: fil(5.0),
+            timestamp: 950,
+            verified_prices: vec![(REGION_EUROPE, fil(0.1))].into_iter().collect(),
+            ..Default::default()
+        });
+
+        let price = contract.get_price("storage:2".to_string()).unwrap();
+        assert_eq!(OraclePrice { multiplier: fil(0.3), decimals: 18, timestamp: 950 }, price);
+        assert_eq!(fil(5.0), contract.get_price("fil".to_string()).unwrap().multiplier);
+        assert_eq!(fil(0.2), contract.get_price("storage".to_string()).unwrap().multiplier);
+        assert_eq!(fil(0.1), contract.get_price("storage-verified:2".to_string()).unwrap().multiplier);
+        assert_eq!(None, contract.get_price("storage-verified".to_string()));
+        assert_eq!(None, contract.get_price("storage:3".to_string()));
+        assert_eq!(None, contract.get_price("storage:europe".to_string()));
+        assert_eq!(None, contract.get_price("near".to_string()));
+    }
+
+    #[test]
+    fn get_price_data_leaves_out_stale_prices() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.set_max_price_age(100);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.2), fil_price: fil(5.0), timestamp: 950, ..Default::default() });
+
+        let data = contract.get_price_data(Some(vec!["fil".to_string(), "storage:2".to_string()]));
+        assert_eq!(100, data.recency_duration_sec);
+        assert_eq!(fil(5.0), data.prices[0].price.as_ref().unwrap().multiplier);
+        assert_eq!(None, data.prices[1].price);
+
+        // fil, storage and storage-verified, then both storage prices of the 6 regions
+        assert_eq!(15, contract.get_price_data(None).prices.len());
+
+        set_context("carol_near", NEAR, 1_100);
+        let data = contract.get_price_data(Some(vec!["fil".to_string()]));
+        assert_eq!(None, data.prices[0].price);
+        assert_eq!(1_100 * NANOSECONDS_PER_SECOND, data.timestamp);
+    }
+}
@@ -0,0 +1,276 @@
+/*
+ * Order book per region: storage providers post asks with the capacity they have available, clients post bids
+ * with the capacity they need, and the book is kept sorted best price first on each side. Orders are standing
+ * quotes, matching them happens off-chain or through storage requests and deals
+ */
+
+use crate::*;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum OrderSide {
+    Ask, // storage offered by a storage provider
+    Bid, // storage wanted by a client
+}
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Order {
+    id: u64,
+    side: OrderSide,
+    account_id: AccountId,       // posted the order and paid its storage
+    provider_id: Option<String>, // storage provider of an ask
+    region: u8,
+    price: U128,     // attoFIL/GiB/epoch
+    capacity: U128,  // bytes
+    created_at: u64, // block timestamp in nanoseconds
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderBook {
+    asks: Vec<Order>, // cheapest first
+    bids: Vec<Order>, // highest first
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BookSummary {
+    best_ask: Option<U128>,  // attoFIL/GiB/epoch
+    best_bid: Option<U128>,  // attoFIL/GiB/epoch
+    spread: Option<U128>,    // best ask - best bid, 0 for a crossed book
+    mid_price: Option<U128>, // (best ask + best bid) / 2, rounded down
+}
+
+// (region, side, rank, id), ascending order is best price first on each side
+type OrderKey = (u8, u8, u128, u64);
+
+fn side_index(side: OrderSide) -> u8 {
+    match side {
+        OrderSide::Ask => 0,
+        OrderSide::Bid => 1,
+    }
+}
+
+fn order_key(order: &Order) -> OrderKey {
+    let rank = match order.side {
+        OrderSide::Ask => order.price.0,
+        OrderSide::Bid => u128::MAX - order.price.0,
+    };
+    (order.region, side_index(order.side), rank, order.id)
+}
+
+impl FilMarket {
+    fn insert_order(&mut self, side: OrderSide, provider_id: Option<String>, region: u8, price: U128, capacity: U128) -> u64 {
+        require!(price.0 > 0, "price must be greater than 0");
+        require!(capacity.0 > 0, "capacity must be greater than 0");
+
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+
+        let initial_storage_usage = env::storage_usage();
+        let order = Order {
+            id,
+            side,
+            account_id: env::predecessor_account_id(),
+            provider_id,
+            region,
+            price,
+            capacity,
+            created_at: env::block_timestamp(),
+        };
+        self.orders.insert(&id, &order);
+        self.order_book.insert(&order_key(&order), &());
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("order_posted", json!({ "order": order }));
+        id
+    }
+
+    // the best orders of one side of a region's book, asks of providers that are no longer Active are left out
+    fn book_side(&self, region: u8, side: OrderSide, depth: u64) -> Vec<Order> {
+        let side = side_index(side);
+        self.order_book
+            .iter_from((region, side, 0, 0))
+            .take_while(|((key_region, key_side, _, _), _)| *key_region == region && *key_side == side)
+            .filter_map(|((_, _, _, id), _)| self.orders.get(&id))
+            .filter(|order| match &order.provider_id {
+                Some(provider_id) => self.storage_providers.get(provider_id).map(|sp| sp.is_active()).unwrap_or(false),
+                None => true,
+            })
+            .take(view_limit(depth))
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // post an ask for an Active storage provider, by its account or an SpManager, in the provider's region
+    // the attached deposit must cover the storage of the order, the rest is refunded
+    #[payable]
+    pub fn post_ask(&mut self, provider_id: String, price: U128, capacity: U128) -> u64 {
+        self.assert_not_paused(Feature::Deals);
+        self.assert_provider_side(&provider_id);
+
+        let provider = self.storage_providers.get(&provider_id)
+            .unwrap_or_else(|| panic!("storage provider {} not found", provider_id));
+        require!(provider.is_active(), format!("storage provider {} is not active", provider_id));
+
+        self.insert_order(OrderSide::Ask, Some(provider_id), provider.region, price, capacity)
+    }
+
+    // post a bid for storage in a region
+    // the attached deposit must cover the storage of the order, the rest is refunded
+    #[payable]
+    pub fn post_bid(&mut self, region: u8, price: U128, capacity: U128) -> u64 {
+        self.assert_not_paused(Feature::Deals);
+        require!(self.is_valid_region(region), format!("invalid region {}", region));
+
+        self.insert_order(OrderSide::Bid, None, region, price, capacity)
+    }
+
+    // cancel an order, by the account that posted it or the owner, its storage staking is refunded to the poster
+    pub fn cancel_order(&mut self, order_id: u64) {
+        let order = self.orders.get(&order_id).unwrap_or_else(|| panic!("order {} not found", order_id));
+        if env::predecessor_account_id() != order.account_id {
+            self.assert_owner();
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.orders.remove(&order_id);
+        self.order_book.remove(&order_key(&order));
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        if freed > 0 {
+            Promise::new(order.account_id.clone()).transfer(env::storage_byte_cost() * Balance::from(freed));
+        }
+
+        self.emit_event("order_cancelled", json!({
+            "order_id": order_id,
+            "account_id": order.account_id,
+        }));
+    }
+
+    // get an order by id
+    pub fn get_order(&self, order_id: u64) -> Option<Order> {
+        self.orders.get(&order_id)
+    }
+
+    // get the orders posted by an account, paginated
+    pub fn get_orders_by_account(&self, account_id: AccountId, from_index: u64, limit: u64) -> Vec<Order> {
+        self.orders
+            .values_as_vector()
+            .iter()
+            .filter(|order| order.account_id == account_id)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+
+    // get the best depth asks and bids of a region
+    pub fn get_order_book(&self, region: u8, depth: u64) -> OrderBook {
+        OrderBook {
+            asks: self.book_side(region, OrderSide::Ask, depth),
+            bids: self.book_side(region, OrderSide::Bid, depth),
+        }
+    }
+
+    // get the best ask and bid of a region with the spread and mid price, None where a side is empty
+    pub fn get_book_summary(&self, region: u8) -> BookSummary {
+        let best_ask = self.book_side(region, OrderSide::Ask, 1).pop().map(|order| order.price);
+        let best_bid = self.book_side(region, OrderSide::Bid, 1).pop().map(|order| order.price);
+        let (spread, mid_price) = match (best_ask, best_bid) {
+            (Some(ask), Some(bid)) => (
+                Some(U128(ask.0.saturating_sub(bid.0))),
+                Some(U128(ask.0 / 2 + bid.0 / 2 + (ask.0 % 2 + bid.0 % 2) / 2)),
+            ),
+            _ => (None, None),
+        };
+
+        BookSummary { best_ask, best_bid, spread, mid_price }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    fn prices(orders: Vec<Order>) -> Vec<U128> {
+        orders.into_iter().map(|order| order.price).collect()
+    }
+
+    // two storage providers in Europe, carol being the owner
+    fn market_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract
+    }
+
+    #[test]
+    fn order_book_is_sorted_per_side() {
+        let mut contract = market_contract();
+        contract.post_ask("f01".to_string(), fil(0.3), gib(100.0));
+        contract.post_ask("f02".to_string(), fil(0.2), gib(50.0));
+
+        set_context("alice_near", NEAR, 0);
+        contract.post_bid(REGION_EUROPE, fil(0.1), gib(10.0));
+        let bid_id = contract.post_bid(REGION_EUROPE, fil(0.15), gib(20.0));
+        contract.post_bid(REGION_ASIA, fil(0.5), gib(20.0));
+
+        let book = contract.get_order_book(REGION_EUROPE, 10);
+        assert_eq!(vec![fil(0.2), fil(0.3)], prices(book.asks));
+        assert_eq!(vec![fil(0.15), fil(0.1)], prices(book.bids));
+        assert_eq!(1, contract.get_order_book(REGION_EUROPE, 1).asks.len());
+
+        let summary = contract.get_book_summary(REGION_EUROPE);
+        assert_eq!(Some(fil(0.2)), summary.best_ask);
+        assert_eq!(Some(fil(0.15)), summary.best_bid);
+        assert_eq!(Some(fil(0.05)), summary.spread);
+        assert_eq!(Some(fil(0.175)), summary.mid_price);
+
+        // an empty side has no spread
+        let summary = contract.get_book_summary(REGION_ASIA);
+        assert_eq!((None, Some(fil(0.5)), None), (summary.best_ask, summary.best_bid, summary.spread));
+
+        contract.cancel_order(bid_id);
+        assert_eq!(Some(fil(0.1)), contract.get_book_summary(REGION_EUROPE).best_bid);
+        assert_eq!(2, contract.get_orders_by_account(account("alice_near"), 0, 10).len());
+        assert_eq!(None, contract.get_order(bid_id));
+    }
+
+    #[test]
+    fn asks_of_inactive_providers_are_hidden() {
+        let mut contract = market_contract();
+        contract.post_ask("f01".to_string(), fil(0.2), gib(100.0));
+        contract.post_ask("f02".to_string(), fil(0.3), gib(100.0));
+
+        contract.set_provider_status(vec!["f01".to_string()], ProviderStatus::Inactive);
+        assert_eq!(vec![fil(0.3)], prices(contract.get_order_book(REGION_EUROPE, 10).asks));
+        assert_eq!(Some(fil(0.3)), contract.get_book_summary(REGION_EUROPE).best_ask);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn post_ask_for_another_provider() {
+        let mut contract = market_contract();
+
+        set_context("bob_near", NEAR, 0);
+        contract.post_ask("f01".to_string(), fil(0.2), gib(100.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn cancel_order_of_another_account() {
+        let mut contract = market_contract();
+        set_context("alice_near", NEAR, 0);
+        let bid_id = contract.post_bid(REGION_EUROPE, fil(0.1), gib(10.0));
+
+        set_context("bob_near", NEAR, 0);
+        contract.cancel_order(bid_id);
+    }
+}
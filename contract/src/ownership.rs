@@ -0,0 +1,134 @@
+/*
+ * Two-step ownership transfer and owner history
+ */
+
+use crate::*;
+
+impl FilMarket {
+    // whether an account acts as the owner: the owner account, or the contract executing a proposal once a council is set
+    pub(crate) fn is_owner(&self, account_id: &AccountId) -> bool {
+        match self.council {
+            Some(_) => *account_id == env::current_account_id(),
+            None => *account_id == self.owner,
+        }
+    }
+
+    // fail the call unless the predecessor is the owner
+    pub(crate) fn assert_owner(&self) {
+        let account_id = env::predecessor_account_id();
+
+        require!(
+            self.is_owner(&account_id),
+            format!("{}: account_id {} is not owner", errors::ERR_NOT_OWNER, account_id)
+        );
+        self.mark_privileged(account_id);
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // propose a new owner, the transfer completes once the new owner calls accept_ownership
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        let account_id = env::predecessor_account_id();
+
+        env::log_str(&format!("propose_owner(): account_id {} proposed owner {}", account_id, new_owner));
+        self.emit_event("owner_proposed", json!({
+            "owner": account_id,
+            "pending_owner": new_owner,
+        }));
+        self.pending_owner = Some(new_owner);
+    }
+
+    // accept a pending ownership proposal, must be called by the proposed owner
+    pub fn accept_ownership(&mut self) {
+        let account_id = env::predecessor_account_id();
+
+        require!(
+            self.pending_owner.as_ref() == Some(&account_id),
+            format!("{}: account_id {} is not the proposed owner", errors::ERR_NOT_PENDING_OWNER, account_id)
+        );
+
+        let old_owner = std::mem::replace(&mut self.owner, account_id.clone());
+        self.pending_owner = None;
+        self.owner_history.push(&(account_id.clone(), env::block_height()));
+
+        self.emit_event("ownership_transferred", json!({
+            "old_owner": old_owner,
+            "new_owner": account_id,
+        }));
+    }
+
+    // get the current owner
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+
+    // get the proposed owner, if any
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner.clone()
+    }
+
+    // get every owner of the contract with the block height it took over
+    pub fn get_owner_history(&self) -> Vec<(AccountId, BlockHeight)> {
+        self.owner_history.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, context};
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn transfer_ownership_twice() {
+        testing_env!(context("carol_near", 0, 0).block_index(10).build());
+        let mut contract = FilMarket::new();
+
+        contract.propose_owner(account("bob_near"));
+        assert_eq!(Some(account("bob_near")), contract.get_pending_owner());
+
+        testing_env!(context("bob_near", 0, 0).block_index(20).build());
+        contract.accept_ownership();
+        assert_eq!(account("bob_near"), contract.get_owner());
+        assert_eq!(None, contract.get_pending_owner());
+        assert!(get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")
+            && log.contains("\"ownership_transferred\"")
+            && log.contains("\"old_owner\":\"carol_near\"")
+            && log.contains("\"new_owner\":\"bob_near\"")));
+
+        contract.propose_owner(account("alice_near"));
+        testing_env!(context("alice_near", 0, 0).block_index(30).build());
+        contract.accept_ownership();
+        assert_eq!(account("alice_near"), contract.get_owner());
+
+        let history = contract.get_owner_history();
+        assert_eq!(3, history.len());
+        assert_eq!((account("carol_near"), 10), history[0]);
+        assert_eq!((account("bob_near"), 20), history[1]);
+        assert_eq!((account("alice_near"), 30), history[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER: account_id bob_near is not owner")]
+    fn propose_owner_not_owner() {
+        testing_env!(context("carol_near", 0, 0).block_index(10).build());
+        let mut contract = FilMarket::new();
+
+        testing_env!(context("bob_near", 0, 0).block_index(20).build());
+        contract.propose_owner(account("bob_near"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PENDING_OWNER: account_id alice_near is not the proposed owner")]
+    fn transfer_ownership_not_proposed() {
+        testing_env!(context("carol_near", 0, 0).block_index(10).build());
+        let mut contract = FilMarket::new();
+        contract.propose_owner(account("bob_near"));
+
+        testing_env!(context("alice_near", 0, 0).block_index(20).build());
+        contract.accept_ownership();
+    }
+}
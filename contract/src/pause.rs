@@ -0,0 +1,147 @@
+/*
+ * Circuit breaker: the owner can pause the whole contract or single features, e.g. freeze price writes when the
+ * off-chain oracle misbehaves while storage providers keep updating. Views and owner methods stay available
+ */
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Feature {
+    Prices,    // price writes and price candidates
+    Providers, // storage provider listings, claims and reputation
+    Deals,     // storage requests, offers, deals and escrow releases
+    Tokens,    // payment token deposits and withdrawals
+}
+
+impl FilMarket {
+    // fail the call if the contract or the given feature is paused
+    pub(crate) fn assert_not_paused(&self, feature: Feature) {
+        require!(!self.paused, format!("{}: the contract is paused", errors::ERR_PAUSED));
+        require!(
+            !self.paused_features.contains(&feature),
+            format!("{}: {:?} is paused", errors::ERR_PAUSED, feature)
+        );
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // pause every feature, until unpause
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        if self.paused {
+            return;
+        }
+
+        self.paused = true;
+        self.emit_event("paused", json!({}));
+    }
+
+    // lift the pause of the whole contract, features paused one by one stay paused
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        if !self.paused {
+            return;
+        }
+
+        self.paused = false;
+        self.emit_event("unpaused", json!({}));
+    }
+
+    // pause a single feature
+    pub fn pause_feature(&mut self, feature: Feature) {
+        self.assert_owner();
+        if self.paused_features.contains(&feature) {
+            return;
+        }
+
+        self.paused_features.push(feature);
+        self.emit_event("feature_paused", json!({ "feature": feature }));
+    }
+
+    // lift the pause of a single feature
+    pub fn unpause_feature(&mut self, feature: Feature) {
+        self.assert_owner();
+        if !self.paused_features.contains(&feature) {
+            return;
+        }
+
+        self.paused_features.retain(|f| *f != feature);
+        self.emit_event("feature_unpaused", json!({ "feature": feature }));
+    }
+
+    // whether the whole contract is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // get the features paused one by one
+    pub fn get_paused_features(&self) -> Vec<Feature> {
+        self.paused_features.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn sp_list() -> Vec<StorageProvider> {
+        vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }]
+    }
+
+    #[test]
+    fn pause_and_unpause() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        assert!(!contract.is_paused());
+
+        contract.pause();
+        assert!(contract.is_paused());
+        assert!(contract.get_health().paused);
+
+        contract.unpause();
+        contract.pause_feature(Feature::Prices);
+        contract.pause_feature(Feature::Prices);
+        assert_eq!(vec![Feature::Prices], contract.get_paused_features());
+
+        // storage providers can still be updated while prices are frozen
+        contract.update_storage_providers(sp_list(), None);
+        assert_eq!(1, contract.get_storage_providers().len());
+
+        contract.unpause_feature(Feature::Prices);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 1, ..Default::default() });
+        assert!(contract.get_paused_features().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: the contract is paused")]
+    fn update_storage_providers_while_paused() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.pause();
+        contract.update_storage_providers(sp_list(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Prices is paused")]
+    fn set_price_while_prices_paused() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.pause_feature(Feature::Prices);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 1, ..Default::default() });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn pause_by_other_account() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        contract.pause();
+    }
+}
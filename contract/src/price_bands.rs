@@ -0,0 +1,159 @@
+/*
+ * Price bands: the spread of the storage provider prices behind the average price of a region, set by the feeder
+ * along with the price entry, so consumers can judge how representative the average is
+ */
+
+use crate::*;
+use crate::price_stats::median;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceBand {
+    min: U128,     // attoFIL
+    max: U128,     // attoFIL
+    std_dev: U128, // attoFIL
+    samples: u32,  // storage provider prices the average was computed from
+}
+
+// PriceBand with prices as decimal strings in FIL
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceBandStr {
+    min: String,
+    max: String,
+    std_dev: String,
+    samples: u32,
+}
+
+impl PriceBandStr {
+    // parse the prices with parse(field, value)
+    pub(crate) fn parse(&self, region: u8, parse: impl Fn(&str, &str) -> U128) -> PriceBand {
+        PriceBand {
+            min: parse(&format!("band min of region {}", region), &self.min),
+            max: parse(&format!("band max of region {}", region), &self.max),
+            std_dev: parse(&format!("band std_dev of region {}", region), &self.std_dev),
+            samples: self.samples,
+        }
+    }
+}
+
+// fail the call unless every band is for a priced region and holds its average price
+pub(crate) fn assert_price_bands(ppr: &PricePerRegion) {
+    for (region, band) in ppr.bands.iter() {
+        let price = ppr.prices.get(region).unwrap_or_else(|| panic!("price band for region {} without a price", region));
+        require!(band.samples > 0, format!("price band of region {} has no samples", region));
+        require!(
+            band.min.0 <= price.0 && price.0 <= band.max.0,
+            format!("price of region {} is outside its price band", region)
+        );
+        require!(
+            band.std_dev.0 <= band.max.0 - band.min.0,
+            format!("std_dev of region {} is wider than its price band", region)
+        );
+    }
+}
+
+// combine the bands of several observations: the widest min and max, the median std_dev and samples
+pub(crate) fn aggregate_bands<'a>(bands: impl Iterator<Item = &'a BTreeMap<u8, PriceBand>>) -> BTreeMap<u8, PriceBand> {
+    let mut per_region: BTreeMap<u8, Vec<&PriceBand>> = BTreeMap::new();
+    for region_bands in bands {
+        for (region, band) in region_bands.iter() {
+            per_region.entry(*region).or_default().push(band);
+        }
+    }
+
+    let median_of = |mut values: Vec<u128>| {
+        values.sort_unstable();
+        median(&values)
+    };
+    per_region
+        .into_iter()
+        .map(|(region, bands)| {
+            let band = PriceBand {
+                min: U128(bands.iter().map(|band| band.min.0).min().unwrap_or(0)),
+                max: U128(bands.iter().map(|band| band.max.0).max().unwrap_or(0)),
+                std_dev: U128(median_of(bands.iter().map(|band| band.std_dev.0).collect())),
+                samples: median_of(bands.iter().map(|band| band.samples as u128).collect()) as u32,
+            };
+            (region, band)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    fn band(min: f64, max: f64, std_dev: f64, samples: u32) -> PriceBand {
+        PriceBand { min: fil(min), max: fil(max), std_dev: fil(std_dev), samples }
+    }
+
+    fn priced(price: f64, band: PriceBand) -> PricePerRegion {
+        PricePerRegion {
+            prices: vec![(REGION_EUROPE, fil(price))].into_iter().collect(),
+            global: fil(price),
+            timestamp: 10,
+            bands: vec![(REGION_EUROPE, band)].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn set_price_with_bands() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.set_price_per_region(priced(0.2, band(0.1, 0.5, 0.08, 12)));
+
+        let latest = contract.get_latest_price_per_region();
+        assert_eq!(band(0.1, 0.5, 0.08, 12), latest.bands[&REGION_EUROPE]);
+    }
+
+    #[test]
+    fn set_price_str_with_bands() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let band_str = PriceBandStr { min: "0.1".to_string(), max: "0.5".to_string(), std_dev: "0.08".to_string(), samples: 12 };
+        contract.set_price_per_region_str(PricePerRegionStr {
+            prices: vec![(REGION_EUROPE, "0.2".to_string())].into_iter().collect(),
+            global: "0.2".to_string(),
+            fil_price: "5".to_string(),
+            power: U128(0),
+            timestamp: 10,
+            verified_prices: BTreeMap::new(),
+            verified_global: None,
+            bands: vec![(REGION_EUROPE, band_str)].into_iter().collect(),
+        });
+
+        assert_eq!(band(0.1, 0.5, 0.08, 12), contract.get_latest_price_per_region().bands[&REGION_EUROPE]);
+    }
+
+    #[test]
+    #[should_panic(expected = "price of region 2 is outside its price band")]
+    fn set_price_outside_band() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.set_price_per_region(priced(0.6, band(0.1, 0.5, 0.08, 12)));
+    }
+
+    #[test]
+    #[should_panic(expected = "price band for region 3 without a price")]
+    fn set_band_without_price() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let mut ppr = priced(0.2, band(0.1, 0.5, 0.08, 12));
+        ppr.bands.insert(REGION_ASIA, band(0.1, 0.5, 0.08, 12));
+        contract.set_price_per_region(ppr);
+    }
+
+    #[test]
+    fn aggregate_observation_bands() {
+        let bands = [
+            priced(0.2, band(0.1, 0.4, 0.05, 10)).bands,
+            priced(0.3, band(0.2, 0.5, 0.09, 14)).bands,
+            priced(0.25, band(0.15, 0.3, 0.07, 11)).bands,
+        ];
+
+        assert_eq!(band(0.1, 0.5, 0.07, 11), aggregate_bands(bands.iter())[&REGION_EUROPE]);
+    }
+}
@@ -0,0 +1,221 @@
+/*
+ * Price candidates submitted by any account, one per account and timestamp, and approved or rejected by the owner
+ * or a price feeder
+ */
+
+use crate::*;
+
+// deposit required to submit a price candidate, refunded on approval and kept on rejection
+pub const PRICE_CANDIDATE_DEPOSIT: Balance = 100_000_000_000_000_000_000_000; // 0.1 NEAR
+
+#[near_bindgen]
+impl FilMarket {
+    // submit a price candidate for the owner to review, each account can have one candidate pending per timestamp
+    // requires PRICE_CANDIDATE_DEPOSIT on top of the storage staking of the candidate, the excess is refunded
+    #[payable]
+    pub fn submit_price_candidate(&mut self, candidate: PricePerRegion) {
+        self.assert_not_paused(Feature::Prices);
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+
+        require!(
+            env::attached_deposit() >= PRICE_CANDIDATE_DEPOSIT,
+            format!("must attach {} yoctoNEAR to submit a price candidate", PRICE_CANDIDATE_DEPOSIT)
+        );
+        self.assert_valid_price_timestamp(candidate.timestamp);
+        self.assert_valid_price_regions(&candidate);
+
+        let timestamp = candidate.timestamp;
+        let key = (timestamp, account_id.clone());
+        require!(
+            self.pending_prices.get(&key).is_none(),
+            format!("{} already has a price candidate pending for timestamp {}", account_id, timestamp)
+        );
+
+        env::log_str(&format!("submit_price_candidate(): account_id {} timestamp {}", account_id, timestamp));
+        self.pending_prices.insert(&key, &candidate);
+        self.emit_event("price_candidate_submitted", json!({
+            "submitter": account_id,
+            "timestamp": timestamp,
+        }));
+
+        let deposit = self.deposit_after_storage(initial_storage_usage);
+        require!(
+            deposit >= PRICE_CANDIDATE_DEPOSIT,
+            format!("must attach {} yoctoNEAR to submit a price candidate on top of the storage staking", PRICE_CANDIDATE_DEPOSIT)
+        );
+        let refund = deposit - PRICE_CANDIDATE_DEPOSIT;
+        if refund > 0 {
+            Promise::new(account_id).transfer(refund);
+        }
+    }
+
+    // promote a pending price candidate into the price history and refund its submitter's deposit
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn approve_price_candidate(&mut self, timestamp: u64, submitter: AccountId) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_direct_price_write();
+        let initial_storage_usage = env::storage_usage();
+
+        let candidate = self.pending_prices.remove(&(timestamp, submitter.clone()))
+            .unwrap_or_else(|| panic!("no price candidate of {} pending for timestamp {}", submitter, timestamp));
+
+        self.emit_event("price_candidate_approved", json!({
+            "submitter": submitter,
+            "timestamp": timestamp,
+        }));
+        self.internal_set_price_per_region(candidate);
+        self.refund_deposit(initial_storage_usage);
+        Promise::new(submitter).transfer(PRICE_CANDIDATE_DEPOSIT);
+    }
+
+    // drop a pending price candidate, its deposit is kept to discourage spam
+    pub fn reject_price_candidate(&mut self, timestamp: u64, submitter: AccountId) {
+        self.assert_role(Role::PriceFeeder);
+
+        self.pending_prices.remove(&(timestamp, submitter.clone()))
+            .unwrap_or_else(|| panic!("no price candidate of {} pending for timestamp {}", submitter, timestamp));
+
+        env::log_str(&format!("reject_price_candidate(): timestamp {} submitted by {}", timestamp, submitter));
+        self.emit_event("price_candidate_rejected", json!({
+            "submitter": submitter,
+            "timestamp": timestamp,
+        }));
+    }
+
+    // get the pending price candidates with their submitters, paginated
+    pub fn get_pending_prices(&self, from_index: u64, limit: u64) -> Vec<(AccountId, PricePerRegion)> {
+        self.pending_prices
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .map(|((_, submitter), candidate)| (submitter, candidate))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, set_context, transfers, NEAR};
+
+    fn submit_candidate(contract: &mut FilMarket, submitter: &str, global: f64, timestamp: u64) {
+        set_context(submitter, PRICE_CANDIDATE_DEPOSIT * 2, 0);
+        contract.submit_price_candidate(PricePerRegion { global: fil(global), timestamp, ..Default::default() });
+
+        // the storage staking of the candidate is kept on top of the deposit
+        let refunds = transfers();
+        assert_eq!(1, refunds.len());
+        assert_eq!(account(submitter), refunds[0].0);
+        assert!(refunds[0].1 > 0 && refunds[0].1 < PRICE_CANDIDATE_DEPOSIT);
+    }
+
+    #[test]
+    fn submit_then_approve_price_candidate() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        submit_candidate(&mut contract, "bob_near", 0.0004, 10);
+        let pending = contract.get_pending_prices(0, 10);
+        assert_eq!(1, pending.len());
+        assert_eq!(account("bob_near"), pending[0].0);
+        assert_eq!(fil(0.0004), pending[0].1.global);
+        assert!(contract.get_price_per_region_list().is_empty());
+
+        set_context("carol_near", NEAR, 0);
+        contract.approve_price_candidate(10, account("bob_near"));
+
+        assert!(contract.get_pending_prices(0, 10).is_empty());
+        assert_eq!(fil(0.0004), contract.get_latest_price_per_region().global);
+        assert!(transfers().contains(&(account("bob_near"), PRICE_CANDIDATE_DEPOSIT)));
+    }
+
+    #[test]
+    fn submit_then_reject_price_candidate() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        submit_candidate(&mut contract, "bob_near", 0.0004, 10);
+        assert_eq!(1, contract.get_pending_prices(0, 10).len());
+
+        set_context("carol_near", 0, 0);
+        contract.reject_price_candidate(10, account("bob_near"));
+
+        assert!(contract.get_pending_prices(0, 10).is_empty());
+        assert!(contract.get_price_per_region_list().is_empty());
+        assert!(transfers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn approve_price_candidate_without_role() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        submit_candidate(&mut contract, "bob_near", 0.0004, 10);
+
+        // only the owner and price feeders may review candidates
+        set_context("bob_near", 0, 0);
+        contract.approve_price_candidate(10, account("bob_near"));
+    }
+
+    #[test]
+    #[should_panic(expected = "yoctoNEAR to submit a price candidate")]
+    fn submit_price_candidate_without_deposit() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", PRICE_CANDIDATE_DEPOSIT - 1, 0);
+        contract.submit_price_candidate(PricePerRegion { global: fil(0.0004), timestamp: 10, ..Default::default() });
+    }
+
+    #[test]
+    #[should_panic(expected = "bob_near already has a price candidate pending for timestamp 10")]
+    fn submit_price_candidate_twice() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        submit_candidate(&mut contract, "bob_near", 0.0004, 10);
+        submit_candidate(&mut contract, "bob_near", 0.0005, 10);
+    }
+
+    #[test]
+    fn candidates_of_several_submitters_for_a_timestamp() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        // a candidate pending for a timestamp doesn't keep others from submitting theirs
+        submit_candidate(&mut contract, "bob_near", 0.0004, 10);
+        submit_candidate(&mut contract, "dave_near", 0.0005, 10);
+        assert_eq!(2, contract.get_pending_prices(0, 10).len());
+
+        set_context("carol_near", NEAR, 0);
+        contract.reject_price_candidate(10, account("bob_near"));
+        contract.approve_price_candidate(10, account("dave_near"));
+        assert_eq!(fil(0.0005), contract.get_latest_price_per_region().global);
+        assert!(contract.get_pending_prices(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid region 42")]
+    fn submit_price_candidate_with_invalid_region() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", PRICE_CANDIDATE_DEPOSIT * 2, 0);
+        let mut candidate = PricePerRegion { global: fil(0.0004), timestamp: 10, ..Default::default() };
+        candidate.prices.insert(42, fil(0.0004));
+        contract.submit_price_candidate(candidate);
+    }
+
+    #[test]
+    #[should_panic(expected = "yoctoNEAR to submit a price candidate on top of the storage staking")]
+    fn submit_price_candidate_without_storage_deposit() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", PRICE_CANDIDATE_DEPOSIT, 0);
+        contract.submit_price_candidate(PricePerRegion { global: fil(0.0004), timestamp: 10, ..Default::default() });
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * Guards on price writes against a misbehaving feeder: an entry is rejected when a field moves more than
+ * max_deviation_bps from the previous entry, or, with reject_out_of_order, when it is older than the latest entry.
+ * Negative values cannot reach the contract, prices are unsigned and parse_decimal rejects a sign. The owner can
+ * switch the guards off with the override flag to let a genuine market move through
+ */
+
+use crate::*;
+
+const BPS_DENOMINATOR: u128 = 10_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceGuards {
+    pub max_deviation_bps: u32,   // from the previous entry, 0 disables the check
+    pub reject_out_of_order: bool, // reject entries older than the latest one
+    pub overridden: bool,         // set by the owner to skip the guards
+}
+
+// whether value moved more than max_deviation_bps from previous, a zero previous value is not compared
+fn deviates(previous: u128, value: u128, max_deviation_bps: u32) -> bool {
+    if previous == 0 {
+        return false;
+    }
+
+    let change = value.abs_diff(previous);
+    change.saturating_mul(BPS_DENOMINATOR) > previous.saturating_mul(max_deviation_bps as u128)
+}
+
+impl FilMarket {
+    // reject an entry tripping one of the guards, unless the owner overrode them
+    pub(crate) fn assert_price_guards(&self, price_per_region: &PricePerRegion) {
+        let guards = &self.price_guards;
+        if guards.reject_out_of_order && !guards.overridden {
+            require!(
+                price_per_region.timestamp >= self.latest_timestamp,
+                format!("timestamp {} is older than the latest entry {}", price_per_region.timestamp, self.latest_timestamp)
+            );
+        }
+
+        self.assert_price_deviation(price_per_region);
+    }
+
+    // reject an entry moving more than max_deviation_bps from the previous entry, unless the owner overrode the guards
+    pub(crate) fn assert_price_deviation(&self, price_per_region: &PricePerRegion) {
+        let guards = &self.price_guards;
+        if guards.overridden || guards.max_deviation_bps == 0 {
+            return;
+        }
+        let previous = match self.price_per_region.lower(&price_per_region.timestamp).and_then(|timestamp| self.price_per_region.get(&timestamp)) {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let mut fields: Vec<(String, u128, u128)> = vec![
+            ("global".to_string(), previous.global.0, price_per_region.global.0),
+            ("fil_price".to_string(), previous.fil_price.0, price_per_region.fil_price.0),
+            ("power".to_string(), previous.power.0, price_per_region.power.0),
+        ];
+        if let (Some(old), Some(new)) = (previous.verified_global, price_per_region.verified_global) {
+            fields.push(("verified_global".to_string(), old.0, new.0));
+        }
+        for (region, price) in price_per_region.prices.iter() {
+            if let Some(old) = previous.prices.get(region) {
+                fields.push((format!("region {}", region), old.0, price.0));
+            }
+        }
+        for (region, price) in price_per_region.verified_prices.iter() {
+            if let Some(old) = previous.verified_prices.get(region) {
+                fields.push((format!("verified region {}", region), old.0, price.0));
+            }
+        }
+
+        for (field, old, new) in fields.into_iter() {
+            require!(
+                !deviates(old, new, guards.max_deviation_bps),
+                format!("{} moved from {} to {}, more than {} bps from the previous entry", field, old, new, guards.max_deviation_bps)
+            );
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the largest move from the previous entry in basis points, 0 disables the check, and whether entries older
+    // than the latest one are rejected
+    pub fn set_price_guards(&mut self, max_deviation_bps: u32, reject_out_of_order: bool) {
+        self.assert_config_authority();
+
+        self.price_guards.max_deviation_bps = max_deviation_bps;
+        self.price_guards.reject_out_of_order = reject_out_of_order;
+        self.emit_event("price_guards_set", json!({
+            "max_deviation_bps": max_deviation_bps,
+            "reject_out_of_order": reject_out_of_order,
+        }));
+    }
+
+    // skip the price guards until the override is cleared
+    pub fn set_price_guard_override(&mut self, overridden: bool) {
+        self.assert_owner();
+        if self.price_guards.overridden == overridden {
+            return;
+        }
+
+        self.price_guards.overridden = overridden;
+        self.emit_event("price_guard_override_set", json!({ "overridden": overridden }));
+    }
+
+    // get the price guards and whether they are overridden
+    pub fn get_price_guards(&self) -> PriceGuards {
+        self.price_guards.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, set_context, NEAR};
+
+    fn price(timestamp: u64, europe: f64) -> PricePerRegion {
+        PricePerRegion {
+            prices: vec![(REGION_EUROPE, fil(europe))].into_iter().collect(),
+            global: fil(europe),
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn deviation() {
+        assert!(!deviates(100, 110, 1_000));
+        assert!(deviates(100, 111, 1_000));
+        assert!(deviates(100, 89, 1_000));
+        assert!(!deviates(0, 1_000, 1_000));
+    }
+
+    #[test]
+    fn deviation_guard() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.set_price_guards(1_000, false);
+        assert_eq!(1_000, contract.get_price_guards().max_deviation_bps);
+
+        contract.set_price_per_region(price(10, 1.0));
+        contract.set_price_per_region(price(20, 1.1));
+        assert_eq!(fil(1.1), contract.get_latest_price_per_region().global);
+
+        // the owner lets a genuine move through
+        contract.set_price_guard_override(true);
+        contract.set_price_per_region(price(30, 2.0));
+        assert_eq!(fil(2.0), contract.get_latest_price_per_region().global);
+    }
+
+    #[test]
+    #[should_panic(expected = "region 2 moved from 1000000000000000000 to 2000000000000000000, more than 1000 bps")]
+    fn deviation_guard_rejects() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.set_price_guards(1_000, false);
+
+        contract.set_price_per_region(price(10, 1.0));
+        let mut ppr = price(20, 2.0);
+        ppr.global = fil(1.0);
+        contract.set_price_per_region(ppr);
+    }
+
+    #[test]
+    #[should_panic(expected = "timestamp 10 is older than the latest entry 20")]
+    fn out_of_order_guard() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.set_price_guards(0, true);
+
+        contract.set_price_per_region(price(20, 1.0));
+        // rewriting the latest entry is allowed
+        contract.set_price_per_region(price(20, 1.0));
+        contract.set_price_per_region(price(10, 1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn set_price_guard_override_by_other_account() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 1_000);
+        contract.set_price_guard_override(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn set_price_guard_override_by_governance() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.set_governance(Some(account("dao_near")));
+
+        set_context("dao_near", NEAR, 1_000);
+        contract.set_price_guard_override(true);
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ * Multi-feeder price rounds: price feeders submit their own observation for a timestamp, and once feeder_quorum
+ * observations are in, the round is finalized into the price history with the median of every field. While the
+ * quorum is above 1, the direct price writes are left to the owner
+ */
+
+use crate::*;
+use crate::price_stats::median;
+
+pub const DEFAULT_FEEDER_QUORUM: u32 = 1;
+
+// the median of the given values, which need not be sorted
+fn median_of(mut values: Vec<u128>) -> U128 {
+    values.sort_unstable();
+    U128(median(&values))
+}
+
+// combine the observations of a round field by field, a region is priced by the observations that include it
+fn aggregate(timestamp: u64, observations: &[(AccountId, PricePerRegion)]) -> PricePerRegion {
+    let per_region = |get: fn(&PricePerRegion) -> &BTreeMap<u8, U128>| {
+        let mut prices: BTreeMap<u8, Vec<u128>> = BTreeMap::new();
+        for (_, observation) in observations.iter() {
+            for (region, price) in get(observation).iter() {
+                prices.entry(*region).or_default().push(price.0);
+            }
+        }
+        prices.into_iter().map(|(region, values)| (region, median_of(values))).collect()
+    };
+    let field = |get: fn(&PricePerRegion) -> u128| median_of(observations.iter().map(|(_, observation)| get(observation)).collect());
+
+    // the verified global price is quoted by the observations that include it
+    let verified_globals: Vec<u128> = observations.iter().filter_map(|(_, observation)| observation.verified_global.map(|price| price.0)).collect();
+
+    PricePerRegion {
+        prices: per_region(|ppr| &ppr.prices),
+        global: field(|ppr| ppr.global.0),
+        fil_price: field(|ppr| ppr.fil_price.0),
+        power: field(|ppr| ppr.power.0),
+        timestamp,
+        verified_prices: per_region(|ppr| &ppr.verified_prices),
+        verified_global: if verified_globals.is_empty() { None } else { Some(median_of(verified_globals)) },
+        bands: price_bands::aggregate_bands(observations.iter().map(|(_, observation)| &observation.bands)),
+    }
+}
+
+impl FilMarket {
+    // fail a direct price write unless the caller is the owner or a single observation finalizes a round,
+    // so a lone price feeder can't bypass the median of the quorum
+    pub(crate) fn assert_direct_price_write(&self) {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.feeder_quorum <= 1 || self.is_owner(&account_id),
+            format!(
+                "{}: account_id {} must submit price observations while the feeder quorum is {}",
+                errors::ERR_QUORUM_REQUIRED, account_id, self.feeder_quorum
+            )
+        );
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // submit the caller's price observation for the round of its timestamp, finalizing the round at quorum
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn submit_price_observation(&mut self, observation: PricePerRegion) {
+        let initial_storage_usage = env::storage_usage();
+        self.internal_submit_price_observation(observation);
+        self.refund_deposit(initial_storage_usage);
+    }
+
+    fn internal_submit_price_observation(&mut self, observation: PricePerRegion) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_not_paused(Feature::Prices);
+        self.assert_valid_price_timestamp(observation.timestamp);
+        price_bands::assert_price_bands(&observation);
+
+        let account_id = env::predecessor_account_id();
+        let timestamp = observation.timestamp;
+        require!(
+            self.price_per_region.get(&timestamp).is_none(),
+            format!("price round {} is already finalized", timestamp)
+        );
+
+        let mut observations = self.price_rounds.get(&timestamp).unwrap_or_default();
+        require!(
+            observations.iter().all(|(feeder, _)| *feeder != account_id),
+            format!("{} already submitted an observation for price round {}", account_id, timestamp)
+        );
+        observations.push((account_id.clone(), observation));
+
+        self.emit_event("price_observation_submitted", json!({
+            "feeder": account_id,
+            "timestamp": timestamp,
+            "observations": observations.len(),
+        }));
+
+        if observations.len() < self.feeder_quorum as usize {
+            self.price_rounds.insert(&timestamp, &observations);
+            return;
+        }
+
+        self.price_rounds.remove(&timestamp);
+        let feeders: Vec<AccountId> = observations.iter().map(|(feeder, _)| feeder.clone()).collect();
+        self.emit_event("price_round_finalized", json!({
+            "timestamp": timestamp,
+            "feeders": feeders,
+        }));
+        let median = aggregate(timestamp, &observations);
+        self.record_feeder_outliers(timestamp, &observations, &median);
+        self.internal_set_price_per_region(median);
+    }
+
+    // drop the observations of a round that will not reach quorum
+    pub fn drop_price_round(&mut self, timestamp: u64) {
+        self.assert_owner();
+
+        if self.price_rounds.remove(&timestamp).is_some() {
+            self.emit_event("price_round_dropped", json!({ "timestamp": timestamp }));
+        }
+    }
+
+    // set how many observations finalize a price round
+    pub fn set_feeder_quorum(&mut self, quorum: u32) {
+        self.assert_config_authority();
+        require!(quorum > 0, "quorum must be greater than 0");
+
+        self.feeder_quorum = quorum;
+        self.emit_event("feeder_quorum_set", json!({ "quorum": quorum }));
+    }
+
+    // get how many observations finalize a price round
+    pub fn get_feeder_quorum(&self) -> u32 {
+        self.feeder_quorum
+    }
+
+    // get the observations submitted so far for an open price round
+    pub fn get_price_round(&self, timestamp: u64) -> Vec<(AccountId, PricePerRegion)> {
+        self.price_rounds.get(&timestamp).unwrap_or_default()
+    }
+
+    // get the timestamps of the open price rounds with their number of observations, paginated
+    pub fn get_open_price_rounds(&self, from_index: u64, limit: u64) -> Vec<(u64, u32)> {
+        self.price_rounds
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .map(|(timestamp, observations)| (timestamp, observations.len() as u32))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, set_context, NEAR};
+
+    // a contract with three price feeders and a quorum of 3
+    fn feeders_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        for feeder in ["feeder1_near", "feeder2_near", "feeder3_near"] {
+            contract.add_role(account(feeder), Role::PriceFeeder);
+        }
+        contract.set_feeder_quorum(3);
+        contract
+    }
+
+    fn observation(global: f64, europe: Option<f64>) -> PricePerRegion {
+        let prices: BTreeMap<u8, U128> = europe.map(|price| vec![(REGION_EUROPE, fil(price))]).unwrap_or_default().into_iter().collect();
+        PricePerRegion { verified_prices: prices.clone(), prices, global: fil(global), timestamp: 10, ..Default::default() }
+    }
+
+    #[test]
+    fn finalize_price_round_at_quorum() {
+        let mut contract = feeders_contract();
+
+        set_context("feeder1_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.1, Some(1.0)));
+        set_context("feeder2_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.9, None));
+        assert_eq!(2, contract.get_price_round(10).len());
+        assert_eq!(vec![(10, 2)], contract.get_open_price_rounds(0, 10));
+        assert!(contract.get_price_nearest(10).is_none());
+
+        set_context("feeder3_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.2, Some(2.0)));
+
+        let result = contract.get_price_nearest(10).unwrap();
+        assert_eq!(fil(0.2), result.global);
+        // only two observations priced Europe
+        assert_eq!(fil(1.5), result.prices[&REGION_EUROPE]);
+        assert_eq!(fil(1.5), result.verified_prices[&REGION_EUROPE]);
+        assert_eq!(None, result.verified_global);
+        assert!(contract.get_open_price_rounds(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "feeder1_near already submitted an observation for price round 10")]
+    fn submit_price_observation_twice() {
+        let mut contract = feeders_contract();
+
+        set_context("feeder1_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.1, None));
+        contract.submit_price_observation(observation(0.2, None));
+    }
+
+    #[test]
+    #[should_panic(expected = "price round 10 is already finalized")]
+    fn submit_price_observation_after_finalization() {
+        let mut contract = feeders_contract();
+        contract.set_feeder_quorum(1);
+
+        set_context("feeder1_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.1, None));
+        set_context("feeder2_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.2, None));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_QUORUM_REQUIRED")]
+    fn lone_feeder_cannot_set_price() {
+        let mut contract = feeders_contract();
+        contract.set_price_per_region(observation(0.1, None));
+
+        // a single observation leaves the latest price to the quorum
+        set_context("feeder1_near", NEAR, 0);
+        contract.submit_price_observation(PricePerRegion { timestamp: 20, ..observation(9.0, None) });
+        assert_eq!(fil(0.1), contract.get_latest_price_per_region().global);
+
+        contract.set_price_per_region(PricePerRegion { timestamp: 20, ..observation(9.0, None) });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn submit_price_observation_without_role() {
+        let mut contract = feeders_contract();
+
+        set_context("bob_near", NEAR, 0);
+        contract.submit_price_observation(observation(0.1, None));
+    }
+}
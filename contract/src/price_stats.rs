@@ -0,0 +1,165 @@
+/*
+ * Price statistics of a region computed on-chain from the listed storage providers, so they can be checked
+ * against the prices pushed by the price feeders
+ */
+
+use crate::*;
+
+const BYTES_PER_GIB: u128 = 1 << 30;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceStats {
+    count: u64,
+    // normalized prices in attoFIL/TiB/day, all zeros without providers
+    min: U128,
+    p25: U128,
+    median: U128,
+    p75: U128,
+    max: U128,
+    weighted_mean: U128, // weighted by power in whole GiB, rounded down
+}
+
+// the nearest-rank percentile of ascending prices
+fn percentile(prices: &[u128], percent: usize) -> u128 {
+    let rank = (percent * prices.len()).div_ceil(100);
+    prices[rank.max(1) - 1]
+}
+
+// the median of ascending values, the mean of the two middle values rounded down for an even count
+pub(crate) fn median(values: &[u128]) -> u128 {
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        let (low, high) = (values[middle - 1], values[middle]);
+        low / 2 + high / 2 + (low % 2 + high % 2) / 2
+    } else {
+        values[middle]
+    }
+}
+
+impl PriceStats {
+    // summarize (normalized price, power) pairs given in ascending price order
+    fn from_sorted(providers: &[(u128, u128)]) -> Self {
+        let prices: Vec<u128> = providers.iter().map(|(price, _)| *price).collect();
+        if prices.is_empty() {
+            let zero = U128(0);
+            return PriceStats { count: 0, min: zero, p25: zero, median: zero, p75: zero, max: zero, weighted_mean: zero };
+        }
+
+        let mut weighted_total: u128 = 0;
+        let mut total_weight: u128 = 0;
+        for (price, power) in providers.iter() {
+            let weight = power / BYTES_PER_GIB;
+            weighted_total = price.checked_mul(weight)
+                .and_then(|weighted| weighted_total.checked_add(weighted))
+                .expect("weighted price overflows u128");
+            total_weight += weight;
+        }
+
+        PriceStats {
+            count: prices.len() as u64,
+            min: U128(prices[0]),
+            p25: U128(percentile(&prices, 25)),
+            median: U128(median(&prices)),
+            p75: U128(percentile(&prices, 75)),
+            max: U128(prices[prices.len() - 1]),
+            weighted_mean: U128(weighted_total.checked_div(total_weight).unwrap_or(0)),
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the min, max, median, quartiles and power-weighted mean of the normalized prices in a region
+    // note: this reads every provider of the region, so gas grows with the size of the region
+    pub fn get_price_stats(&self, region: u8) -> PriceStats {
+        let providers: Vec<(u128, u128)> = self.price_index
+            .iter_from((region, 0, String::new()))
+            .take_while(|((key_region, _, _), _)| *key_region == region)
+            .filter_map(|((_, price, id), _)| self.storage_providers.get(&id).map(|sp| (price, sp.power.0)))
+            .collect();
+
+        PriceStats::from_sorted(&providers)
+    }
+
+    // get the same statistics over the verified deal prices of the providers in a region offering verified deals
+    // note: verified prices are not indexed, so they are sorted here and gas grows with the size of the region
+    pub fn get_verified_price_stats(&self, region: u8) -> PriceStats {
+        let mut providers: Vec<(u128, u128)> = self.region_index
+            .get(&region)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.storage_providers.get(&id))
+                    .filter_map(|sp| {
+                        let price = normalize_price(sp.verified_price?.0, sp.price_unit)?;
+                        Some((price, sp.power.0))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        providers.sort_unstable();
+
+        PriceStats::from_sorted(&providers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    // a price in attoFIL/GiB/epoch normalized to attoFIL/TiB/day
+    fn normalized(price: U128) -> U128 {
+        U128(normalize_price(price.0, PRICE_UNIT_FIL_PER_GIB_PER_EPOCH).unwrap())
+    }
+
+    #[test]
+    fn percentiles() {
+        let providers: Vec<(u128, u128)> = (1..=10).map(|price| (price, 0)).collect();
+        let stats = PriceStats::from_sorted(&providers);
+        assert_eq!(U128(1), stats.min);
+        assert_eq!(U128(3), stats.p25);
+        assert_eq!(U128(5), stats.median);
+        assert_eq!(U128(8), stats.p75);
+        assert_eq!(U128(10), stats.max);
+        assert_eq!(U128(0), stats.weighted_mean);
+
+        let stats = PriceStats::from_sorted(&[(7, 0)]);
+        assert_eq!((U128(7), U128(7), U128(7)), (stats.p25, stats.median, stats.p75));
+        assert_eq!(0, PriceStats::from_sorted(&[]).count);
+    }
+
+    #[test]
+    fn get_price_stats() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(30.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_EUROPE, power: gib(0.5), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_ASIA, power: gib(90.0), price: fil(0.01), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let stats = contract.get_price_stats(REGION_EUROPE);
+        assert_eq!(3, stats.count);
+        assert_eq!(normalized(fil(0.1)), stats.min);
+        assert_eq!(normalized(fil(0.1)), stats.p25);
+        assert_eq!(normalized(fil(0.2)), stats.median);
+        assert_eq!(normalized(fil(0.3)), stats.p75);
+        assert_eq!(normalized(fil(0.3)), stats.max);
+        // f03 has less than a GiB and no weight: (0.1 * 30 + 0.3 * 10) / 40
+        assert_eq!(normalized(fil(0.15)), stats.weighted_mean);
+
+        assert_eq!(1, contract.get_price_stats(REGION_ASIA).count);
+        assert_eq!(0, contract.get_verified_price_stats(REGION_EUROPE).count);
+
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), verified_price: Some(fil(0.05)), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        let stats = contract.get_verified_price_stats(REGION_EUROPE);
+        assert_eq!(1, stats.count);
+        assert_eq!(normalized(fil(0.05)), stats.median);
+        assert_eq!(0, contract.get_price_stats(REGION_AFRICA).count);
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Storage provider self-registration: an account claims a miner id, the claim is approved by the
+ * owner or an SpManager, after which the account manages the listing of that miner id itself
+ */
+
+use crate::*;
+
+impl FilMarket {
+    // fail the call unless the predecessor controls the given storage provider
+    pub(crate) fn assert_provider_account(&self, id: &str) {
+        let account_id = env::predecessor_account_id();
+
+        require!(
+            self.provider_accounts.get(&id.to_string()).as_ref() == Some(&account_id),
+            format!("{}: account_id {} does not control storage provider {}", errors::ERR_NOT_PROVIDER_ACCOUNT, account_id, id)
+        );
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // claim control of a storage provider id, the attached deposit must cover the storage of the claim
+    #[payable]
+    pub fn claim_provider(&mut self, id: String) {
+        self.assert_not_paused(Feature::Providers);
+        let account_id = env::predecessor_account_id();
+
+        require!(id.len() <= MAX_ID_LEN, format!("storage provider id is longer than {} bytes", MAX_ID_LEN));
+        let id = self.expect_provider_id(&id);
+        require!(self.provider_accounts.get(&id).is_none(), format!("storage provider {} is already claimed", id));
+        require!(self.provider_claims.get(&id).is_none(), format!("a claim for storage provider {} is already pending", id));
+
+        let initial_storage_usage = env::storage_usage();
+        self.provider_claims.insert(&id, &account_id);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("provider_claimed", json!({
+            "id": id,
+            "account_id": account_id,
+        }));
+    }
+
+    // grant the claiming account control of the storage provider
+    pub fn approve_provider_claim(&mut self, id: String) {
+        self.assert_role(Role::SpManager);
+        let id = self.lookup_provider_id(&id);
+
+        let account_id = self.provider_claims.remove(&id)
+            .unwrap_or_else(|| panic!("no claim pending for storage provider {}", id));
+        self.provider_accounts.insert(&id, &account_id);
+
+        self.emit_event("provider_claim_approved", json!({
+            "id": id,
+            "account_id": account_id,
+        }));
+    }
+
+    // drop a pending claim
+    pub fn reject_provider_claim(&mut self, id: String) {
+        self.assert_role(Role::SpManager);
+        let id = self.lookup_provider_id(&id);
+
+        let account_id = self.provider_claims.remove(&id)
+            .unwrap_or_else(|| panic!("no claim pending for storage provider {}", id));
+
+        self.emit_event("provider_claim_rejected", json!({
+            "id": id,
+            "account_id": account_id,
+        }));
+    }
+
+    // take control of a storage provider away from its account, the listing itself is kept
+    pub fn revoke_provider_claim(&mut self, id: String) {
+        self.assert_role(Role::SpManager);
+        let id = self.lookup_provider_id(&id);
+
+        let account_id = self.provider_accounts.remove(&id)
+            .unwrap_or_else(|| panic!("storage provider {} is not claimed", id));
+
+        self.emit_event("provider_claim_revoked", json!({
+            "id": id,
+            "account_id": account_id,
+        }));
+    }
+
+    // add or update the listing of a storage provider controlled by the caller, once its listing fee is paid
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn register_provider(&mut self, storage_provider: StorageProvider) -> BatchResult {
+        self.assert_provider_account(&storage_provider.id);
+        self.assert_listing_paid(&storage_provider.id);
+
+        let initial_storage_usage = env::storage_usage();
+        let result = self.internal_update_storage_providers(vec![storage_provider], true);
+        self.refund_deposit(initial_storage_usage);
+
+        result
+    }
+
+    // get the account controlling a storage provider, if any
+    pub fn get_provider_account(&self, id: String) -> Option<AccountId> {
+        self.provider_accounts.get(&self.lookup_provider_id(&id))
+    }
+
+    // get the pending claims as (storage provider id, account), paginated
+    pub fn get_pending_provider_claims(&self, from_index: u64, limit: u64) -> Vec<(String, AccountId)> {
+        self.provider_claims
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    fn provider(id: &str, price: f64) -> StorageProvider {
+        StorageProvider { id: id.to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(price), ..Default::default() }
+    }
+
+    fn claimed_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+        assert_eq!(vec![("f01234".to_string(), account("miner_near"))], contract.get_pending_provider_claims(0, 10));
+
+        set_context("carol_near", NEAR, 0);
+        contract.approve_provider_claim("f01234".to_string());
+        contract
+    }
+
+    #[test]
+    fn claim_then_register_provider() {
+        let mut contract = claimed_contract();
+        assert!(contract.get_pending_provider_claims(0, 10).is_empty());
+        assert_eq!(Some(account("miner_near")), contract.get_provider_account("f01234".to_string()));
+
+        set_context("miner_near", NEAR, 0);
+        assert_eq!(1, contract.register_provider(provider("f01234", 0.3)).added);
+        contract.register_provider(StorageProvider { label: Some("miner".to_string()), ..provider("f01234", 0.2) });
+
+        let result = contract.get_storage_providers();
+        assert_eq!(1, result.len());
+        assert_eq!(fil(0.2), result[0].price);
+        assert_eq!(Some("miner".to_string()), result[0].label);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PROVIDER_ACCOUNT: account_id other_near does not control storage provider f01234")]
+    fn register_provider_not_claimed() {
+        let mut contract = claimed_contract();
+
+        set_context("other_near", NEAR, 0);
+        contract.register_provider(provider("f01234", 0.3));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PROVIDER_ACCOUNT")]
+    fn register_provider_after_revoke() {
+        let mut contract = claimed_contract();
+
+        set_context("carol_near", NEAR, 0);
+        contract.revoke_provider_claim("f01234".to_string());
+        assert_eq!(None, contract.get_provider_account("f01234".to_string()));
+
+        set_context("miner_near", NEAR, 0);
+        contract.register_provider(provider("f01234", 0.3));
+    }
+
+    #[test]
+    fn reject_provider_claim() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+
+        set_context("carol_near", NEAR, 0);
+        contract.reject_provider_claim("f01234".to_string());
+        assert!(contract.get_pending_provider_claims(0, 10).is_empty());
+        assert_eq!(None, contract.get_provider_account("f01234".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider f01234 is already claimed")]
+    fn claim_provider_already_claimed() {
+        let mut contract = claimed_contract();
+
+        set_context("other_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn approve_provider_claim_without_role() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+        contract.approve_provider_claim("f01234".to_string());
+    }
+}
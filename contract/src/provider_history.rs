@@ -0,0 +1,136 @@
+/*
+ * Bounded price and power history per storage provider
+ */
+
+use crate::*;
+
+pub const DEFAULT_PROVIDER_HISTORY_DEPTH: u32 = 10;
+pub const MAX_PROVIDER_HISTORY_DEPTH: u32 = 100;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderHistoryEntry {
+    timestamp: u64, // block timestamp in nanoseconds
+    price: U128,    // attoFIL per price_unit
+    power: U128,    // bytes
+}
+
+impl FilMarket {
+    // append the current price and power of a storage provider, dropping the oldest entries past the depth
+    pub(crate) fn record_provider_history(&mut self, sp: &StorageProvider) {
+        let mut history = self.provider_history.get(&sp.id).unwrap_or_default();
+        history.push(ProviderHistoryEntry { timestamp: sp.last_updated, price: sp.price, power: sp.power });
+
+        let excess = history.len().saturating_sub(self.provider_history_depth as usize);
+        history.drain(..excess);
+
+        self.provider_history.insert(&sp.id, &history);
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set how many history entries are kept per storage provider, longer histories are trimmed on their next update
+    pub fn set_provider_history_depth(&mut self, depth: u32) {
+        self.assert_config_authority();
+
+        require!(
+            depth > 0 && depth <= MAX_PROVIDER_HISTORY_DEPTH,
+            format!("depth must be between 1 and {}", MAX_PROVIDER_HISTORY_DEPTH)
+        );
+
+        self.provider_history_depth = depth;
+        self.emit_event("provider_history_depth_set", json!({ "depth": depth }));
+    }
+
+    // get how many history entries are kept per storage provider
+    pub fn get_provider_history_depth(&self) -> u32 {
+        self.provider_history_depth
+    }
+
+    // get the history entries of a storage provider with from_ns <= timestamp <= to_ns, in ascending order
+    pub fn get_provider_history(&self, id: String, from_ns: u64, to_ns: u64) -> Vec<ProviderHistoryEntry> {
+        let history = self.provider_history.get(&id).unwrap_or_default();
+        let skip = history.len().saturating_sub(self.provider_history_depth as usize);
+
+        history
+            .into_iter()
+            .skip(skip)
+            .filter(|entry| entry.timestamp >= from_ns && entry.timestamp <= to_ns)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn ns(seconds: u64) -> u64 {
+        seconds * NANOSECONDS_PER_SECOND
+    }
+
+    fn update_price(contract: &mut FilMarket, price: f64) {
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(price), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    fn provider_history_is_recorded() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        update_price(&mut contract, 0.3);
+
+        set_context("carol_near", NEAR, 200);
+        update_price(&mut contract, 0.2);
+
+        set_context("carol_near", NEAR, 300);
+        contract.patch_provider("f01".to_string(), Some(gib(20.0)), None, None);
+
+        let history = contract.get_provider_history("f01".to_string(), 0, u64::MAX);
+        assert_eq!(3, history.len());
+        assert_eq!((ns(100), fil(0.3)), (history[0].timestamp, history[0].price));
+        assert_eq!((ns(200), fil(0.2)), (history[1].timestamp, history[1].price));
+        assert_eq!((ns(300), gib(20.0)), (history[2].timestamp, history[2].power));
+
+        let history = contract.get_provider_history("f01".to_string(), ns(150), ns(250));
+        assert_eq!(1, history.len());
+        assert_eq!(ns(200), history[0].timestamp);
+
+        assert!(contract.get_provider_history("f02".to_string(), 0, u64::MAX).is_empty());
+
+        contract.delete_storage_providers(vec!["f01".to_string()]);
+        assert!(contract.get_provider_history("f01".to_string(), 0, u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn provider_history_depth() {
+        set_context("carol_near", NEAR, 1);
+        let mut contract = FilMarket::new();
+        contract.set_provider_history_depth(2);
+        assert_eq!(2, contract.get_provider_history_depth());
+
+        for (now, price) in [(1, 0.1), (2, 0.2), (3, 0.3)] {
+            set_context("carol_near", NEAR, now);
+            update_price(&mut contract, price);
+        }
+
+        let history = contract.get_provider_history("f01".to_string(), 0, u64::MAX);
+        assert_eq!(vec![ns(2), ns(3)], history.iter().map(|entry| entry.timestamp).collect::<Vec<u64>>());
+
+        // lowering the depth hides the older entries right away
+        contract.set_provider_history_depth(1);
+        let history = contract.get_provider_history("f01".to_string(), 0, u64::MAX);
+        assert_eq!(1, history.len());
+        assert_eq!(fil(0.3), history[0].price);
+    }
+
+    #[test]
+    #[should_panic(expected = "depth must be between 1 and 100")]
+    fn set_provider_history_depth_too_large() {
+        set_context("carol_near", NEAR, 1);
+        let mut contract = FilMarket::new();
+
+        contract.set_provider_history_depth(MAX_PROVIDER_HISTORY_DEPTH + 1);
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * Storage provider ids are Filecoin actor ID addresses of the contract's network: f0<actor id> on mainnet,
+ * t0<actor id> on testnets. Ids are normalized (surrounding whitespace removed, lowercase) before being stored or
+ * looked up, so "F01000 " and "f01000" are the same provider
+ */
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FilecoinNetwork {
+    Mainnet, // f addresses
+    Testnet, // t addresses
+}
+
+impl FilecoinNetwork {
+    fn prefix(self) -> char {
+        match self {
+            FilecoinNetwork::Mainnet => 'f',
+            FilecoinNetwork::Testnet => 't',
+        }
+    }
+}
+
+// normalize an ID address of the given network, Err with the reason it is malformed
+pub(crate) fn parse_id_address(id: &str, network: FilecoinNetwork) -> Result<String, String> {
+    let id = id.trim().to_lowercase();
+    let mut chars = id.chars();
+
+    match chars.next() {
+        Some(prefix) if prefix == network.prefix() => (),
+        Some('f') | Some('t') => return Err(format!("not a {:?} address", network)),
+        _ => return Err("not a Filecoin address".to_string()),
+    }
+    if chars.next() != Some('0') {
+        return Err("not an ID address".to_string());
+    }
+
+    let actor_id = chars.as_str();
+    if actor_id.is_empty() || !actor_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err("actor id must be a number".to_string());
+    }
+    if actor_id.len() > 1 && actor_id.starts_with('0') {
+        return Err("actor id has leading zeros".to_string());
+    }
+    if actor_id.parse::<u64>().is_err() {
+        return Err("actor id is out of range".to_string());
+    }
+
+    Ok(id)
+}
+
+impl FilMarket {
+    // the normalized form of a storage provider id, Err with the reason it is malformed
+    pub(crate) fn normalize_provider_id(&self, id: &str) -> Result<String, String> {
+        parse_id_address(id, self.filecoin_network)
+    }
+
+    // the normalized form of a storage provider id, failing the call if it is malformed
+    pub(crate) fn expect_provider_id(&self, id: &str) -> String {
+        self.normalize_provider_id(id)
+            .unwrap_or_else(|reason| panic!("invalid storage provider id {}: {}", id, reason))
+    }
+
+    // the normalized form of a storage provider id to look it up by, a malformed id is kept as is and matches nothing
+    pub(crate) fn lookup_provider_id(&self, id: &str) -> String {
+        self.normalize_provider_id(id).unwrap_or_else(|_| id.to_string())
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the Filecoin network of the storage provider ids, only new ids are checked against it
+    pub fn set_filecoin_network(&mut self, network: FilecoinNetwork) {
+        self.assert_config_authority();
+
+        self.filecoin_network = network;
+        self.emit_event("filecoin_network_set", json!({ "network": network }));
+    }
+
+    // get the Filecoin network of the storage provider ids
+    pub fn get_filecoin_network(&self) -> FilecoinNetwork {
+        self.filecoin_network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    #[test]
+    fn parse_id_addresses() {
+        let mainnet = FilecoinNetwork::Mainnet;
+
+        assert_eq!(Ok("f01000".to_string()), parse_id_address("f01000", mainnet));
+        assert_eq!(Ok("f01000".to_string()), parse_id_address(" F01000 ", mainnet));
+        assert_eq!(Ok("f00".to_string()), parse_id_address("f00", mainnet));
+        assert_eq!(Ok("t01000".to_string()), parse_id_address("t01000", FilecoinNetwork::Testnet));
+
+        assert_eq!(Err("not a Mainnet address".to_string()), parse_id_address("t01000", mainnet));
+        assert_eq!(Err("not a Filecoin address".to_string()), parse_id_address("garbage", mainnet));
+        assert_eq!(Err("not a Filecoin address".to_string()), parse_id_address("", mainnet));
+        assert_eq!(Err("not an ID address".to_string()), parse_id_address("f1abcdef", mainnet));
+        assert_eq!(Err("actor id must be a number".to_string()), parse_id_address("f0", mainnet));
+        assert_eq!(Err("actor id must be a number".to_string()), parse_id_address("f01 000", mainnet));
+        assert_eq!(Err("actor id has leading zeros".to_string()), parse_id_address("f001000", mainnet));
+        assert_eq!(Err("actor id is out of range".to_string()), parse_id_address("f099999999999999999999", mainnet));
+    }
+
+    #[test]
+    fn malformed_ids_are_reported() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01000 ".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "t01000".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "garbage".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+        ];
+        let result = contract.update_storage_providers(sp_list, None);
+
+        assert_eq!((1, 2), (result.added, result.skipped));
+        assert_eq!(vec![
+            ("t01000".to_string(), "not a Mainnet address".to_string()),
+            ("garbage".to_string(), "not a Filecoin address".to_string()),
+        ], result.errors);
+        assert!(contract.has_storage_provider("f01000".to_string()));
+        assert_eq!(1, contract.get_storage_providers_count());
+
+        // testnet ids are accepted once the network is set
+        contract.set_filecoin_network(FilecoinNetwork::Testnet);
+        let sp_list = vec![StorageProvider { id: "T01000".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        assert_eq!(1, contract.update_storage_providers(sp_list, None).added);
+        assert!(contract.has_storage_provider("t01000".to_string()));
+    }
+
+    #[test]
+    fn ids_are_normalized_on_lookup() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider { id: "f01000".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02000".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        assert!(contract.has_storage_provider("F01000 ".to_string()));
+        assert_eq!("f01000", contract.get_storage_provider(" F01000".to_string()).unwrap().id);
+        assert!(contract.get_storage_provider("garbage".to_string()).is_none());
+
+        contract.patch_provider("F01000".to_string(), None, Some(fil(0.2)), None);
+        contract.set_provider_verified(vec!["F01000".to_string()], true);
+        let storage_provider = contract.get_storage_provider("f01000".to_string()).unwrap();
+        assert_eq!((fil(0.2), true), (storage_provider.price, storage_provider.verified));
+
+        let result = contract.delete_storage_providers(vec!["F02000".to_string(), "garbage".to_string()]);
+        assert_eq!((1, 1), (result.removed, result.skipped));
+        assert!(!contract.has_storage_provider("f02000".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid storage provider id f1abc: not an ID address")]
+    fn claim_malformed_id() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.claim_provider("f1abc".to_string());
+    }
+}
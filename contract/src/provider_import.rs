@@ -0,0 +1,194 @@
+/*
+ * Bulk import of storage providers from a binary payload: a Borsh-encoded Vec<ProviderRecord>, optionally
+ * gzip-compressed, sent base64-encoded. The records hold only the fields a feeder sets, so a payload carries
+ * several times the providers of the same update sent as JSON
+ *
+ * The gzip CRC is not checked, a corrupted payload fails the inflate or the Borsh decoding instead
+ */
+
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+
+// bytes a payload may inflate to, so a small payload can't exhaust the memory of the call
+pub const MAX_IMPORT_BYTES: usize = 1 << 20;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const GZIP_DEFLATE: u8 = 8;
+const GZIP_HEADER_LEN: usize = 10;
+const GZIP_TRAILER_LEN: usize = 8;
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+// a storage provider as sent in an import payload
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct ProviderRecord {
+    pub id: String,
+    pub region: u8,
+    pub power: u128, // bytes
+    pub price: u128, // attoFIL per price_unit
+    pub price_unit: u8,
+    pub label: Option<String>,
+    pub verified_price: Option<u128>,  // attoFIL per price_unit
+    pub retrieval_price: Option<u128>, // attoFIL per GiB retrieved
+}
+
+impl From<ProviderRecord> for StorageProvider {
+    fn from(record: ProviderRecord) -> Self {
+        StorageProvider {
+            id: record.id,
+            region: record.region,
+            power: U128(record.power),
+            price: U128(record.price),
+            price_unit: record.price_unit,
+            label: record.label,
+            verified_price: record.verified_price.map(U128),
+            retrieval_price: record.retrieval_price.map(U128),
+            ..Default::default()
+        }
+    }
+}
+
+// the deflate stream of a gzip member, Err if the header is malformed
+fn gzip_body(data: &[u8]) -> Result<&[u8], String> {
+    if data.len() < GZIP_HEADER_LEN + GZIP_TRAILER_LEN || data[..2] != GZIP_MAGIC {
+        return Err("not a gzip payload".to_string());
+    }
+    if data[2] != GZIP_DEFLATE {
+        return Err("unsupported gzip compression method".to_string());
+    }
+
+    let flags = data[3];
+    let end = data.len() - GZIP_TRAILER_LEN;
+    let mut pos = GZIP_HEADER_LEN;
+    if flags & FLAG_EXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or("truncated gzip header")?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let len = data.get(pos..end).and_then(|rest| rest.iter().position(|b| *b == 0)).ok_or("truncated gzip header")?;
+            pos += len + 1;
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        pos += 2;
+    }
+
+    data.get(pos..end).ok_or_else(|| "truncated gzip header".to_string())
+}
+
+// inflate a gzip payload, checking the size recorded in its trailer
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    let body = gzip_body(data)?;
+    let inflated = miniz_oxide::inflate::decompress_to_vec_with_limit(body, MAX_IMPORT_BYTES)
+        .map_err(|_| format!("gzip payload is corrupted or inflates to more than {} bytes", MAX_IMPORT_BYTES))?;
+
+    let trailer = &data[data.len() - 4..];
+    let size = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if size != inflated.len() as u32 {
+        return Err("gzip payload size does not match its trailer".to_string());
+    }
+
+    Ok(inflated)
+}
+
+// decode the records of an import payload, Err with the reason it is malformed
+fn decode_records(payload: &[u8], gzip: bool) -> Result<Vec<ProviderRecord>, String> {
+    let inflated;
+    let bytes = if gzip {
+        inflated = gunzip(payload)?;
+        &inflated[..]
+    } else {
+        payload
+    };
+
+    Vec::<ProviderRecord>::try_from_slice(bytes).map_err(|e| format!("records are not valid Borsh, {}", e))
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // add or update the storage providers of a Borsh-encoded Vec<ProviderRecord>, gzip-compressed when gzip is set
+    // the records go through the same checks as update_storage_providers, duplicated ids keep the last record
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn import_storage_providers(&mut self, payload: Base64VecU8, gzip: Option<bool>) -> BatchResult {
+        self.assert_role(Role::SpManager);
+
+        let records = decode_records(&payload.0, gzip.unwrap_or(false))
+            .unwrap_or_else(|reason| panic!("invalid import payload: {}", reason));
+
+        let initial_storage_usage = env::storage_usage();
+        let result = self.internal_update_storage_providers(records.into_iter().map(StorageProvider::from).collect(), false);
+        self.refund_deposit(initial_storage_usage);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn records() -> Vec<ProviderRecord> {
+        vec![
+            ProviderRecord { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0).0, price: fil(0.1).0, price_unit: 0, label: None, verified_price: None, retrieval_price: None },
+            ProviderRecord { id: "f02".to_string(), region: REGION_ASIA, power: gib(20.0).0, price: fil(0.2).0, price_unit: 0, label: Some("sp".to_string()), verified_price: Some(fil(0.05).0), retrieval_price: Some(fil(0.01).0) },
+        ]
+    }
+
+    // a gzip member with a file name, as written by the gzip tool
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut gz = vec![0x1f, 0x8b, GZIP_DEFLATE, FLAG_NAME, 0, 0, 0, 0, 0, 0xff];
+        gz.extend_from_slice(b"providers.borsh\0");
+        gz.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+        gz.extend_from_slice(&[0; 4]);
+        gz.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        gz
+    }
+
+    #[test]
+    fn import_borsh_and_gzip_payloads() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let payload = records().try_to_vec().unwrap();
+        assert_eq!(2, contract.import_storage_providers(Base64VecU8(payload.clone()), None).added);
+
+        let sp = contract.get_storage_provider("f02".to_string()).unwrap();
+        assert_eq!((REGION_ASIA, gib(20.0), fil(0.2)), (sp.region, sp.power, sp.price));
+        assert_eq!((Some("sp".to_string()), Some(fil(0.05))), (sp.label, sp.verified_price));
+
+        let result = contract.import_storage_providers(Base64VecU8(gzip(&payload)), Some(true));
+        assert_eq!((0, 2), (result.added, result.updated));
+    }
+
+    #[test]
+    fn decode_malformed_payloads() {
+        let payload = records().try_to_vec().unwrap();
+        assert_eq!(Ok(records()), decode_records(&gzip(&payload), true));
+
+        assert_eq!(Err("not a gzip payload".to_string()), decode_records(&payload, true));
+        let mut gz = gzip(&payload);
+        let len = gz.len();
+        gz[len - 1] ^= 1;
+        assert_eq!(Err("gzip payload size does not match its trailer".to_string()), decode_records(&gz, true));
+        assert!(decode_records(&payload[..payload.len() - 1], false).unwrap_err().starts_with("records are not valid Borsh"));
+
+        // a payload inflating past the limit is rejected
+        let bomb = gzip(&vec![0; MAX_IMPORT_BYTES + 1]);
+        assert!(decode_records(&bomb, true).unwrap_err().contains("inflates to more than"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn import_without_role() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        contract.import_storage_providers(Base64VecU8(records().try_to_vec().unwrap()), None);
+    }
+}
@@ -0,0 +1,182 @@
+/*
+ * Indexes of the storage providers maintained on every write that adds, changes or removes a provider: the ids of
+ * every listed provider in order, and for the Active providers the ids, active count and total power per region,
+ * and the providers of each region ordered by normalized price and by power
+ */
+
+use crate::*;
+use near_sdk::collections::UnorderedSet;
+
+// (region, normalized price, id), ascending order is cheapest first
+type PriceKey = (u8, u128, String);
+// (region, u128::MAX - power, id), ascending order is largest first
+type PowerKey = (u8, u128, String);
+
+fn price_key(sp: &StorageProvider) -> PriceKey {
+    (sp.region, normalize_price(sp.price.0, sp.price_unit).unwrap_or(u128::MAX), sp.id.clone())
+}
+
+fn power_key(sp: &StorageProvider) -> PowerKey {
+    (sp.region, u128::MAX - sp.power.0, sp.id.clone())
+}
+
+impl FilMarket {
+    // update the indexes from the previous to the current state of a storage provider,
+    // None for a provider being added or removed, a provider that isn't Active is left out of the indexes
+    pub(crate) fn reindex_provider(&mut self, old: Option<&StorageProvider>, new: Option<&StorageProvider>) {
+        match (old, new) {
+            (None, Some(sp)) => { self.provider_ids.insert(&sp.id, &()); }
+            (Some(sp), None) => { self.provider_ids.remove(&sp.id); }
+            _ => {}
+        }
+
+        let old = old.filter(|sp| sp.is_active());
+        let new = new.filter(|sp| sp.is_active());
+        let old_region = old.map(|sp| sp.region);
+        let new_region = new.map(|sp| sp.region);
+
+        if let Some(sp) = old {
+            self.power_per_region.subtract(sp.region, sp.power.0);
+            if new_region != old_region {
+                self.active_per_region.decrement(sp.region);
+            }
+            if new.map(price_key) != Some(price_key(sp)) {
+                self.price_index.remove(&price_key(sp));
+            }
+            if new.map(power_key) != Some(power_key(sp)) {
+                self.power_index.remove(&power_key(sp));
+            }
+            if new_region != old_region {
+                if let Some(mut ids) = self.region_index.get(&sp.region) {
+                    ids.remove(&sp.id);
+                    self.region_index.insert(&sp.region, &ids);
+                }
+            }
+        }
+
+        if let Some(sp) = new {
+            self.power_per_region.add(sp.region, sp.power.0);
+            if new_region != old_region {
+                self.active_per_region.increment(sp.region);
+            }
+            self.price_index.insert(&price_key(sp), &());
+            self.power_index.insert(&power_key(sp), &());
+            if new_region != old_region {
+                let mut ids = self.region_index.get(&sp.region)
+                    .unwrap_or_else(|| UnorderedSet::new(StorageKey::RegionProviders { region: sp.region }));
+                ids.insert(&sp.id);
+                self.region_index.insert(&sp.region, &ids);
+            }
+        }
+    }
+
+    // the first n storage providers of a region in the given index
+    fn first_in_region(&self, index: &TreeMap<(u8, u128, String), ()>, region: u8, n: u64) -> Vec<StorageProvider> {
+        index
+            .iter_from((region, 0, String::new()))
+            .take_while(|((key_region, _, _), _)| *key_region == region)
+            .take(view_limit(n))
+            .filter_map(|((_, _, id), _)| self.storage_providers.get(&id))
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the storage providers of a region, paginated
+    pub fn get_storage_providers_by_region(&self, region: u8, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        let ids = match self.region_index.get(&region) {
+            Some(ids) => ids,
+            None => return Vec::new(),
+        };
+
+        ids.as_vector()
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .filter_map(|id| self.storage_providers.get(&id))
+            .collect()
+    }
+
+    // get the n cheapest storage providers of a region by normalized price, ties broken by id
+    pub fn get_cheapest_providers(&self, region: u8, n: u64) -> Vec<StorageProvider> {
+        self.first_in_region(&self.price_index, region, n)
+    }
+
+    // get the n largest storage providers of a region by power, ties broken by id
+    pub fn get_largest_providers(&self, region: u8, n: u64) -> Vec<StorageProvider> {
+        self.first_in_region(&self.power_index, region, n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn ids(storage_providers: Vec<StorageProvider>) -> Vec<String> {
+        let mut ids: Vec<String> = storage_providers.into_iter().map(|sp| sp.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn get_storage_providers_by_region() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.2), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(vec!["f01", "f02"], ids(contract.get_storage_providers_by_region(REGION_EUROPE, 0, 10)));
+        assert_eq!(1, contract.get_storage_providers_by_region(REGION_EUROPE, 1, 10).len());
+        assert!(contract.get_storage_providers_by_region(REGION_AFRICA, 0, 10).is_empty());
+
+        // every write moving a provider keeps the index in sync
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_ASIA, power: gib(10.0), price: fil(0.3), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        contract.patch_provider("f02".to_string(), None, None, Some(REGION_AFRICA));
+        contract.reassign_regions(vec![("f03".to_string(), REGION_OTHER)]);
+        contract.delete_storage_providers(vec!["f04".to_string()]);
+
+        assert!(contract.get_storage_providers_by_region(REGION_EUROPE, 0, 10).is_empty());
+        assert_eq!(vec!["f01"], ids(contract.get_storage_providers_by_region(REGION_ASIA, 0, 10)));
+        assert_eq!(vec!["f02"], ids(contract.get_storage_providers_by_region(REGION_AFRICA, 0, 10)));
+        assert_eq!(vec!["f03"], ids(contract.get_storage_providers_by_region(REGION_OTHER, 0, 10)));
+    }
+
+    #[test]
+    fn get_cheapest_and_largest_providers() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(30.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f04".to_string(), region: REGION_ASIA, power: gib(90.0), price: fil(0.01), ..Default::default() },
+            // 0.1 FIL/TiB/day is far cheaper than 0.1 FIL/GiB/epoch
+            StorageProvider { id: "f05".to_string(), region: REGION_EUROPE, power: gib(5.0), price: fil(0.1), price_unit: PRICE_UNIT_FIL_PER_TIB_PER_DAY, ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let ids = |storage_providers: Vec<StorageProvider>| storage_providers.into_iter().map(|sp| sp.id).collect::<Vec<String>>();
+        assert_eq!(vec!["f05", "f02", "f03"], ids(contract.get_cheapest_providers(REGION_EUROPE, 3)));
+        assert_eq!(vec!["f02", "f03"], ids(contract.get_largest_providers(REGION_EUROPE, 2)));
+        assert_eq!(vec!["f04"], ids(contract.get_cheapest_providers(REGION_ASIA, 10)));
+        assert!(contract.get_largest_providers(REGION_AFRICA, 10).is_empty());
+
+        // changes to price, power and region reorder the indexes
+        contract.patch_provider("f01".to_string(), Some(gib(100.0)), Some(fil(0.05)), None);
+        contract.reassign_regions(vec![("f02".to_string(), REGION_ASIA)]);
+        contract.delete_storage_providers(vec!["f05".to_string()]);
+
+        assert_eq!(vec!["f01", "f03"], ids(contract.get_cheapest_providers(REGION_EUROPE, 10)));
+        assert_eq!(vec!["f01", "f03"], ids(contract.get_largest_providers(REGION_EUROPE, 10)));
+        assert_eq!(vec!["f04", "f02"], ids(contract.get_largest_providers(REGION_ASIA, 10)));
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Storage provider metadata, updated on its own so that metadata changes don't touch price and power
+ */
+
+use crate::*;
+
+pub const MAX_MULTIADDRS: usize = 8;
+pub const MAX_ADDRESS_LEN: usize = 256; // bytes of a multiaddr or peer id
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Default, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderMetadata {
+    multiaddrs: Vec<String>, // at most MAX_MULTIADDRS
+    peer_id: Option<String>,
+    min_piece_size: Option<U128>, // bytes
+    max_piece_size: Option<U128>, // bytes
+    accepts_verified_deals: bool, // FIL+ verified deals
+    retrievals: bool, // serves retrievals
+}
+
+impl ProviderMetadata {
+    fn assert_valid(&self) {
+        require!(self.multiaddrs.len() <= MAX_MULTIADDRS, format!("at most {} multiaddrs are allowed", MAX_MULTIADDRS));
+        for address in self.multiaddrs.iter().chain(self.peer_id.iter()) {
+            require!(address.len() <= MAX_ADDRESS_LEN, format!("address {} is longer than {} bytes", address, MAX_ADDRESS_LEN));
+        }
+        if let (Some(min), Some(max)) = (self.min_piece_size, self.max_piece_size) {
+            require!(min.0 <= max.0, "min_piece_size must not be greater than max_piece_size");
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // replace the metadata of a storage provider, and its label unless None, by its account or a SpManager
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn update_provider_metadata(&mut self, id: String, metadata: ProviderMetadata, label: Option<String>) {
+        self.assert_provider_side(&id);
+        self.assert_not_paused(Feature::Providers);
+        metadata.assert_valid();
+        if let Some(label) = &label {
+            require!(label.len() <= MAX_LABEL_LEN, format!("label of {} is longer than {} bytes", id, MAX_LABEL_LEN));
+        }
+
+        let mut storage_provider = self.storage_providers.get(&id)
+            .unwrap_or_else(|| panic!("storage provider {} not found", id));
+
+        let initial_storage_usage = env::storage_usage();
+        storage_provider.metadata = metadata;
+        if label.is_some() {
+            storage_provider.label = label;
+        }
+        self.touch_provider(&mut storage_provider);
+        self.storage_providers.insert(&id, &storage_provider);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("provider_metadata_updated", json!({
+            "id": id,
+            "metadata": storage_provider.metadata,
+            "label": storage_provider.label,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata {
+            multiaddrs: vec!["/ip4/203.0.113.7/tcp/24001".to_string()],
+            peer_id: Some("12D3KooWExample".to_string()),
+            min_piece_size: Some(gib(0.25)),
+            max_piece_size: Some(gib(32.0)),
+            accepts_verified_deals: true,
+            retrievals: true,
+        }
+    }
+
+    #[test]
+    fn update_provider_metadata() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        // the update is picked up by incremental sync
+        set_context("carol_near", NEAR, 100);
+        contract.update_provider_metadata("f01234".to_string(), metadata(), Some("Acme Storage".to_string()));
+        let result = contract.get_storage_provider("f01234".to_string()).unwrap();
+        assert_eq!(metadata(), result.metadata);
+        assert_eq!(Some("Acme Storage".to_string()), result.label);
+        assert_eq!(fil(0.1), result.price);
+        assert_eq!(100 * NANOSECONDS_PER_SECOND, result.last_updated);
+
+        // price updates keep the metadata
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.2), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        let result = contract.get_storage_provider("f01234".to_string()).unwrap();
+        assert_eq!(metadata(), result.metadata);
+        assert_eq!(fil(0.2), result.price);
+    }
+
+    #[test]
+    #[should_panic(expected = "min_piece_size must not be greater than max_piece_size")]
+    fn update_provider_metadata_with_invalid_piece_sizes() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        let metadata = ProviderMetadata { min_piece_size: Some(gib(64.0)), ..metadata() };
+        contract.update_provider_metadata("f01234".to_string(), metadata, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn update_provider_metadata_by_other_account() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+
+        set_context("bob_near", NEAR, 0);
+        contract.update_provider_metadata("f01234".to_string(), metadata(), None);
+    }
+}
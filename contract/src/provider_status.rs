@@ -0,0 +1,156 @@
+/*
+ * Storage provider lifecycle: a provider stays listed with its history whatever its status, but only Active
+ * providers are indexed and counted in the active counts, power and price aggregates. Retired is final
+ */
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProviderStatus {
+    #[default]
+    Active,    // listed and counted
+    Inactive,  // temporarily out of the market, e.g. under maintenance
+    Suspended, // taken out of the market by an SpManager
+    Retired,   // permanently out of the market, can't change status anymore
+}
+
+impl StorageProvider {
+    // whether the storage provider is counted in the indexes and aggregates
+    pub(crate) fn is_active(&self) -> bool {
+        self.status == ProviderStatus::Active
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the status of the given storage providers, retired providers are left unchanged
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn set_provider_status(&mut self, ids: Vec<String>, status: ProviderStatus) {
+        self.assert_role(Role::SpManager);
+        self.assert_not_paused(Feature::Providers);
+        let initial_storage_usage = env::storage_usage();
+
+        let ids: Vec<String> = ids.iter().map(|id| self.lookup_provider_id(id)).collect();
+        let mut transitions = Vec::new();
+        for id in ids.iter() {
+            let mut storage_provider = match self.storage_providers.get(id) {
+                Some(storage_provider) => storage_provider,
+                None => {
+                    env::log_str(&format!("set_provider_status(): storage provider {} not found", id));
+                    continue;
+                }
+            };
+            if storage_provider.status == ProviderStatus::Retired {
+                env::log_str(&format!("set_provider_status(): storage provider {} is retired", id));
+                continue;
+            }
+            if storage_provider.status == status {
+                continue;
+            }
+
+            let previous = storage_provider.clone();
+            storage_provider.status = status;
+            self.touch_provider(&mut storage_provider);
+            self.storage_providers.insert(id, &storage_provider);
+            self.reindex_provider(Some(&previous), Some(&storage_provider));
+            transitions.push(json!({ "id": id, "from": previous.status }));
+        }
+        self.record_active_history();
+        self.refund_deposit(initial_storage_usage);
+
+        if !transitions.is_empty() {
+            self.emit_event("provider_status_changed", json!({
+                "status": status,
+                "transitions": transitions,
+            }));
+        }
+    }
+
+    // get the storage providers with the given status, paginated
+    pub fn get_providers_by_status(&self, status: ProviderStatus, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        self.storage_providers
+            .values_as_vector()
+            .iter()
+            .filter(|sp| sp.status == status)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn ids(storage_providers: Vec<StorageProvider>) -> Vec<String> {
+        storage_providers.into_iter().map(|sp| sp.id).collect()
+    }
+
+    // three providers in Europe priced 0.1, 0.2 and 0.3 FIL
+    fn providers_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_EUROPE, power: gib(30.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract
+    }
+
+    #[test]
+    fn inactive_providers_leave_the_aggregates() {
+        let mut contract = providers_contract();
+        assert_eq!(3, contract.get_active_per_region().get(REGION_EUROPE));
+
+        contract.set_provider_status(vec!["f01".to_string(), "f04".to_string()], ProviderStatus::Suspended);
+
+        // still listed, but no longer counted
+        assert_eq!(ProviderStatus::Suspended, contract.get_storage_provider("f01".to_string()).unwrap().status);
+        assert_eq!(3, contract.get_storage_providers_count());
+        assert_eq!(2, contract.get_active_per_region().get(REGION_EUROPE));
+        assert_eq!(gib(50.0), contract.get_total_power());
+        assert_eq!(gib(50.0), contract.get_power_per_region().0[&REGION_EUROPE]);
+        assert_eq!(fil(0.2), contract.get_price_summary(None).min);
+        assert_eq!(vec!["f02", "f03"], ids(contract.get_cheapest_providers(REGION_EUROPE, 10)));
+        assert_eq!(vec!["f01"], ids(contract.get_providers_by_status(ProviderStatus::Suspended, 0, 10)));
+
+        // a price update keeps the status
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.05), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(2, contract.get_active_per_region().get(REGION_EUROPE));
+
+        // recomputing agrees with the maintained counts
+        contract.recompute_active_per_region();
+        assert_eq!(2, contract.get_active_per_region().get(REGION_EUROPE));
+        assert_eq!(gib(50.0), contract.get_power_per_region().0[&REGION_EUROPE]);
+
+        contract.set_provider_status(vec!["f01".to_string()], ProviderStatus::Active);
+        assert_eq!(3, contract.get_active_per_region().get(REGION_EUROPE));
+        assert_eq!(vec!["f01", "f02", "f03"], ids(contract.get_cheapest_providers(REGION_EUROPE, 10)));
+    }
+
+    #[test]
+    fn retired_is_final() {
+        let mut contract = providers_contract();
+
+        contract.set_provider_status(vec!["f02".to_string()], ProviderStatus::Retired);
+        contract.set_provider_status(vec!["f02".to_string()], ProviderStatus::Active);
+
+        assert_eq!(ProviderStatus::Retired, contract.get_storage_provider("f02".to_string()).unwrap().status);
+        assert_eq!(2, contract.get_active_per_region().get(REGION_EUROPE));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn set_provider_status_without_role() {
+        let mut contract = providers_contract();
+
+        set_context("bob_near", NEAR, 0);
+        contract.set_provider_status(vec!["f01".to_string()], ProviderStatus::Inactive);
+    }
+}
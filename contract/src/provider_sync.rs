@@ -0,0 +1,192 @@
+/*
+ * Incremental sync of the provider set: the listings are indexed by the block timestamp of their latest change and
+ * the providers deleted or denied by the block timestamp of their removal, so a mirror pages through what changed
+ * since its last sync with a cursor that stays valid while providers change. A provider listed again is taken out
+ * of the removals, and removals older than REMOVED_PROVIDERS_RETENTION are pruned: a mirror syncing less often
+ * than that reloads the whole list
+ */
+
+use crate::*;
+use crate::retention::AUTO_PRUNE_ENTRIES;
+
+// nanoseconds a removal is kept for incremental sync
+pub const REMOVED_PROVIDERS_RETENTION: u64 = 30 * 86_400 * NANOSECONDS_PER_SECOND;
+
+// (block timestamp in nanoseconds, storage provider id)
+type SyncKey = (u64, String);
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderUpdates {
+    storage_providers: Vec<StorageProvider>, // ordered by last_updated, then id
+    next_cursor: Option<SyncKey>,            // (since_ns, start_id) of the next page, None on the last page
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderRemovals {
+    removed: Vec<(String, u64)>,        // (id, removal timestamp in nanoseconds), ordered by removal, then id
+    next_cursor: Option<SyncKey>,       // (since_ns, start_id) of the next page, None on the last page
+}
+
+// the keys of an index from (since_ns, start_id) inclusive, up to limit, with the key following them
+fn sync_page(index: &TreeMap<SyncKey, ()>, since_ns: u64, start_id: Option<String>, limit: u64) -> (Vec<SyncKey>, Option<SyncKey>) {
+    let mut keys: Vec<SyncKey> = index
+        .range((Bound::Included((since_ns, start_id.unwrap_or_default())), Bound::Unbounded))
+        .take(view_limit(limit) + 1)
+        .map(|(key, _)| key)
+        .collect();
+    let next_cursor = if keys.len() > view_limit(limit) { keys.pop() } else { None };
+    (keys, next_cursor)
+}
+
+impl FilMarket {
+    // stamp a listing changed by the current call with the block timestamp, moving it in the sync index
+    // note: called before the listing is stored
+    pub(crate) fn touch_provider(&mut self, storage_provider: &mut StorageProvider) {
+        self.updated_index.remove(&(storage_provider.last_updated, storage_provider.id.clone()));
+        storage_provider.last_updated = env::block_timestamp();
+        self.updated_index.insert(&(storage_provider.last_updated, storage_provider.id.clone()), &());
+    }
+
+    // record the removal of a storage provider from the provider set
+    pub(crate) fn record_provider_removed(&mut self, storage_provider: &StorageProvider) {
+        self.prune_removed_providers();
+
+        let now = env::block_timestamp();
+        self.updated_index.remove(&(storage_provider.last_updated, storage_provider.id.clone()));
+        self.removed_providers.insert(&storage_provider.id, &now);
+        self.removed_index.insert(&(now, storage_provider.id.clone()), &());
+    }
+
+    // forget the removal of a storage provider listed again
+    pub(crate) fn clear_provider_removed(&mut self, id: &String) {
+        if self.removed_providers.is_empty() {
+            return;
+        }
+        if let Some(removed_at) = self.removed_providers.remove(id) {
+            self.removed_index.remove(&(removed_at, id.clone()));
+        }
+    }
+
+    // drop up to AUTO_PRUNE_ENTRIES removals older than REMOVED_PROVIDERS_RETENTION
+    fn prune_removed_providers(&mut self) {
+        let cutoff = env::block_timestamp().saturating_sub(REMOVED_PROVIDERS_RETENTION);
+        let expired: Vec<SyncKey> = self.removed_index
+            .iter()
+            .take(AUTO_PRUNE_ENTRIES as usize)
+            .take_while(|((removed_at, _), _)| *removed_at < cutoff)
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in expired.iter() {
+            self.removed_index.remove(key);
+            self.removed_providers.remove(&key.1);
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the storage providers changed at or after since_ns (block timestamp in nanoseconds), in order of change,
+    // starting at start_id among the ones changed at since_ns; the next page starts at next_cursor
+    // the ones deleted or denied since then are returned by get_providers_removed_since
+    pub fn get_providers_updated_since(&self, since_ns: u64, start_id: Option<String>, limit: u64) -> ProviderUpdates {
+        let (keys, next_cursor) = sync_page(&self.updated_index, since_ns, start_id, limit);
+
+        ProviderUpdates {
+            storage_providers: keys.iter().filter_map(|(_, id)| self.storage_providers.get(id)).collect(),
+            next_cursor,
+        }
+    }
+
+    // get the storage providers deleted or denied at or after since_ns (block timestamp in nanoseconds), in order of
+    // removal, starting at start_id among the ones removed at since_ns; the next page starts at next_cursor
+    pub fn get_providers_removed_since(&self, since_ns: u64, start_id: Option<String>, limit: u64) -> ProviderRemovals {
+        let (keys, next_cursor) = sync_page(&self.removed_index, since_ns, start_id, limit);
+
+        ProviderRemovals {
+            removed: keys.into_iter().map(|(removed_at, id)| (id, removed_at)).collect(),
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn ns(seconds: u64) -> u64 {
+        seconds * NANOSECONDS_PER_SECOND
+    }
+
+    fn provider(id: &str) -> StorageProvider {
+        StorageProvider { id: id.to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }
+    }
+
+    fn updated_ids(contract: &FilMarket, since_ns: u64, start_id: Option<String>, limit: u64) -> Vec<String> {
+        contract.get_providers_updated_since(since_ns, start_id, limit).storage_providers.into_iter().map(|sp| sp.id).collect()
+    }
+
+    #[test]
+    fn removed_providers_since() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.update_storage_providers(vec![provider("f01"), provider("f02"), provider("f03")], None);
+
+        set_context("carol_near", NEAR, 2_000);
+        contract.delete_storage_providers(vec!["f01".to_string()]);
+        set_context("carol_near", NEAR, 3_000);
+        contract.deny_provider("f02".to_string());
+
+        assert_eq!(
+            vec![("f01".to_string(), ns(2_000)), ("f02".to_string(), ns(3_000))],
+            contract.get_providers_removed_since(ns(1_500), None, 10).removed
+        );
+        let page = contract.get_providers_removed_since(0, None, 1);
+        assert_eq!((vec![("f01".to_string(), ns(2_000))], Some((ns(3_000), "f02".to_string()))), (page.removed, page.next_cursor));
+        assert_eq!(vec![("f02".to_string(), ns(3_000))], contract.get_providers_removed_since(ns(3_000), Some("f02".to_string()), 10).removed);
+
+        // listed again, the providers show up as updated instead
+        set_context("carol_near", NEAR, 4_000);
+        contract.update_storage_providers(vec![provider("f01")], None);
+        contract.allow_provider("f02".to_string());
+        assert!(contract.get_providers_removed_since(0, None, 10).removed.is_empty());
+        assert_eq!(vec!["f01", "f02"], updated_ids(&contract, ns(4_000), None, 10));
+    }
+
+    #[test]
+    fn updated_providers_by_cursor() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.update_storage_providers(vec![provider("f01"), provider("f02"), provider("f03")], None);
+
+        set_context("carol_near", NEAR, 2_000);
+        contract.update_storage_providers(vec![provider("f02"), provider("f04")], None);
+        contract.patch_provider("f03".to_string(), None, Some(fil(0.25)), None);
+        assert_eq!(vec!["f01", "f02", "f03", "f04"], updated_ids(&contract, ns(1_000), None, 10));
+        assert!(updated_ids(&contract, ns(2_000) + 1, None, 10).is_empty());
+
+        let page = contract.get_providers_updated_since(ns(1_500), None, 2);
+        assert_eq!(ns(2_000), page.storage_providers[0].last_updated);
+        assert_eq!(Some((ns(2_000), "f04".to_string())), page.next_cursor);
+
+        // removing a provider of a page read doesn't shift the next one
+        contract.delete_storage_providers(vec!["f02".to_string()]);
+        assert_eq!(vec!["f04"], updated_ids(&contract, ns(2_000), Some("f04".to_string()), 10));
+    }
+
+    #[test]
+    fn removals_are_pruned_after_retention() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.update_storage_providers(vec![provider("f01"), provider("f02")], None);
+        contract.delete_storage_providers(vec!["f01".to_string()]);
+
+        set_context("carol_near", NEAR, 1_000 + REMOVED_PROVIDERS_RETENTION / NANOSECONDS_PER_SECOND + 1);
+        contract.delete_storage_providers(vec!["f02".to_string()]);
+        let removed: Vec<String> = contract.get_providers_removed_since(0, None, 10).removed.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(vec!["f02"], removed);
+    }
+}
@@ -0,0 +1,210 @@
+/*
+ * Provider tiers: Bronze, Silver or Gold, from configurable thresholds on power, days since the provider was
+ * first listed and the fault rate of its deals. The tier is stored with the listing and recomputed on every
+ * write to it; as listings age without being written, refresh_provider_tiers brings the stored tiers up to date
+ */
+
+use crate::*;
+
+const NANOSECONDS_PER_DAY: u64 = 86_400 * NANOSECONDS_PER_SECOND;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProviderTier {
+    #[default]
+    Bronze, // every listed provider
+    Silver,
+    Gold,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TierThresholds {
+    min_power: U128,       // bytes
+    min_history_days: u64, // days since the provider was first listed
+    max_fault_bps: u32,    // faults over deals in basis points, providers without deals pass
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TierConfig {
+    silver: TierThresholds,
+    gold: TierThresholds,
+}
+
+impl Default for TierConfig {
+    fn default() -> Self {
+        TierConfig {
+            silver: TierThresholds { min_power: U128(BYTES_PER_TIB), min_history_days: 30, max_fault_bps: 1_000 },
+            gold: TierThresholds { min_power: U128(100 * BYTES_PER_TIB), min_history_days: 180, max_fault_bps: 200 },
+        }
+    }
+}
+
+impl TierThresholds {
+    fn is_met(&self, power: u128, history_days: u64, fault_bps: Option<u32>) -> bool {
+        power >= self.min_power.0
+            && history_days >= self.min_history_days
+            && fault_bps.is_none_or(|fault_bps| fault_bps <= self.max_fault_bps)
+    }
+}
+
+impl FilMarket {
+    // the tier a storage provider qualifies for now
+    // note: providers listed before the listing time was tracked have no history
+    pub(crate) fn compute_tier(&self, sp: &StorageProvider) -> ProviderTier {
+        let history_days = if sp.listed_at == 0 { 0 } else { env::block_timestamp().saturating_sub(sp.listed_at) / NANOSECONDS_PER_DAY };
+        let fault_bps = self.reputations.get(&sp.id).and_then(|reputation| reputation.fault_bps());
+
+        if self.tier_config.gold.is_met(sp.power.0, history_days, fault_bps) {
+            ProviderTier::Gold
+        } else if self.tier_config.silver.is_met(sp.power.0, history_days, fault_bps) {
+            ProviderTier::Silver
+        } else {
+            ProviderTier::Bronze
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the thresholds of the Silver and Gold tiers, the stored tiers follow through refresh_provider_tiers
+    pub fn set_tier_config(&mut self, tier_config: TierConfig) {
+        self.assert_config_authority();
+
+        self.tier_config = tier_config;
+        self.emit_event("tier_config_set", json!({ "tier_config": self.tier_config }));
+    }
+
+    // get the thresholds of the Silver and Gold tiers
+    pub fn get_tier_config(&self) -> TierConfig {
+        self.tier_config.clone()
+    }
+
+    // recompute the stored tier of up to limit storage providers starting at from_index, returns the number changed
+    pub fn refresh_provider_tiers(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_not_paused(Feature::Providers);
+
+        let storage_providers: Vec<StorageProvider> = self.storage_providers
+            .values_as_vector()
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect();
+
+        let mut changed: Vec<(String, ProviderTier)> = Vec::new();
+        for mut storage_provider in storage_providers.into_iter() {
+            let tier = self.compute_tier(&storage_provider);
+            if tier != storage_provider.tier {
+                storage_provider.tier = tier;
+                self.touch_provider(&mut storage_provider);
+                self.storage_providers.insert(&storage_provider.id, &storage_provider);
+                changed.push((storage_provider.id, tier));
+            }
+        }
+
+        if !changed.is_empty() {
+            self.emit_event("provider_tiers_changed", json!({ "tiers": changed }));
+        }
+        changed.len() as u64
+    }
+
+    // get the Active storage providers of at least min_tier, paginated
+    pub fn get_providers_by_tier(&self, min_tier: ProviderTier, from_index: u64, limit: u64) -> Vec<StorageProvider> {
+        self.storage_providers
+            .values_as_vector()
+            .iter()
+            .filter(|sp| sp.is_active() && sp.tier >= min_tier)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+
+    // get the min, max and mean price over the Active storage providers of at least min_tier, optionally of a region
+    pub fn get_price_summary_by_tier(&self, min_tier: ProviderTier, region: Option<u8>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.tier >= min_tier && region.is_none_or(|region| sp.region == region))
+                .map(|sp| sp.price.0)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    const DAY: u64 = NANOSECONDS_PER_DAY / NANOSECONDS_PER_SECOND;
+
+    fn thresholds(min_power: U128, min_history_days: u64, max_fault_bps: u32) -> TierThresholds {
+        TierThresholds { min_power, min_history_days, max_fault_bps }
+    }
+
+    fn tiers(contract: &FilMarket) -> Vec<ProviderTier> {
+        ["f01", "f02", "f03"].iter().map(|id| contract.get_storage_provider(id.to_string()).unwrap().tier).collect()
+    }
+
+    #[test]
+    fn tiers_from_power_history_and_faults() {
+        set_context("carol_near", NEAR, 100 * DAY);
+        let mut contract = FilMarket::new();
+        contract.set_tier_config(TierConfig {
+            silver: thresholds(gib(10.0), 0, 5_000),
+            gold: thresholds(gib(20.0), 30, 1_000),
+        });
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(5.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(vec![ProviderTier::Bronze, ProviderTier::Silver, ProviderTier::Silver], tiers(&contract));
+
+        // a month later the history qualifies for Gold, unless the deals fail too often
+        set_context("carol_near", NEAR, 130 * DAY);
+        contract.submit_reputation_events(vec![
+            ("f03".to_string(), ReputationEvent::SuccessfulDeal),
+            ("f03".to_string(), ReputationEvent::Fault),
+        ]);
+        assert_eq!(1, contract.refresh_provider_tiers(0, 10));
+        assert_eq!(vec![ProviderTier::Bronze, ProviderTier::Gold, ProviderTier::Silver], tiers(&contract));
+        // tier and score changes are picked up by incremental sync
+        for id in ["f02", "f03"] {
+            assert_eq!(130 * NANOSECONDS_PER_DAY, contract.get_storage_provider(id.to_string()).unwrap().last_updated);
+        }
+        assert_eq!(100 * NANOSECONDS_PER_DAY, contract.get_storage_provider("f01".to_string()).unwrap().last_updated);
+
+        let gold: Vec<String> = contract.get_providers_by_tier(ProviderTier::Gold, 0, 10).into_iter().map(|sp| sp.id).collect();
+        assert_eq!(vec!["f02"], gold);
+        let summary = contract.get_price_summary_by_tier(ProviderTier::Silver, None);
+        assert_eq!((fil(0.2), fil(0.3), 2), (summary.min, summary.max, summary.count));
+        assert_eq!(1, contract.get_price_summary_by_tier(ProviderTier::Silver, Some(REGION_ASIA)).count);
+
+        // the tier follows a power update
+        contract.patch_provider("f02".to_string(), Some(gib(1.0)), None, None);
+        assert_eq!(ProviderTier::Bronze, tiers(&contract)[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn refresh_provider_tiers_while_paused() {
+        set_context("carol_near", NEAR, 100 * DAY);
+        let mut contract = FilMarket::new();
+        contract.pause_feature(Feature::Providers);
+
+        contract.refresh_provider_tiers(0, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn set_tier_config_by_other_account() {
+        set_context("carol_near", NEAR, 100 * DAY);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", 0, 0);
+        contract.set_tier_config(TierConfig::default());
+    }
+}
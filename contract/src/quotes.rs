@@ -0,0 +1,94 @@
+/*
+ * Storage deal cost quotes from the latest price entry, so frontends and contracts don't have to redo the unit
+ * math: the cost of storing size_bytes for duration_days at the region price, in FIL and in USD
+ */
+
+use crate::*;
+use crate::conversions::to_usd;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageQuote {
+    price: U128,            // attoFIL/GiB/epoch quoted
+    cost: U128,             // attoFIL, rounded up
+    cost_usd: Option<U128>, // USD scaled by 10^18, None without a FIL price
+    timestamp: u64,         // of the price entry quoted
+}
+
+// the cost in attoFIL of storing size_bytes for duration_days at price attoFIL/GiB/epoch, rounded up
+fn storage_cost(price: u128, size_bytes: u128, duration_days: u32) -> u128 {
+    price
+        .checked_mul(EPOCHS_PER_DAY * duration_days as u128)
+        .and_then(|cost| cost.checked_mul(size_bytes))
+        .expect("storage cost overflows u128")
+        .div_ceil(BYTES_PER_GIB)
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // quote storing size_bytes for duration_days at the latest price of a region, or the global price for None,
+    // verified for the FIL+ verified deal price
+    // None without a price entry or without a price for the region
+    pub fn quote_storage(&self, region: Option<u8>, size_bytes: U128, duration_days: u32, verified: bool) -> Option<StorageQuote> {
+        let ppr = self.price_per_region.get(&self.latest_timestamp)?;
+        let price = match (region, verified) {
+            (None, false) => ppr.global,
+            (None, true) => ppr.verified_global?,
+            (Some(region), false) => *ppr.prices.get(&region)?,
+            (Some(region), true) => *ppr.verified_prices.get(&region)?,
+        };
+
+        let cost = storage_cost(price.0, size_bytes.0, duration_days);
+        Some(StorageQuote {
+            price,
+            cost: U128(cost),
+            cost_usd: if ppr.fil_price.0 == 0 { None } else { Some(U128(to_usd(cost, ppr.fil_price.0))) },
+            timestamp: ppr.timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    #[test]
+    fn storage_cost_rounds_up() {
+        assert_eq!(10_000_000_000 * 2_880 * 30 * 1_024, storage_cost(10_000_000_000, BYTES_PER_TIB, 30));
+        assert_eq!(1, storage_cost(1, 1, 1));
+        assert_eq!(0, storage_cost(10_000_000_000, BYTES_PER_TIB, 0));
+    }
+
+    #[test]
+    fn quote_storage() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        assert_eq!(None, contract.quote_storage(None, U128(BYTES_PER_TIB), 30, false));
+
+        contract.set_price_per_region(PricePerRegion {
+            prices: vec![(REGION_EUROPE, fil(0.00000001))].into_iter().collect(),
+            global: fil(0.00000002),
+            fil_price: fil(5.0),
+            timestamp: 10,
+            verified_prices: vec![(REGION_EUROPE, fil(0.000000005))].into_iter().collect(),
+            ..Default::default()
+        });
+
+        // 0.884736 FIL for a TiB over 30 days, at 5 USD/FIL
+        let quote = contract.quote_storage(Some(REGION_EUROPE), U128(BYTES_PER_TIB), 30, false).unwrap();
+        assert_eq!(fil(0.00000001), quote.price);
+        assert_eq!(fil(0.884736), quote.cost);
+        assert_eq!(Some(fil(4.42368)), quote.cost_usd);
+        assert_eq!(10, quote.timestamp);
+
+        let quote = contract.quote_storage(Some(REGION_EUROPE), U128(BYTES_PER_TIB), 30, true).unwrap();
+        assert_eq!(fil(0.442368), quote.cost);
+
+        let quote = contract.quote_storage(None, U128(BYTES_PER_TIB), 30, false).unwrap();
+        assert_eq!(fil(1.769472), quote.cost);
+
+        assert_eq!(None, contract.quote_storage(None, U128(BYTES_PER_TIB), 30, true));
+        assert_eq!(None, contract.quote_storage(Some(REGION_ASIA), U128(BYTES_PER_TIB), 30, false));
+    }
+}
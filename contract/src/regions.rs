@@ -0,0 +1,197 @@
+/*
+ * Region registry: storage providers, prices and counts refer to regions by id, and regions are
+ * added or renamed by the governance account, or the owner when governance is off, without redeploying the contract
+ */
+
+use crate::*;
+
+pub const MAX_REGION_NAME_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Region {
+    id: u8,
+    name: String,
+}
+
+// the numbers of a region alongside its name
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RegionSummary {
+    id: u8,
+    name: String,
+    active: u32,          // active storage providers
+    price: Option<U128>,  // attoFIL, from the latest price entry, None if it has no price for the region
+    providers: u64,       // Active storage providers in the region index
+    power: U128,          // bytes, summed over the Active storage providers
+}
+
+// the regions registered at initialization
+pub(crate) fn default_regions() -> TreeMap<u8, String> {
+    let mut regions = TreeMap::new(StorageKey::Regions);
+    for (id, name) in [
+        (REGION_NORTH_AMERICA, "North America"),
+        (REGION_EUROPE, "Europe"),
+        (REGION_ASIA, "Asia"),
+        (REGION_OTHER, "Other"),
+        (REGION_SOUTH_AMERICA, "South America"),
+        (REGION_AFRICA, "Africa"),
+    ].iter() {
+        regions.insert(id, &name.to_string());
+    }
+
+    regions
+}
+
+fn assert_valid_region_name(name: &str) {
+    require!(!name.is_empty(), "region name must not be empty");
+    require!(name.len() <= MAX_REGION_NAME_LEN, format!("region name is longer than {} bytes", MAX_REGION_NAME_LEN));
+}
+
+impl FilMarket {
+    // whether region is a registered region id
+    pub(crate) fn is_valid_region(&self, region: u8) -> bool {
+        self.regions.get(&region).is_some()
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // register a new region, id 0 is reserved for providers without a region
+    pub fn add_region(&mut self, id: u8, name: String) {
+        self.assert_config_authority();
+
+        require!(id != 0, "region id 0 is reserved");
+        require!(!self.is_valid_region(id), format!("region {} already exists", id));
+        assert_valid_region_name(&name);
+
+        self.regions.insert(&id, &name);
+        self.emit_event("region_added", json!({ "id": id, "name": name }));
+    }
+
+    // change the name of a registered region
+    pub fn rename_region(&mut self, id: u8, name: String) {
+        self.assert_config_authority();
+
+        require!(self.is_valid_region(id), format!("invalid region {}", id));
+        assert_valid_region_name(&name);
+
+        self.regions.insert(&id, &name);
+        self.emit_event("region_renamed", json!({ "id": id, "name": name }));
+    }
+
+    // get the registered regions, ordered by id
+    pub fn get_regions(&self) -> Vec<Region> {
+        self.regions.iter().map(|(id, name)| Region { id, name }).collect()
+    }
+
+    // get the name, active count, latest price, provider count and power of every registered region
+    pub fn get_region_summaries(&self) -> Vec<RegionSummary> {
+        let latest = self.price_per_region.get(&self.latest_timestamp).unwrap_or_default();
+
+        self.regions
+            .iter()
+            .map(|(id, name)| RegionSummary {
+                id,
+                name,
+                active: self.active_per_region.get(id),
+                price: latest.prices.get(&id).copied(),
+                providers: self.region_index.get(&id).map(|ids| ids.len()).unwrap_or(0),
+                power: self.power_per_region.0.get(&id).copied().unwrap_or(U128(0)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    const REGION_OCEANIA: u8 = 7;
+
+    #[test]
+    fn add_and_rename_region() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        assert_eq!(6, contract.get_regions().len());
+        assert_eq!("Europe", contract.get_regions()[1].name);
+
+        contract.add_region(REGION_OCEANIA, "Oceania".to_string());
+        contract.rename_region(REGION_OTHER, "Rest of the world".to_string());
+
+        let regions = contract.get_regions();
+        assert_eq!(7, regions.len());
+        assert_eq!(REGION_OCEANIA, regions[6].id);
+        assert_eq!("Rest of the world", regions[3].name);
+
+        // the new region is usable right away
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_OCEANIA, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        let mut prices = BTreeMap::new();
+        prices.insert(REGION_OCEANIA, fil(0.0003));
+        contract.set_price_per_region(PricePerRegion { prices, global: fil(0.0002), timestamp: 10, ..Default::default() });
+        contract.recompute_active_per_region();
+
+        let summary = contract.get_region_summaries().pop().unwrap();
+        assert_eq!("Oceania", summary.name);
+        assert_eq!(1, summary.active);
+        assert_eq!(Some(fil(0.0003)), summary.price);
+        assert_eq!(1, summary.providers);
+        assert_eq!(gib(10.0), summary.power);
+
+        let summary = &contract.get_region_summaries()[0];
+        assert_eq!(None, summary.price);
+        assert_eq!(0, summary.providers);
+    }
+
+    #[test]
+    #[should_panic(expected = "region 2 already exists")]
+    fn add_existing_region() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.add_region(REGION_EUROPE, "Europe".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid region 7")]
+    fn set_price_for_unknown_region() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        let mut prices = BTreeMap::new();
+        prices.insert(REGION_OCEANIA, fil(0.0003));
+        contract.set_price_per_region(PricePerRegion { prices, timestamp: 10, ..Default::default() });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn add_region_not_owner() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 0);
+        contract.add_region(REGION_OCEANIA, "Oceania".to_string());
+    }
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE")]
+    fn add_region_by_owner_under_governance() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.set_governance(Some(account("dao_near")));
+
+        contract.add_region(REGION_OCEANIA, "Oceania".to_string());
+    }
+
+    #[test]
+    fn governance_renames_region() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.set_governance(Some(account("dao_near")));
+
+        set_context("dao_near", NEAR, 0);
+        contract.add_region(REGION_OCEANIA, "Oceania".to_string());
+        contract.rename_region(REGION_OCEANIA, "Pacific".to_string());
+        assert_eq!("Pacific", contract.get_regions()[6].name);
+    }
+}
@@ -0,0 +1,220 @@
+/*
+ * Storage provider reputation: counters of successful deals, faults, uptime reports and client ratings,
+ * summarized in a 0-100 score kept in StorageProvider.reputation. The proving faults and uptime checks reported
+ * through report_provider_sla are counted here too, so a fault is reported once, either way
+ */
+
+use crate::*;
+
+// score of a provider without any reputation event
+pub const NEUTRAL_REPUTATION: u32 = 50;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Default, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Reputation {
+    successful_deals: u32,
+    faults: u32,
+    uptime_reports: u32,
+    uptime_online: u32, // uptime reports where the provider was online
+    rating_count: u32,
+    rating_sum: u32, // sum of the 1-5 star ratings
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ReputationEvent {
+    SuccessfulDeal,
+    Fault,
+    Uptime { online: bool },
+    Rating { stars: u8 }, // 1 to 5
+}
+
+impl Reputation {
+    fn apply(&mut self, event: ReputationEvent) {
+        match event {
+            ReputationEvent::SuccessfulDeal => self.successful_deals = self.successful_deals.saturating_add(1),
+            ReputationEvent::Fault => self.faults = self.faults.saturating_add(1),
+            ReputationEvent::Uptime { online } => {
+                self.uptime_reports = self.uptime_reports.saturating_add(1);
+                if online {
+                    self.uptime_online = self.uptime_online.saturating_add(1);
+                }
+            }
+            ReputationEvent::Rating { stars } => {
+                require!((1..=5).contains(&stars), "rating must be between 1 and 5 stars");
+                self.rating_count = self.rating_count.saturating_add(1);
+                self.rating_sum = self.rating_sum.saturating_add(stars as u32);
+            }
+        }
+    }
+
+    // count the proving faults and the uptime check of an SLA report
+    fn apply_sla(&mut self, faults: u32, online: Option<bool>) {
+        self.faults = self.faults.saturating_add(faults);
+        if let Some(online) = online {
+            self.apply(ReputationEvent::Uptime { online });
+        }
+    }
+
+    // the mean of the deal success rate, the uptime rate and the rating scaled to 0-100,
+    // each part counting as NEUTRAL_REPUTATION until it has data
+    pub(crate) fn score(&self) -> u32 {
+        fn percent(part: u32, total: u32) -> u64 {
+            if total == 0 {
+                NEUTRAL_REPUTATION as u64
+            } else {
+                100 * part as u64 / total as u64
+            }
+        }
+
+        let deals = percent(self.successful_deals, self.successful_deals.saturating_add(self.faults));
+        let uptime = percent(self.uptime_online, self.uptime_reports);
+        let rating = if self.rating_count == 0 {
+            NEUTRAL_REPUTATION as u64
+        } else {
+            // map the mean of 1-5 stars to 0-100
+            100 * (self.rating_sum as u64 - self.rating_count as u64) / (4 * self.rating_count as u64)
+        };
+
+        ((deals + uptime + rating) / 3) as u32
+    }
+
+    // faults over deals in basis points, None without deals
+    pub(crate) fn fault_bps(&self) -> Option<u32> {
+        let deals = self.successful_deals as u64 + self.faults as u64;
+        (10_000 * self.faults as u64).checked_div(deals).map(|bps| bps as u32)
+    }
+}
+
+impl FilMarket {
+    // the reputation score of a storage provider, NEUTRAL_REPUTATION without any event
+    pub(crate) fn reputation_score(&self, id: &String) -> u32 {
+        self.reputations.get(id).map_or(NEUTRAL_REPUTATION, |reputation| reputation.score())
+    }
+
+    // apply reputation events to their storage providers and refresh the score and tier of each listing
+    pub(crate) fn internal_record_reputation(&mut self, events: &[(String, ReputationEvent)]) {
+        for (id, event) in events.iter() {
+            self.update_reputation(id, |reputation| reputation.apply(*event));
+        }
+    }
+
+    // count the proving faults and the uptime check of an SLA report in the reputation of its storage provider
+    pub(crate) fn record_sla_reputation(&mut self, id: &String, faults: u32, online: Option<bool>) {
+        if faults > 0 || online.is_some() {
+            self.update_reputation(id, |reputation| reputation.apply_sla(faults, online));
+        }
+    }
+
+    // update the reputation of a storage provider and refresh the score and tier of its listing
+    fn update_reputation<F: FnOnce(&mut Reputation)>(&mut self, id: &String, update: F) {
+        let mut storage_provider = self.storage_providers.get(id)
+            .unwrap_or_else(|| panic!("storage provider {} not found", id));
+
+        let mut reputation = self.reputations.get(id).unwrap_or_default();
+        update(&mut reputation);
+        self.reputations.insert(id, &reputation);
+
+        let (score, tier) = (reputation.score(), self.compute_tier(&storage_provider));
+        if (score, tier) != (storage_provider.reputation, storage_provider.tier) {
+            storage_provider.reputation = score;
+            storage_provider.tier = tier;
+            self.touch_provider(&mut storage_provider);
+            self.storage_providers.insert(id, &storage_provider);
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // record reputation events as (storage provider id, event)
+    pub fn submit_reputation_events(&mut self, events: Vec<(String, ReputationEvent)>) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_not_paused(Feature::Providers);
+        require!(!events.is_empty(), "no reputation events to submit");
+
+        self.internal_record_reputation(&events);
+        self.emit_event("reputation_events_submitted", json!({ "events": events }));
+    }
+
+    // get the reputation counters of a storage provider, None before its first event
+    pub fn get_reputation(&self, id: String) -> Option<Reputation> {
+        self.reputations.get(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn listed_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+        contract
+    }
+
+    #[test]
+    fn reputation_score() {
+        let mut reputation = Reputation::default();
+        assert_eq!(NEUTRAL_REPUTATION, reputation.score());
+
+        reputation.apply(ReputationEvent::SuccessfulDeal);
+        reputation.apply(ReputationEvent::SuccessfulDeal);
+        reputation.apply(ReputationEvent::SuccessfulDeal);
+        reputation.apply(ReputationEvent::Fault);
+        // deals 75, uptime and rating neutral
+        assert_eq!(58, reputation.score());
+
+        reputation.apply(ReputationEvent::Uptime { online: true });
+        reputation.apply(ReputationEvent::Rating { stars: 5 });
+        reputation.apply(ReputationEvent::Rating { stars: 3 });
+        // deals 75, uptime 100, rating 75
+        assert_eq!(83, reputation.score());
+    }
+
+    #[test]
+    fn submit_reputation_events() {
+        let mut contract = listed_contract();
+        assert_eq!(NEUTRAL_REPUTATION, contract.get_storage_providers()[0].reputation);
+        assert_eq!(None, contract.get_reputation("f01".to_string()));
+
+        contract.submit_reputation_events(vec![
+            ("f01".to_string(), ReputationEvent::Fault),
+            ("f01".to_string(), ReputationEvent::Uptime { online: false }),
+        ]);
+        assert_eq!(1, contract.get_reputation("f01".to_string()).unwrap().faults);
+        assert_eq!(16, contract.get_storage_providers()[0].reputation);
+
+        // updating the listing keeps the score
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.1), reputation: 100, ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(16, contract.get_storage_providers()[0].reputation);
+
+        // price views can leave out providers below a reputation
+        assert_eq!(fil(0.2), contract.get_price_summary(None).mean);
+        assert_eq!(fil(0.3), contract.get_price_summary(Some(NEUTRAL_REPUTATION)).mean);
+        assert_eq!(1, contract.get_providers_by_price_range(fil(0.0), fil(1.0), 0, 10, Some(20)).len());
+    }
+
+    #[test]
+    #[should_panic(expected = "rating must be between 1 and 5 stars")]
+    fn submit_invalid_rating() {
+        let mut contract = listed_contract();
+        contract.submit_reputation_events(vec![("f01".to_string(), ReputationEvent::Rating { stars: 6 })]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn submit_reputation_events_without_role() {
+        let mut contract = listed_contract();
+
+        set_context("bob_near", NEAR, 0);
+        contract.submit_reputation_events(vec![("f01".to_string(), ReputationEvent::SuccessfulDeal)]);
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * Retention of the price history: entries older than the retention period set by the owner are pruned a few at a
+ * time on every price write, and by anyone through prune_price_history. The latest entry is always kept, so a feed
+ * stalled for longer than the retention still serves its last price
+ */
+
+use crate::*;
+
+// expired entries pruned by every price write
+pub(crate) const AUTO_PRUNE_ENTRIES: u64 = 2;
+// expired entries pruned by a prune_price_history call at most, each removal rebalances the price tree
+pub const MAX_PRUNE_ENTRIES: u64 = 25;
+
+impl FilMarket {
+    // delete up to limit entries older than cutoff_ts, oldest first, returns their timestamps
+    pub(crate) fn internal_delete_prices_before(&mut self, cutoff_ts: u64, limit: u64) -> Vec<u64> {
+        let timestamps: Vec<u64> = self.price_per_region
+            .iter()
+            .map(|(timestamp, _)| timestamp)
+            .take_while(|timestamp| *timestamp < cutoff_ts)
+            .take(limit as usize)
+            .collect();
+
+        for timestamp in timestamps.iter() {
+            self.price_per_region.remove(timestamp);
+        }
+
+        // only changes when the latest entry itself was pruned
+        if timestamps.contains(&self.latest_timestamp) {
+            self.latest_timestamp = self.price_per_region.max().unwrap_or(0);
+        }
+
+        if !timestamps.is_empty() {
+            self.emit_event("price_per_region_deleted", json!({ "timestamps": timestamps }));
+        }
+
+        timestamps
+    }
+
+    // the timestamp before which entries expire, None while the history is kept forever
+    pub(crate) fn retention_cutoff(&self) -> Option<u64> {
+        if self.price_retention == 0 {
+            return None;
+        }
+
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        Some(now.saturating_sub(self.price_retention))
+    }
+
+    // the timestamp before which expired entries are pruned, capped at the latest entry so it is never pruned
+    fn prune_cutoff(&self) -> Option<u64> {
+        self.retention_cutoff().map(|cutoff_ts| cutoff_ts.min(self.latest_timestamp))
+    }
+
+    // prune a few expired entries, called on every price write
+    pub(crate) fn auto_prune_price_history(&mut self) {
+        if let Some(cutoff_ts) = self.prune_cutoff() {
+            self.internal_delete_prices_before(cutoff_ts, AUTO_PRUNE_ENTRIES);
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // keep price entries for retention_seconds, 0 keeps the whole history
+    pub fn set_price_retention(&mut self, retention_seconds: u64) {
+        self.assert_config_authority();
+
+        self.price_retention = retention_seconds;
+        self.emit_event("price_retention_set", json!({ "retention_seconds": retention_seconds }));
+    }
+
+    // get how many seconds price entries are kept, 0 when the whole history is kept
+    pub fn get_price_retention(&self) -> u64 {
+        self.price_retention
+    }
+
+    // delete up to max_entries_per_call (clamped to MAX_PRUNE_ENTRIES) expired price entries, returns the number
+    // of entries removed, call again until it returns 0
+    pub fn prune_price_history(&mut self, max_entries_per_call: u64) -> u64 {
+        let cutoff_ts = self.prune_cutoff().expect("no price retention is set");
+
+        let removed = self.internal_delete_prices_before(cutoff_ts, max_entries_per_call.min(MAX_PRUNE_ENTRIES));
+        env::log_str(&format!("prune_price_history(): cutoff {} entries {}", cutoff_ts, removed.len()));
+        removed.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    fn timestamps(contract: &FilMarket) -> Vec<u64> {
+        contract.get_price_per_region_list().iter().map(|ppr| ppr.timestamp).collect()
+    }
+
+    #[test]
+    fn prune_price_history() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        for timestamp in [10, 20, 30, 40, 50, 60] {
+            contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp, ..Default::default() });
+        }
+
+        contract.set_price_retention(65);
+        assert_eq!(65, contract.get_price_retention());
+
+        // anyone can prune the entries older than 35
+        set_context("bob_near", NEAR, 100);
+        assert_eq!(2, contract.prune_price_history(2));
+        assert_eq!(1, contract.prune_price_history(2));
+        assert_eq!(0, contract.prune_price_history(2));
+        assert_eq!(vec![40, 50, 60], timestamps(&contract));
+    }
+
+    #[test]
+    fn price_writes_prune_expired_entries() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        for timestamp in [10, 20, 30, 40] {
+            contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp, ..Default::default() });
+        }
+
+        contract.set_price_retention(75);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 100, ..Default::default() });
+        assert_eq!(vec![30, 40, 100], timestamps(&contract));
+    }
+
+    #[test]
+    fn latest_entry_outlives_retention() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 10, ..Default::default() });
+        contract.set_price_retention(50);
+
+        // the feed stalled past the retention, the only entry is kept
+        set_context("bob_near", NEAR, 1_000);
+        assert_eq!(0, contract.prune_price_history(10));
+        assert_eq!(vec![10], timestamps(&contract));
+        assert_eq!(fil(0.1), contract.get_latest_price_per_region().global);
+
+        // the next write prunes it once a newer entry is in
+        set_context("carol_near", NEAR, 1_000);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.2), timestamp: 990, ..Default::default() });
+        contract.set_price_per_region(PricePerRegion { global: fil(0.2), timestamp: 995, ..Default::default() });
+        assert_eq!(vec![990, 995], timestamps(&contract));
+    }
+
+    #[test]
+    #[should_panic(expected = "no price retention is set")]
+    fn prune_price_history_without_retention() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        contract.prune_price_history(10);
+    }
+}
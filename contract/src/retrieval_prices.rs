@@ -0,0 +1,195 @@
+/*
+ * Retrieval pricing, quoted separately from storage on Filecoin markets: storage providers list the price they
+ * charge per GiB retrieved, and the price feeders push regional retrieval averages with their own history,
+ * following the same timestamp, region and retention rules as the storage price entries
+ */
+
+use crate::*;
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RetrievalPrice {
+    prices: BTreeMap<u8, U128>, // attoFIL per GiB retrieved per region id
+    global: U128,               // attoFIL per GiB retrieved
+    timestamp: u64,             // epoch time in seconds
+}
+
+impl FilMarket {
+    // delete up to limit retrieval price entries older than cutoff_ts, oldest first, returns their timestamps
+    fn internal_delete_retrieval_prices_before(&mut self, cutoff_ts: u64, limit: u64) -> Vec<u64> {
+        let timestamps: Vec<u64> = self.retrieval_prices
+            .iter()
+            .map(|(timestamp, _)| timestamp)
+            .take_while(|timestamp| *timestamp < cutoff_ts)
+            .take(limit as usize)
+            .collect();
+
+        for timestamp in timestamps.iter() {
+            self.retrieval_prices.remove(timestamp);
+        }
+        if timestamps.contains(&self.latest_retrieval_timestamp) {
+            self.latest_retrieval_timestamp = self.retrieval_prices.max().unwrap_or(0);
+        }
+
+        if !timestamps.is_empty() {
+            self.emit_event("retrieval_price_deleted", json!({ "timestamps": timestamps }));
+        }
+
+        timestamps
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the average retrieval price per region, replacing the entry of the same timestamp
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn set_retrieval_price(&mut self, retrieval_price: RetrievalPrice) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_not_paused(Feature::Prices);
+        self.assert_valid_price_timestamp(retrieval_price.timestamp);
+        for region in retrieval_price.prices.keys() {
+            require!(self.is_valid_region(*region), format!("invalid region {}", region));
+        }
+        let initial_storage_usage = env::storage_usage();
+
+        if let Some(cutoff_ts) = self.retention_cutoff() {
+            self.internal_delete_retrieval_prices_before(cutoff_ts, retention::AUTO_PRUNE_ENTRIES);
+        }
+        self.retrieval_prices.insert(&retrieval_price.timestamp, &retrieval_price);
+        self.latest_retrieval_timestamp = self.latest_retrieval_timestamp.max(retrieval_price.timestamp);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("retrieval_price_set", json!({ "retrieval_price": retrieval_price }));
+    }
+
+    // delete the given retrieval price timestamps, returns the number of entries removed
+    // the storage staking of the removed entries stays with the contract, the attached deposit is refunded
+    #[payable]
+    pub fn delete_retrieval_prices(&mut self, timestamps: Vec<u64>) -> u32 {
+        self.assert_owner();
+        let initial_storage_usage = env::storage_usage();
+
+        let removed: Vec<u64> = timestamps.into_iter().filter(|timestamp| self.retrieval_prices.remove(timestamp).is_some()).collect();
+        self.latest_retrieval_timestamp = self.retrieval_prices.max().unwrap_or(0);
+        self.refund_deposit(initial_storage_usage);
+
+        if !removed.is_empty() {
+            self.emit_event("retrieval_price_deleted", json!({ "timestamps": removed }));
+        }
+
+        removed.len() as u32
+    }
+
+    // get the latest retrieval price entry, None if none was set
+    pub fn get_latest_retrieval_price(&self) -> Option<RetrievalPrice> {
+        self.retrieval_prices.get(&self.latest_retrieval_timestamp)
+    }
+
+    // get up to limit retrieval price entries with from_timestamp <= timestamp <= to_timestamp, in ascending order
+    pub fn get_retrieval_price_history(&self, from_timestamp: u64, to_timestamp: u64, limit: u64) -> Vec<RetrievalPrice> {
+        require!(from_timestamp <= to_timestamp, "from_timestamp must not be greater than to_timestamp");
+
+        self.retrieval_prices
+            .range((Bound::Included(from_timestamp), Bound::Included(to_timestamp)))
+            .take(view_limit(limit))
+            .map(|(_, retrieval_price)| retrieval_price)
+            .collect()
+    }
+
+    // get the retrieval price entry in effect at timestamp, the one with the greatest timestamp <= timestamp
+    pub fn get_retrieval_price_at(&self, timestamp: u64) -> Option<RetrievalPrice> {
+        let timestamp = self.retrieval_prices.floor_key(&timestamp)?;
+        self.retrieval_prices.get(&timestamp)
+    }
+
+    // get the min, max and mean retrieval price over the Active storage providers quoting one,
+    // optionally with at least min_reputation
+    pub fn get_retrieval_price_summary(&self, min_reputation: Option<u32>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.reputation >= min_reputation.unwrap_or(0))
+                .filter_map(|sp| sp.retrieval_price.map(|price| price.0))
+        )
+    }
+
+    // get the min, max and mean retrieval price over the Active storage providers of a region quoting one,
+    // optionally with at least min_reputation
+    pub fn get_retrieval_price_summary_by_region(&self, region: u8, min_reputation: Option<u32>) -> PriceSummary {
+        PriceSummary::from_prices(
+            self.storage_providers
+                .values_as_vector()
+                .iter()
+                .filter(|sp| sp.is_active() && sp.region == region && sp.reputation >= min_reputation.unwrap_or(0))
+                .filter_map(|sp| sp.retrieval_price.map(|price| price.0))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    fn retrieval_price(europe: f64, timestamp: u64) -> RetrievalPrice {
+        RetrievalPrice { prices: vec![(REGION_EUROPE, fil(europe))].into_iter().collect(), global: fil(europe), timestamp }
+    }
+
+    #[test]
+    fn retrieval_price_history() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        assert_eq!(None, contract.get_latest_retrieval_price());
+
+        contract.set_retrieval_price(retrieval_price(0.02, 200));
+        contract.set_retrieval_price(retrieval_price(0.01, 100));
+        contract.set_retrieval_price(retrieval_price(0.03, 300));
+
+        // the latest entry only moves forward, the storage prices are untouched
+        assert_eq!(Some(retrieval_price(0.03, 300)), contract.get_latest_retrieval_price());
+        assert_eq!(0, contract.get_price_entry_count());
+
+        let history = contract.get_retrieval_price_history(100, 250, 10);
+        assert_eq!(vec![100, 200], history.iter().map(|entry| entry.timestamp).collect::<Vec<u64>>());
+        assert_eq!(Some(retrieval_price(0.02, 200)), contract.get_retrieval_price_at(299));
+        assert_eq!(None, contract.get_retrieval_price_at(99));
+
+        assert_eq!(1, contract.delete_retrieval_prices(vec![300, 400]));
+        assert_eq!(Some(retrieval_price(0.02, 200)), contract.get_latest_retrieval_price());
+
+        // expired entries are pruned on the next write
+        contract.set_price_retention(850);
+        contract.set_retrieval_price(retrieval_price(0.04, 900));
+        assert_eq!(vec![200, 900], contract.get_retrieval_price_history(0, 1_000, 10).iter().map(|entry| entry.timestamp).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn retrieval_price_summary() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![
+            StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.3), retrieval_price: Some(fil(0.01)), ..Default::default() },
+            StorageProvider { id: "f02".to_string(), region: REGION_EUROPE, power: gib(20.0), price: fil(0.2), ..Default::default() },
+            StorageProvider { id: "f03".to_string(), region: REGION_ASIA, power: gib(30.0), price: fil(0.5), retrieval_price: Some(fil(0.03)), ..Default::default() },
+        ];
+        contract.update_storage_providers(sp_list, None);
+
+        let result = contract.get_retrieval_price_summary(None);
+        assert_eq!((fil(0.01), fil(0.03), fil(0.02), 2), (result.min, result.max, result.mean, result.count));
+
+        let result = contract.get_retrieval_price_summary_by_region(REGION_EUROPE, None);
+        assert_eq!((fil(0.01), 1), (result.mean, result.count));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn set_retrieval_price_without_role() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", NEAR, 1_000);
+        contract.set_retrieval_price(retrieval_price(0.02, 200));
+    }
+}
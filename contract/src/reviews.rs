@@ -0,0 +1,332 @@
+/*
+ * Client reviews of storage providers: a 1-5 star rating with a short comment, one per account and provider.
+ * A review citing a completed deal of the reviewer with the provider is verified; any other account backs its
+ * review with the review deposit and can post one every REVIEW_COOLDOWN seconds. The reviews of a provider are
+ * capped at MAX_REVIEWS_PER_PROVIDER; a verified review arriving at the cap evicts the oldest unverified one, so
+ * unverified reviews can't lock clients out. The owner removes spam, forfeiting its deposit to the fee treasury
+ */
+
+use crate::*;
+
+pub const MAX_REVIEWS_PER_PROVIDER: usize = 100;
+pub const MAX_REVIEW_COMMENT_LEN: usize = 280;
+pub const REVIEW_COOLDOWN: u64 = 3600;
+pub const DEFAULT_REVIEW_DEPOSIT: Balance = 100_000_000_000_000_000_000_000; // 0.1 NEAR
+
+#[derive(Serialize, Deserialize, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Review {
+    account_id: AccountId,
+    stars: u8,             // 1 to 5
+    comment: String,       // at most MAX_REVIEW_COMMENT_LEN bytes
+    deal_id: Option<u64>,  // completed deal of the reviewer with the provider, None for an unverified review
+    deposit: U128,         // yoctoNEAR held until the review is deleted
+    updated_at: u64,       // epoch time in seconds
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReviewSummary {
+    count: u32,
+    verified_count: u32,
+    mean_stars: Option<U128>, // scaled by 10^18, None without reviews
+}
+
+impl FilMarket {
+    // fail the call unless deal_id is a completed deal of the caller with the storage provider
+    fn assert_review_deal(&self, provider_id: &str, deal_id: u64) {
+        let deal = self.expect_deal(deal_id);
+        require!(deal.client == env::predecessor_account_id(), format!("deal {} is not a deal of the caller", deal_id));
+        require!(deal.provider_id == provider_id, format!("deal {} is not with storage provider {}", deal_id, provider_id));
+        require!(deal.state == DealState::Completed, format!("deal {} is not completed", deal_id));
+    }
+
+    // take the review of account_id off a storage provider
+    fn take_review(&mut self, provider_id: &String, account_id: &AccountId) -> Review {
+        let mut reviews = self.reviews.get(provider_id).unwrap_or_default();
+        let index = reviews.iter().position(|review| review.account_id == *account_id)
+            .unwrap_or_else(|| panic!("{} has no review of storage provider {}", account_id, provider_id));
+
+        let review = reviews.remove(index);
+        if reviews.is_empty() {
+            self.reviews.remove(provider_id);
+        } else {
+            self.reviews.insert(provider_id, &reviews);
+        }
+        review
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // post or replace the caller's review of a storage provider, verified when deal_id is given
+    // the attached deposit must cover the storage of the review and, for an unverified review, the review deposit;
+    // the rest is refunded
+    #[payable]
+    pub fn post_review(&mut self, provider_id: String, stars: u8, comment: String, deal_id: Option<u64>) {
+        self.assert_not_paused(Feature::Providers);
+        let account_id = env::predecessor_account_id();
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        require!((1..=5).contains(&stars), "rating must be between 1 and 5 stars");
+        require!(comment.len() <= MAX_REVIEW_COMMENT_LEN, format!("comment is longer than {} bytes", MAX_REVIEW_COMMENT_LEN));
+        require!(self.storage_providers.get(&provider_id).is_some(), format!("storage provider {} not found", provider_id));
+
+        let mut initial_storage_usage = env::storage_usage();
+        let mut reviews = self.reviews.get(&provider_id).unwrap_or_default();
+        let existing = reviews.iter().position(|review| review.account_id == account_id);
+        let held = existing.map_or(0, |index| reviews[index].deposit.0);
+
+        // a verified review returns the deposit of the review it replaces
+        let (deposit, required, released) = match deal_id {
+            Some(deal_id) => {
+                self.assert_review_deal(&provider_id, deal_id);
+                (0, 0, held)
+            }
+            None => {
+                let last_review_at = self.last_review_at.get(&account_id).unwrap_or(0);
+                require!(
+                    last_review_at == 0 || now >= last_review_at + REVIEW_COOLDOWN,
+                    format!("{} can post a review again at {}", account_id, last_review_at + REVIEW_COOLDOWN)
+                );
+                self.last_review_at.insert(&account_id, &now);
+                let required = self.review_deposit.saturating_sub(held);
+                (held + required, required, 0)
+            }
+        };
+
+        let review = Review { account_id: account_id.clone(), stars, comment, deal_id, deposit: U128(deposit), updated_at: now };
+        match existing {
+            Some(index) => reviews[index] = review.clone(),
+            None => {
+                if reviews.len() >= MAX_REVIEWS_PER_PROVIDER && deal_id.is_some() {
+                    if let Some(index) = reviews.iter().position(|review| review.deal_id.is_none()) {
+                        // the evicted author gets back its deposit and storage staking, the new review pays its own
+                        let evicted = reviews.remove(index);
+                        let evicted_bytes = evicted.try_to_vec().expect("failed to serialize the review").len() as StorageUsage;
+                        initial_storage_usage -= evicted_bytes;
+                        Promise::new(evicted.account_id.clone())
+                            .transfer(evicted.deposit.0 + env::storage_byte_cost() * Balance::from(evicted_bytes));
+                        self.emit_event("review_evicted", json!({
+                            "provider_id": provider_id,
+                            "account_id": evicted.account_id,
+                        }));
+                    }
+                }
+                require!(
+                    reviews.len() < MAX_REVIEWS_PER_PROVIDER,
+                    format!("storage provider {} has the maximum of {} reviews", provider_id, MAX_REVIEWS_PER_PROVIDER)
+                );
+                reviews.push(review.clone());
+            }
+        }
+        self.reviews.insert(&provider_id, &reviews);
+
+        let attached = self.deposit_after_storage(initial_storage_usage);
+        require!(attached >= required, format!("must attach {} yoctoNEAR for the review deposit", required));
+        let refund = attached - required + released;
+        if refund > 0 {
+            Promise::new(account_id).transfer(refund);
+        }
+
+        self.emit_event("review_posted", json!({
+            "provider_id": provider_id,
+            "review": review,
+        }));
+    }
+
+    // delete the caller's review of a storage provider, refunding its deposit and storage staking
+    pub fn delete_review(&mut self, provider_id: String) {
+        let account_id = env::predecessor_account_id();
+
+        let initial_storage_usage = env::storage_usage();
+        let review = self.take_review(&provider_id, &account_id);
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        let refund = review.deposit.0 + env::storage_byte_cost() * Balance::from(freed);
+        if refund > 0 {
+            Promise::new(account_id.clone()).transfer(refund);
+        }
+
+        self.emit_event("review_deleted", json!({
+            "provider_id": provider_id,
+            "account_id": account_id,
+        }));
+    }
+
+    // remove a spam review, its deposit goes to the fee treasury
+    pub fn remove_review(&mut self, provider_id: String, account_id: AccountId, reason: String) {
+        self.assert_owner();
+
+        let review = self.take_review(&provider_id, &account_id);
+        self.fee_treasury = self.fee_treasury.checked_add(review.deposit.0).expect("fee treasury overflows u128");
+
+        self.emit_event("review_removed", json!({
+            "provider_id": provider_id,
+            "account_id": account_id,
+            "forfeited": review.deposit,
+            "reason": reason,
+        }));
+    }
+
+    // set the deposit backing an unverified review, reviews already posted keep theirs
+    pub fn set_review_deposit(&mut self, review_deposit: U128) {
+        self.assert_config_authority();
+
+        self.review_deposit = review_deposit.0;
+        self.emit_event("review_deposit_set", json!({ "review_deposit": review_deposit }));
+    }
+
+    // get the deposit backing an unverified review
+    pub fn get_review_deposit(&self) -> U128 {
+        U128(self.review_deposit)
+    }
+
+    // get the reviews of a storage provider, oldest first, paginated
+    pub fn get_reviews(&self, provider_id: String, from_index: u64, limit: u64) -> Vec<Review> {
+        self.reviews
+            .get(&provider_id)
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+
+    // get the review of an account for a storage provider
+    pub fn get_review(&self, provider_id: String, account_id: AccountId) -> Option<Review> {
+        self.reviews.get(&provider_id)?.into_iter().find(|review| review.account_id == account_id)
+    }
+
+    // get the number of reviews of a storage provider and their mean rating
+    pub fn get_review_summary(&self, provider_id: String) -> ReviewSummary {
+        let reviews = self.reviews.get(&provider_id).unwrap_or_default();
+        let stars: u128 = reviews.iter().map(|review| review.stars as u128).sum();
+
+        ReviewSummary {
+            count: reviews.len() as u32,
+            verified_count: reviews.iter().filter(|review| review.deal_id.is_some()).count() as u32,
+            mean_stars: (stars * 10u128.pow(decimal::PRICE_DECIMALS)).checked_div(reviews.len() as u128).map(U128),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    fn listed_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 10_000);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        contract
+    }
+
+    #[test]
+    fn post_and_delete_reviews() {
+        let mut contract = listed_contract();
+
+        set_context("alice_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 5, "fast retrievals".to_string(), None);
+        set_context("bob_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 2, "".to_string(), None);
+
+        let review = contract.get_review("f01".to_string(), account("alice_near")).unwrap();
+        assert_eq!((5, U128(DEFAULT_REVIEW_DEPOSIT)), (review.stars, review.deposit));
+        assert_eq!(2, contract.get_reviews("f01".to_string(), 0, 10).len());
+        let summary = contract.get_review_summary("f01".to_string());
+        assert_eq!((2, 0, Some(fil(3.5))), (summary.count, summary.verified_count, summary.mean_stars));
+
+        // replacing a review after the cooldown keeps a single review and deposit
+        set_context("alice_near", NEAR, 10_000 + REVIEW_COOLDOWN);
+        contract.post_review("f01".to_string(), 4, "still good".to_string(), None);
+        let reviews = contract.get_reviews("f01".to_string(), 0, 10);
+        assert_eq!(2, reviews.len());
+        assert_eq!((4, U128(DEFAULT_REVIEW_DEPOSIT)), (reviews[0].stars, reviews[0].deposit));
+
+        contract.delete_review("f01".to_string());
+        assert_eq!(None, contract.get_review("f01".to_string(), account("alice_near")));
+
+        // spam is removed by the owner, its deposit goes to the fee treasury
+        set_context("carol_near", NEAR, 10_000 + REVIEW_COOLDOWN);
+        contract.remove_review("f01".to_string(), account("bob_near"), "spam".to_string());
+        assert_eq!(0, contract.get_review_summary("f01".to_string()).count);
+        assert_eq!(U128(DEFAULT_REVIEW_DEPOSIT), contract.get_fee_treasury().balance);
+    }
+
+    // a provider with MAX_REVIEWS_PER_PROVIDER unverified reviews and a completed deal 0 of client_near
+    fn full_contract() -> FilMarket {
+        let mut contract = listed_contract();
+        set_context("miner_near", NEAR, 10_000);
+        contract.claim_provider("f01".to_string());
+        set_context("carol_near", NEAR, 10_000);
+        contract.approve_provider_claim("f01".to_string());
+
+        set_context("client_near", NEAR, 10_000);
+        contract.post_storage_request(gib(32.0), 518_400, REGION_EUROPE, fil(0.2));
+        set_context("miner_near", NEAR, 10_000);
+        let offer_id = contract.post_offer(0, "f01".to_string(), fil(0.15));
+        set_context("client_near", NEAR, 10_000);
+        contract.accept_offer(offer_id);
+        set_context("miner_near", NEAR, 10_000);
+        contract.accept_deal(0);
+        contract.activate_deal(0);
+        contract.complete_deal(0);
+
+        for i in 0..MAX_REVIEWS_PER_PROVIDER {
+            set_context(&format!("spam{}_near", i), NEAR, 10_000);
+            contract.post_review("f01".to_string(), 1, "".to_string(), None);
+        }
+        contract
+    }
+
+    #[test]
+    fn verified_review_evicts_unverified_at_cap() {
+        let mut contract = full_contract();
+
+        set_context("client_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 5, "as agreed".to_string(), Some(0));
+
+        let summary = contract.get_review_summary("f01".to_string());
+        assert_eq!((MAX_REVIEWS_PER_PROVIDER as u32, 1), (summary.count, summary.verified_count));
+        assert_eq!(None, contract.get_review("f01".to_string(), account("spam0_near")));
+        assert_eq!(U128(0), contract.get_review("f01".to_string(), account("client_near")).unwrap().deposit);
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider f01 has the maximum of 100 reviews")]
+    fn unverified_review_at_cap() {
+        let mut contract = full_contract();
+
+        set_context("alice_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 5, "".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "alice_near can post a review again at 13600")]
+    fn review_cooldown() {
+        let mut contract = listed_contract();
+
+        set_context("alice_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 5, "".to_string(), None);
+        contract.post_review("f01".to_string(), 1, "".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "deal 0 not found")]
+    fn review_with_unknown_deal() {
+        let mut contract = listed_contract();
+
+        set_context("alice_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 5, "".to_string(), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "rating must be between 1 and 5 stars")]
+    fn review_out_of_range() {
+        let mut contract = listed_contract();
+
+        set_context("alice_near", NEAR, 10_000);
+        contract.post_review("f01".to_string(), 0, "".to_string(), None);
+    }
+}
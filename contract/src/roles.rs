@@ -0,0 +1,170 @@
+/*
+ * Roles granted by the owner, so bots can push data without holding the owner key
+ */
+
+use crate::*;
+
+// the owner implicitly holds every role
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    PriceFeeder, // sets prices and reviews price candidates
+    SpManager,   // adds, updates and deletes storage providers
+}
+
+impl FilMarket {
+    // fail the call unless the predecessor is the owner or holds the given role
+    pub(crate) fn assert_role(&self, role: Role) {
+        let account_id = env::predecessor_account_id();
+
+        require!(
+            self.is_owner(&account_id) || self.has_role(account_id.clone(), role),
+            format!("{}: account_id {} does not have role {:?}", errors::ERR_MISSING_ROLE, account_id, role)
+        );
+        self.mark_privileged(account_id.clone());
+        if role == Role::PriceFeeder {
+            self.assert_feeder_bonded(&account_id);
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // grant a role to an account
+    pub fn add_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_config_authority();
+
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        if roles.contains(&role) {
+            return;
+        }
+
+        roles.push(role);
+        self.roles.insert(&account_id, &roles);
+        self.emit_event("role_added", json!({
+            "account_id": account_id,
+            "role": role,
+        }));
+    }
+
+    // revoke a role from an account
+    pub fn remove_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_config_authority();
+
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        if !roles.contains(&role) {
+            return;
+        }
+
+        roles.retain(|r| *r != role);
+        if roles.is_empty() {
+            self.roles.remove(&account_id);
+        } else {
+            self.roles.insert(&account_id, &roles);
+        }
+        self.emit_event("role_removed", json!({
+            "account_id": account_id,
+            "role": role,
+        }));
+    }
+
+    // check if an account was granted a role, the owner's implicit roles are not reported
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        self.roles.get(&account_id).is_some_and(|roles| roles.contains(&role))
+    }
+
+    // get the roles granted to an account
+    pub fn get_roles(&self, account_id: AccountId) -> Vec<Role> {
+        self.roles.get(&account_id).unwrap_or_default()
+    }
+
+    // get the accounts holding a role, paginated
+    pub fn get_role_holders(&self, role: Role, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.roles
+            .iter()
+            .filter(|(_, roles)| roles.contains(&role))
+            .map(|(account_id, _)| account_id)
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, fil, gib, set_context, NEAR};
+
+    #[test]
+    fn add_then_remove_role() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        contract.add_role(account("feeder_near"), Role::PriceFeeder);
+        contract.add_role(account("manager_near"), Role::SpManager);
+        contract.add_role(account("manager_near"), Role::PriceFeeder);
+        contract.add_role(account("manager_near"), Role::PriceFeeder);
+
+        assert!(contract.has_role(account("feeder_near"), Role::PriceFeeder));
+        assert!(!contract.has_role(account("feeder_near"), Role::SpManager));
+        assert_eq!(vec![Role::SpManager, Role::PriceFeeder], contract.get_roles(account("manager_near")));
+        assert_eq!(vec![account("feeder_near"), account("manager_near")], contract.get_role_holders(Role::PriceFeeder, 0, 10));
+        assert_eq!(vec![account("manager_near")], contract.get_role_holders(Role::SpManager, 0, 10));
+
+        contract.remove_role(account("feeder_near"), Role::PriceFeeder);
+        assert!(contract.get_roles(account("feeder_near")).is_empty());
+        assert_eq!(vec![account("manager_near")], contract.get_role_holders(Role::PriceFeeder, 0, 10));
+    }
+
+    #[test]
+    fn role_holders_can_call_their_methods() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("feeder_near"), Role::PriceFeeder);
+        contract.add_role(account("manager_near"), Role::SpManager);
+
+        set_context("feeder_near", NEAR, 0);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 10, ..Default::default() });
+        assert_eq!(fil(0.00034), contract.get_latest_price_per_region().global);
+
+        set_context("manager_near", NEAR, 0);
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        assert_eq!(1, contract.get_storage_providers_count());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE: account_id feeder_near does not have role SpManager")]
+    fn price_feeder_cannot_update_storage_providers() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("feeder_near"), Role::PriceFeeder);
+
+        set_context("feeder_near", NEAR, 0);
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: 1, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn removed_role_is_rejected() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("feeder_near"), Role::PriceFeeder);
+        contract.remove_role(account("feeder_near"), Role::PriceFeeder);
+
+        set_context("feeder_near", NEAR, 0);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.00034), timestamp: 10, ..Default::default() });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn add_role_not_owner() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.add_role(account("manager_near"), Role::SpManager);
+
+        set_context("manager_near", NEAR, 0);
+        contract.add_role(account("manager_near"), Role::PriceFeeder);
+    }
+}
@@ -0,0 +1,231 @@
+/*
+ * Reliability of the storage providers as reported by the feeders: proving faults, sector recoveries and uptime
+ * checks, kept as lifetime totals plus daily buckets over a rolling SLA_WINDOW_DAYS window, so clients can
+ * weigh the price of a provider against how reliably it stores data. The faults and uptime checks also count in
+ * the reputation of the provider, which sets its score and tier
+ */
+
+use crate::*;
+
+// days of daily buckets kept per storage provider
+pub const SLA_WINDOW_DAYS: u64 = 30;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct SlaBucket {
+    day: u64, // days since the unix epoch
+    faults: u32,
+    recoveries: u32,
+    uptime_checks: u32,
+    uptime_online: u32, // uptime checks where the provider was online
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct ProviderSlaRecord {
+    total_faults: u64,
+    total_recoveries: u64,
+    last_fault_at: Option<u64>,    // epoch time in seconds
+    last_recovery_at: Option<u64>, // epoch time in seconds
+    last_report_at: u64,           // epoch time in seconds of the latest report
+    buckets: Vec<SlaBucket>,       // ascending by day, within SLA_WINDOW_DAYS of the latest report
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SlaReport {
+    id: String,           // storage provider id
+    faults: u32,          // proving faults observed
+    recoveries: u32,      // sector recoveries observed
+    online: Option<bool>, // result of an uptime check, None without one
+    timestamp: u64,       // epoch time in seconds of the observation
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProviderSla {
+    window_days: u64,
+    faults: u64,             // over the window
+    recoveries: u64,         // over the window
+    uptime_checks: u64,      // over the window
+    uptime_bps: Option<u32>, // online checks over the window in basis points, None without checks
+    total_faults: u64,
+    total_recoveries: u64,
+    last_fault_at: Option<u64>,
+    last_recovery_at: Option<u64>,
+    last_report_at: u64,
+}
+
+impl ProviderSlaRecord {
+    fn apply(&mut self, report: &SlaReport) {
+        let day = report.timestamp / SECONDS_PER_DAY;
+        let latest_day = self.last_report_at.max(report.timestamp) / SECONDS_PER_DAY;
+        let first_day = (latest_day + 1).saturating_sub(SLA_WINDOW_DAYS);
+
+        self.total_faults = self.total_faults.saturating_add(report.faults as u64);
+        self.total_recoveries = self.total_recoveries.saturating_add(report.recoveries as u64);
+        if report.faults > 0 {
+            self.last_fault_at = self.last_fault_at.max(Some(report.timestamp));
+        }
+        if report.recoveries > 0 {
+            self.last_recovery_at = self.last_recovery_at.max(Some(report.timestamp));
+        }
+        self.last_report_at = self.last_report_at.max(report.timestamp);
+
+        // a report older than the window only counts in the totals
+        self.buckets.retain(|bucket| bucket.day >= first_day);
+        if day < first_day {
+            return;
+        }
+
+        let index = match self.buckets.binary_search_by_key(&day, |bucket| bucket.day) {
+            Ok(index) => index,
+            Err(index) => {
+                self.buckets.insert(index, SlaBucket { day, ..Default::default() });
+                index
+            }
+        };
+        let bucket = &mut self.buckets[index];
+        bucket.faults = bucket.faults.saturating_add(report.faults);
+        bucket.recoveries = bucket.recoveries.saturating_add(report.recoveries);
+        if let Some(online) = report.online {
+            bucket.uptime_checks = bucket.uptime_checks.saturating_add(1);
+            bucket.uptime_online = bucket.uptime_online.saturating_add(online as u32);
+        }
+    }
+
+    // the counters of the buckets within the window ending at now
+    fn summary(&self, now: u64) -> ProviderSla {
+        let first_day = (now / SECONDS_PER_DAY + 1).saturating_sub(SLA_WINDOW_DAYS);
+        let window = self.buckets.iter().filter(|bucket| bucket.day >= first_day);
+
+        let (mut faults, mut recoveries, mut uptime_checks, mut uptime_online) = (0u64, 0u64, 0u64, 0u64);
+        for bucket in window {
+            faults += bucket.faults as u64;
+            recoveries += bucket.recoveries as u64;
+            uptime_checks += bucket.uptime_checks as u64;
+            uptime_online += bucket.uptime_online as u64;
+        }
+
+        ProviderSla {
+            window_days: SLA_WINDOW_DAYS,
+            faults,
+            recoveries,
+            uptime_checks,
+            uptime_bps: (10_000 * uptime_online).checked_div(uptime_checks).map(|bps| bps as u32),
+            total_faults: self.total_faults,
+            total_recoveries: self.total_recoveries,
+            last_fault_at: self.last_fault_at,
+            last_recovery_at: self.last_recovery_at,
+            last_report_at: self.last_report_at,
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // record the faults, recoveries and uptime checks observed for listed storage providers
+    // the attached deposit must cover the storage staking of the added bytes, the rest is refunded
+    #[payable]
+    pub fn report_provider_sla(&mut self, reports: Vec<SlaReport>) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_not_paused(Feature::Providers);
+        require!(!reports.is_empty(), "no SLA reports to submit");
+        let initial_storage_usage = env::storage_usage();
+
+        for report in reports.iter() {
+            require!(self.storage_providers.get(&report.id).is_some(), format!("storage provider {} not found", report.id));
+            self.assert_valid_price_timestamp(report.timestamp);
+
+            let mut record = self.provider_sla.get(&report.id).unwrap_or_default();
+            record.apply(report);
+            self.provider_sla.insert(&report.id, &record);
+            self.record_sla_reputation(&report.id, report.faults, report.online);
+        }
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("provider_sla_reported", json!({ "reports": reports }));
+    }
+
+    // get the reliability of a storage provider over the SLA window ending now, None before its first report
+    pub fn get_provider_sla(&self, id: String) -> Option<ProviderSla> {
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        self.provider_sla.get(&id).map(|record| record.summary(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, gib, set_context, NEAR};
+
+    const DAY: u64 = SECONDS_PER_DAY;
+
+    fn report(faults: u32, recoveries: u32, online: Option<bool>, timestamp: u64) -> SlaReport {
+        SlaReport { id: "f01".to_string(), faults, recoveries, online, timestamp }
+    }
+
+    fn listed_contract(now: u64) -> FilMarket {
+        set_context("carol_near", NEAR, now);
+        let mut contract = FilMarket::new();
+        let sp_list = vec![StorageProvider { id: "f01".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        contract
+    }
+
+    #[test]
+    fn rolling_sla_window() {
+        let mut contract = listed_contract(100 * DAY);
+        assert_eq!(None, contract.get_provider_sla("f01".to_string()));
+
+        contract.report_provider_sla(vec![
+            report(2, 0, Some(false), 60 * DAY),
+            report(0, 1, Some(true), 99 * DAY),
+            report(1, 0, Some(true), 100 * DAY),
+            report(0, 0, Some(true), 100 * DAY + 10),
+        ]);
+
+        // the fault of day 60 is out of the window but in the totals
+        let sla = contract.get_provider_sla("f01".to_string()).unwrap();
+        assert_eq!((1, 1, 3), (sla.faults, sla.recoveries, sla.uptime_checks));
+        assert_eq!(Some(10_000), sla.uptime_bps);
+        assert_eq!((3, 1), (sla.total_faults, sla.total_recoveries));
+        assert_eq!((Some(100 * DAY), Some(99 * DAY), 100 * DAY + 10), (sla.last_fault_at, sla.last_recovery_at, sla.last_report_at));
+
+        // the window follows the block time
+        set_context("carol_near", NEAR, 129 * DAY);
+        let sla = contract.get_provider_sla("f01".to_string()).unwrap();
+        assert_eq!((0, 2, Some(10_000)), (sla.recoveries, sla.uptime_checks, sla.uptime_bps));
+        contract.report_provider_sla(vec![report(0, 0, Some(false), 129 * DAY)]);
+        assert_eq!(Some(6_666), contract.get_provider_sla("f01".to_string()).unwrap().uptime_bps);
+    }
+
+    #[test]
+    fn sla_reports_count_in_reputation() {
+        let mut contract = listed_contract(100 * DAY);
+
+        contract.report_provider_sla(vec![report(2, 0, Some(false), 100 * DAY), report(0, 1, None, 100 * DAY)]);
+
+        // no deal succeeded and the uptime check failed, only the neutral rating counts
+        let storage_provider = contract.get_storage_provider("f01".to_string()).unwrap();
+        assert_eq!(reputation::NEUTRAL_REPUTATION / 3, storage_provider.reputation);
+        assert_eq!(100 * DAY * NANOSECONDS_PER_SECOND, storage_provider.last_updated);
+        let reputation = contract.get_reputation("f01".to_string()).unwrap();
+        assert_eq!(Some(10_000), reputation.fault_bps());
+    }
+
+    #[test]
+    #[should_panic(expected = "storage provider f02 not found")]
+    fn report_unlisted_provider() {
+        let mut contract = listed_contract(100 * DAY);
+        contract.report_provider_sla(vec![SlaReport { id: "f02".to_string(), ..report(1, 0, None, 100 * DAY) }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn report_without_role() {
+        let mut contract = listed_contract(100 * DAY);
+
+        set_context("bob_near", NEAR, 100 * DAY);
+        contract.report_provider_sla(vec![report(1, 0, None, 100 * DAY)]);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * NEP-330 contract source metadata, with the hash of the deployed code recorded by the owner after each deploy.
+ * A contract cannot read its own code hash, so integrators check the recorded hash against the code_hash of
+ * the account returned by the RPC (view_account) to tell which build is deployed on mainnet and testnet
+ */
+
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+
+pub const SOURCE_LINK: &str = "https://github.com/CrossChainLabs-FIL/filmarket-contract";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Standard {
+    standard: String, // e.g. nep330
+    version: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSourceMetadata {
+    version: Option<String>, // version of the contract crate
+    link: Option<String>,    // source repository
+    standards: Vec<Standard>,
+}
+
+fn standard(standard: &str, version: &str) -> Standard {
+    Standard { standard: standard.to_string(), version: version.to_string() }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the version, source repository and standards implemented by the deployed contract
+    pub fn contract_source_metadata(&self) -> ContractSourceMetadata {
+        ContractSourceMetadata {
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            link: Some(SOURCE_LINK.to_string()),
+            standards: vec![
+                standard("nep330", "1.1.0"),
+                standard("nep297", "1.0.0"),
+            ],
+        }
+    }
+
+    // record the hash of the deployed code, to be called after every deploy
+    pub fn set_code_hash(&mut self, code_hash: Base58CryptoHash) {
+        self.assert_owner();
+
+        self.code_hash = Some(code_hash);
+        self.emit_event("code_hash_set", json!({ "code_hash": code_hash }));
+    }
+
+    // get the hash of the deployed code as recorded by the owner, None if it was never recorded
+    pub fn get_code_hash(&self) -> Option<Base58CryptoHash> {
+        self.code_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::set_context;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn contract_source_metadata() {
+        set_context("carol_near", 0, 0);
+        let contract = FilMarket::new();
+
+        let metadata = contract.contract_source_metadata();
+        assert_eq!(Some(env!("CARGO_PKG_VERSION").to_string()), metadata.version);
+        assert_eq!(Some(SOURCE_LINK.to_string()), metadata.link);
+        assert!(metadata.standards.contains(&standard("nep330", "1.1.0")));
+    }
+
+    #[test]
+    fn set_code_hash() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+        assert_eq!(None, contract.get_code_hash());
+
+        let code_hash = Base58CryptoHash::try_from("CzXxcDtRRsKBtbpCpBhrydqU6HpG6Ux9Z3BwfZwy9Z9m").unwrap();
+        contract.set_code_hash(code_hash);
+        assert_eq!(Some(code_hash), contract.get_code_hash());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn set_code_hash_by_other_account() {
+        set_context("carol_near", 0, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("bob_near", 0, 0);
+        contract.set_code_hash(Base58CryptoHash::default());
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * Staleness of the price feed: the latest price is fresh while it, or the latest heartbeat confirming it, is not
+ * older than max_price_age seconds
+ */
+
+use crate::*;
+
+pub const DEFAULT_MAX_PRICE_AGE: u64 = 172_800; // 2 days in seconds
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PriceStatus {
+    Fresh,
+    Stale,
+    Missing, // no price was ever set
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CheckedPrice {
+    pub(crate) status: PriceStatus,
+    pub(crate) price: Option<PricePerRegion>, // the latest entry, also returned when stale
+    pub(crate) age_seconds: Option<u64>,      // since the latest entry or heartbeat, None when missing
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // confirm that the latest price still holds when it has not changed, resetting its age
+    pub fn heartbeat(&mut self) {
+        self.assert_role(Role::PriceFeeder);
+        self.assert_not_paused(Feature::Prices);
+        require!(self.price_per_region.get(&self.latest_timestamp).is_some(), "no price to confirm");
+
+        self.last_heartbeat = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        self.emit_event("price_heartbeat", json!({
+            "latest_timestamp": self.latest_timestamp,
+            "heartbeat": self.last_heartbeat,
+        }));
+    }
+
+    // set how many seconds the latest price stays fresh without a new entry or heartbeat
+    pub fn set_max_price_age(&mut self, max_age_seconds: u64) {
+        self.assert_config_authority();
+
+        self.max_price_age = max_age_seconds;
+        self.emit_event("max_price_age_set", json!({ "max_age_seconds": max_age_seconds }));
+    }
+
+    // get how many seconds the latest price stays fresh without a new entry or heartbeat
+    pub fn get_max_price_age(&self) -> u64 {
+        self.max_price_age
+    }
+
+    // get the latest price with whether it is fresh, stale or missing
+    pub fn get_latest_price_checked(&self) -> CheckedPrice {
+        let price = match self.price_per_region.get(&self.latest_timestamp) {
+            Some(price) => price,
+            None => return CheckedPrice { status: PriceStatus::Missing, price: None, age_seconds: None },
+        };
+
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        let age = now.saturating_sub(price.timestamp.max(self.last_heartbeat));
+        let status = if age <= self.max_price_age { PriceStatus::Fresh } else { PriceStatus::Stale };
+
+        CheckedPrice { status, price: Some(price), age_seconds: Some(age) }
+    }
+
+    // whether the latest price is fresh
+    pub fn is_price_fresh(&self) -> bool {
+        self.get_latest_price_checked().status == PriceStatus::Fresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    #[test]
+    fn price_staleness_and_heartbeat() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        assert_eq!(PriceStatus::Missing, contract.get_latest_price_checked().status);
+        assert!(!contract.is_price_fresh());
+
+        contract.set_max_price_age(100);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 950, ..Default::default() });
+        let result = contract.get_latest_price_checked();
+        assert_eq!(PriceStatus::Fresh, result.status);
+        assert_eq!(Some(50), result.age_seconds);
+
+        set_context("carol_near", NEAR, 1_100);
+        let result = contract.get_latest_price_checked();
+        assert_eq!(PriceStatus::Stale, result.status);
+        assert_eq!(fil(0.1), result.price.unwrap().global);
+
+        // a heartbeat makes the unchanged price fresh again
+        contract.heartbeat();
+        assert!(contract.is_price_fresh());
+        assert_eq!(Some(0), contract.get_latest_price_checked().age_seconds);
+    }
+
+    #[test]
+    #[should_panic(expected = "no price to confirm")]
+    fn heartbeat_without_price() {
+        set_context("carol_near", NEAR, 1_000);
+        let mut contract = FilMarket::new();
+        contract.heartbeat();
+    }
+}
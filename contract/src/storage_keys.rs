@@ -0,0 +1,161 @@
+/*
+ * Storage key prefixes of the contract collections, one variant per collection so two subsystems can't pick the
+ * same prefix. The prefixes are the single letters the collections were created with, mapped by hand instead of
+ * deriving BorshStorageKey, whose variant indexes would orphan the stored state
+ *
+ * A new collection adds a variant with an unused letter. "b" holds the V1 prices until the migration clears them,
+ * "S" and "V" are taken by the STATE and VERSION keys
+ */
+
+use near_sdk::IntoStorageKey;
+
+pub(crate) enum StorageKey {
+    StorageProviders,
+    OwnerHistory,
+    PricePerRegion,
+    PendingPrices,
+    Roles,
+    ProviderClaims,
+    ProviderAccounts,
+    ProviderHistory,
+    StorageRequests,
+    Offers,
+    Deals,
+    DealEscrow,
+    TokenBalances,
+    DealTokenEscrow,
+    Reputations,
+    Regions,
+    RegionProviders { region: u8 }, // the ids of the providers of a region
+    RegionIndex,
+    PriceIndex,
+    PowerIndex,
+    Batches,
+    PriceRounds,
+    Subscribers,
+    DeniedProviders,
+    AdminLog,
+    Proposals,
+    ListingPaidUntil,
+    ActiveHistory,
+    Orders,
+    OrderBook,
+    RemovedProviders,
+    RetrievalPrices,
+    ProviderSla,
+    FeederBonds,
+    Reviews,
+    LastReviewAt,
+    ProviderIds,
+    UpdatedIndex,
+    RemovedIndex,
+    ApprovedSubscribers,
+}
+
+impl IntoStorageKey for StorageKey {
+    fn into_storage_key(self) -> Vec<u8> {
+        let prefix: &[u8] = match self {
+            StorageKey::StorageProviders => b"a",
+            StorageKey::OwnerHistory => b"c",
+            StorageKey::PricePerRegion => b"d",
+            StorageKey::PendingPrices => b"e",
+            StorageKey::Roles => b"f",
+            StorageKey::ProviderClaims => b"g",
+            StorageKey::ProviderAccounts => b"h",
+            StorageKey::ProviderHistory => b"i",
+            StorageKey::StorageRequests => b"j",
+            StorageKey::Offers => b"k",
+            StorageKey::Deals => b"l",
+            StorageKey::DealEscrow => b"m",
+            StorageKey::TokenBalances => b"n",
+            StorageKey::DealTokenEscrow => b"o",
+            StorageKey::Reputations => b"p",
+            StorageKey::Regions => b"q",
+            StorageKey::RegionProviders { region } => return [b"r".as_ref(), &[region]].concat(),
+            StorageKey::RegionIndex => b"s",
+            StorageKey::PriceIndex => b"t",
+            StorageKey::PowerIndex => b"u",
+            StorageKey::Batches => b"v",
+            StorageKey::PriceRounds => b"w",
+            StorageKey::Subscribers => b"x",
+            StorageKey::DeniedProviders => b"y",
+            StorageKey::AdminLog => b"z",
+            StorageKey::Proposals => b"A",
+            StorageKey::ListingPaidUntil => b"B",
+            StorageKey::ActiveHistory => b"C",
+            StorageKey::Orders => b"D",
+            StorageKey::OrderBook => b"E",
+            StorageKey::RemovedProviders => b"F",
+            StorageKey::RetrievalPrices => b"G",
+            StorageKey::ProviderSla => b"H",
+            StorageKey::FeederBonds => b"I",
+            StorageKey::Reviews => b"J",
+            StorageKey::LastReviewAt => b"K",
+            StorageKey::ProviderIds => b"L",
+            StorageKey::UpdatedIndex => b"M",
+            StorageKey::RemovedIndex => b"N",
+            StorageKey::ApprovedSubscribers => b"O",
+        };
+
+        prefix.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn prefixes_are_unique() {
+        let keys = vec![
+            StorageKey::StorageProviders,
+            StorageKey::OwnerHistory,
+            StorageKey::PricePerRegion,
+            StorageKey::PendingPrices,
+            StorageKey::Roles,
+            StorageKey::ProviderClaims,
+            StorageKey::ProviderAccounts,
+            StorageKey::ProviderHistory,
+            StorageKey::StorageRequests,
+            StorageKey::Offers,
+            StorageKey::Deals,
+            StorageKey::DealEscrow,
+            StorageKey::TokenBalances,
+            StorageKey::DealTokenEscrow,
+            StorageKey::Reputations,
+            StorageKey::Regions,
+            StorageKey::RegionIndex,
+            StorageKey::PriceIndex,
+            StorageKey::PowerIndex,
+            StorageKey::Batches,
+            StorageKey::PriceRounds,
+            StorageKey::Subscribers,
+            StorageKey::DeniedProviders,
+            StorageKey::AdminLog,
+            StorageKey::Proposals,
+            StorageKey::ListingPaidUntil,
+            StorageKey::ActiveHistory,
+            StorageKey::Orders,
+            StorageKey::OrderBook,
+            StorageKey::RemovedProviders,
+            StorageKey::RetrievalPrices,
+            StorageKey::ProviderSla,
+            StorageKey::FeederBonds,
+            StorageKey::Reviews,
+            StorageKey::LastReviewAt,
+            StorageKey::ProviderIds,
+            StorageKey::UpdatedIndex,
+            StorageKey::RemovedIndex,
+            StorageKey::ApprovedSubscribers,
+        ];
+        let count = keys.len();
+        let prefixes: HashSet<Vec<u8>> = keys.into_iter().map(|key| key.into_storage_key()).collect();
+
+        assert_eq!(count, prefixes.len());
+        assert!(!prefixes.contains(b"b".as_ref()));
+        assert!(!prefixes.contains(b"S".as_ref()) && !prefixes.contains(b"V".as_ref()));
+        assert!(!prefixes.contains(b"r".as_ref()));
+        assert_eq!(b"r\x02".to_vec(), StorageKey::RegionProviders { region: 2 }.into_storage_key());
+    }
+}
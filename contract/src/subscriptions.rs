@@ -0,0 +1,264 @@
+/*
+ * Price push subscriptions: contracts subscribe to receive every price entry written, through an on_price_update
+ * call with the gas they asked for. Only the contracts approved by the owner can subscribe, so the MAX_SUBSCRIBERS
+ * slots can't be squatted. A subscriber failing MAX_SUBSCRIBER_FAILURES calls in a row is removed
+ *
+ * A subscriber implements:
+ *
+ *   pub fn on_price_update(&mut self, price_per_region: PricePerRegion)
+ */
+
+use crate::*;
+use near_sdk::{ext_contract, is_promise_success, Gas};
+
+pub const MAX_SUBSCRIBERS: u64 = 5;
+pub const MAX_SUBSCRIBER_GAS: Gas = Gas(15_000_000_000_000);
+pub const MAX_SUBSCRIBER_FAILURES: u32 = 3;
+const GAS_FOR_ON_SUBSCRIBER_NOTIFIED: Gas = Gas(5_000_000_000_000);
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Subscription {
+    gas: Gas,      // attached to every on_price_update call
+    failures: u32, // failed calls in a row
+}
+
+#[ext_contract(ext_price_subscriber)]
+pub trait PriceSubscriber {
+    fn on_price_update(&mut self, price_per_region: PricePerRegion);
+}
+
+#[ext_contract(ext_self)]
+pub trait SubscriptionCallbacks {
+    fn on_subscriber_notified(&mut self, account_id: AccountId);
+}
+
+impl FilMarket {
+    // push a price entry to every subscriber
+    pub(crate) fn notify_subscribers(&self, price_per_region: &PricePerRegion) {
+        for (account_id, subscription) in self.subscribers.iter() {
+            ext_price_subscriber::on_price_update(price_per_region.clone(), account_id.clone(), 0, subscription.gas)
+                .then(ext_self::on_subscriber_notified(account_id, env::current_account_id(), 0, GAS_FOR_ON_SUBSCRIBER_NOTIFIED));
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // allow a contract to subscribe to price updates
+    pub fn approve_subscriber(&mut self, account_id: AccountId) {
+        self.assert_owner();
+
+        if self.approved_subscribers.insert(&account_id) {
+            self.emit_event("subscriber_approved", json!({ "account_id": account_id }));
+        }
+    }
+
+    // withdraw the approval of a contract, stopping its price updates
+    pub fn revoke_subscriber(&mut self, account_id: AccountId) {
+        self.assert_owner();
+
+        if self.approved_subscribers.remove(&account_id) {
+            self.emit_event("subscriber_revoked", json!({ "account_id": account_id }));
+        }
+        if self.subscribers.remove(&account_id).is_some() {
+            self.emit_event("subscriber_removed", json!({ "account_id": account_id }));
+        }
+    }
+
+    // whether a contract is approved to subscribe
+    pub fn is_subscriber_approved(&self, account_id: AccountId) -> bool {
+        self.approved_subscribers.contains(&account_id)
+    }
+
+    // subscribe the calling contract to price updates, sent with gas (at most MAX_SUBSCRIBER_GAS)
+    // the caller must be approved by the owner
+    // the attached deposit must cover the storage of the subscription, the rest is refunded
+    #[payable]
+    pub fn subscribe(&mut self, gas: Gas) {
+        self.assert_not_paused(Feature::Prices);
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.approved_subscribers.contains(&account_id),
+            format!("{}: account_id {} is not approved to subscribe", errors::ERR_NOT_APPROVED, account_id)
+        );
+        require!(gas.0 > 0 && gas <= MAX_SUBSCRIBER_GAS, format!("gas must be between 1 and {}", MAX_SUBSCRIBER_GAS.0));
+        require!(
+            self.subscribers.get(&account_id).is_some() || self.subscribers.len() < MAX_SUBSCRIBERS,
+            format!("at most {} subscribers are allowed", MAX_SUBSCRIBERS)
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.subscribers.insert(&account_id, &Subscription { gas, failures: 0 });
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("subscriber_added", json!({
+            "account_id": account_id,
+            "gas": gas,
+        }));
+    }
+
+    // stop the price updates to the calling contract
+    pub fn unsubscribe(&mut self) {
+        let account_id = env::predecessor_account_id();
+        if self.subscribers.remove(&account_id).is_some() {
+            self.emit_event("subscriber_removed", json!({ "account_id": account_id }));
+        }
+    }
+
+    // stop the price updates to a subscriber
+    pub fn remove_subscriber(&mut self, account_id: AccountId) {
+        self.assert_owner();
+
+        if self.subscribers.remove(&account_id).is_some() {
+            self.emit_event("subscriber_removed", json!({ "account_id": account_id }));
+        }
+    }
+
+    // count the failures of a subscriber in a row, removing it at MAX_SUBSCRIBER_FAILURES
+    #[private]
+    pub fn on_subscriber_notified(&mut self, account_id: AccountId) {
+        let mut subscription = match self.subscribers.get(&account_id) {
+            Some(subscription) => subscription,
+            None => return,
+        };
+
+        if is_promise_success() {
+            if subscription.failures == 0 {
+                return;
+            }
+            subscription.failures = 0;
+        } else {
+            subscription.failures += 1;
+            env::log_str(&format!("on_subscriber_notified(): update of {} failed {} times", account_id, subscription.failures));
+            if subscription.failures >= MAX_SUBSCRIBER_FAILURES {
+                self.subscribers.remove(&account_id);
+                self.emit_event("subscriber_removed", json!({ "account_id": account_id }));
+                return;
+            }
+        }
+        self.subscribers.insert(&account_id, &subscription);
+    }
+
+    // get the subscribers with their subscription, paginated
+    pub fn get_subscribers(&self, from_index: u64, limit: u64) -> Vec<(AccountId, Subscription)> {
+        self.subscribers
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, context, fil, set_context, NEAR};
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, testing_env_with_promise_results};
+    use near_sdk::PromiseResult;
+
+    #[test]
+    fn push_price_updates_to_subscribers() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.approve_subscriber(account("consumer_near"));
+
+        set_context("consumer_near", NEAR, 0);
+        contract.subscribe(Gas(10_000_000_000_000));
+        assert_eq!(1, contract.get_subscribers(0, 10).len());
+
+        set_context("carol_near", NEAR, 0);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 10, ..Default::default() });
+
+        let receipts = get_created_receipts();
+        assert_eq!(account("consumer_near"), receipts[0].receiver_id);
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall { method_name, gas, .. } => {
+                assert_eq!("on_price_update", method_name);
+                assert_eq!(Gas(10_000_000_000_000), *gas);
+            }
+            _ => panic!("expected a function call"),
+        }
+        assert_eq!(account("filmarket_near"), receipts[1].receiver_id);
+
+        set_context("consumer_near", NEAR, 0);
+        contract.unsubscribe();
+        assert!(contract.get_subscribers(0, 10).is_empty());
+    }
+
+    #[test]
+    fn older_entries_are_not_pushed() {
+        set_context("consumer_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.approve_subscriber(account("consumer_near"));
+        contract.subscribe(Gas(10_000_000_000_000));
+        contract.set_price_per_region(PricePerRegion { global: fil(0.1), timestamp: 20, ..Default::default() });
+
+        set_context("consumer_near", NEAR, 0);
+        contract.set_price_per_region(PricePerRegion { global: fil(0.2), timestamp: 10, ..Default::default() });
+        contract.backfill_price_per_region(PricePerRegion { global: fil(0.3), timestamp: 15, ..Default::default() });
+        // only the deposit refunds, no on_price_update call
+        let receipts = get_created_receipts();
+        assert!(receipts.iter().all(|receipt| matches!(receipt.actions[0], VmAction::Transfer { .. })));
+    }
+
+    #[test]
+    fn remove_failing_subscriber() {
+        set_context("consumer_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.approve_subscriber(account("consumer_near"));
+        contract.subscribe(Gas(10_000_000_000_000));
+
+        for failures in 1..MAX_SUBSCRIBER_FAILURES {
+            testing_env_with_promise_results(context("filmarket_near", NEAR, 0).build(), PromiseResult::Failed);
+            contract.on_subscriber_notified(account("consumer_near"));
+            assert_eq!(failures, contract.get_subscribers(0, 10)[0].1.failures);
+        }
+
+        // a successful update resets the count
+        testing_env_with_promise_results(context("filmarket_near", NEAR, 0).build(), PromiseResult::Successful(vec![]));
+        contract.on_subscriber_notified(account("consumer_near"));
+        assert_eq!(0, contract.get_subscribers(0, 10)[0].1.failures);
+
+        for _ in 0..MAX_SUBSCRIBER_FAILURES {
+            testing_env_with_promise_results(context("filmarket_near", NEAR, 0).build(), PromiseResult::Failed);
+            contract.on_subscriber_notified(account("consumer_near"));
+        }
+        assert!(contract.get_subscribers(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "gas must be between 1 and 15000000000000")]
+    fn subscribe_with_too_much_gas() {
+        set_context("consumer_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.approve_subscriber(account("consumer_near"));
+        contract.subscribe(Gas(MAX_SUBSCRIBER_GAS.0 + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_APPROVED: account_id consumer_near is not approved to subscribe")]
+    fn subscribe_without_approval() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+
+        set_context("consumer_near", NEAR, 0);
+        contract.subscribe(Gas(10_000_000_000_000));
+    }
+
+    #[test]
+    fn revoke_subscriber() {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.approve_subscriber(account("consumer_near"));
+
+        set_context("consumer_near", NEAR, 0);
+        contract.subscribe(Gas(10_000_000_000_000));
+
+        set_context("carol_near", NEAR, 0);
+        contract.revoke_subscriber(account("consumer_near"));
+        assert!(!contract.is_subscriber_approved(account("consumer_near")));
+        assert!(contract.get_subscribers(0, 10).is_empty());
+    }
+}
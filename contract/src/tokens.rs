@@ -0,0 +1,249 @@
+/*
+ * NEP-141 payment token: token transfers to the contract are credited to an internal balance ledger, which pays
+ * for deals and receives their payouts, and balances are withdrawn back to the token contract
+ */
+
+use crate::*;
+use near_sdk::{assert_one_yocto, ext_contract, is_promise_success, Gas, PromiseOrValue};
+
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_ON_TOKENS_WITHDRAWN: Gas = Gas(5_000_000_000_000);
+
+#[ext_contract(ext_ft)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[ext_contract(ext_self)]
+pub trait TokenCallbacks {
+    fn on_tokens_withdrawn(&mut self, account_id: AccountId, amount: U128);
+}
+
+impl FilMarket {
+    pub(crate) fn credit_tokens(&mut self, account_id: &AccountId, amount: Balance) {
+        let balance = self.token_balances.get(account_id).unwrap_or(0);
+        let balance = balance.checked_add(amount).expect("token balance overflows u128");
+        self.token_balances.insert(account_id, &balance);
+    }
+
+    pub(crate) fn debit_tokens(&mut self, account_id: &AccountId, amount: Balance) {
+        let balance = self.token_balances.get(account_id).unwrap_or(0);
+        require!(balance >= amount, format!("token balance of {} is lower than {}", account_id, amount));
+
+        if balance == amount {
+            self.token_balances.remove(account_id);
+        } else {
+            self.token_balances.insert(account_id, &(balance - amount));
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // set the NEP-141 token accepted for payments, only while no token balance or escrow is outstanding
+    pub fn set_payment_token(&mut self, token_id: Option<AccountId>) {
+        self.assert_owner();
+
+        require!(
+            self.token_balances.is_empty() && self.deal_token_escrow.is_empty() && self.fee_treasury_tokens == 0,
+            "cannot change the payment token while token balances are outstanding"
+        );
+
+        self.payment_token = token_id.clone();
+        self.emit_event("payment_token_set", json!({ "token_id": token_id }));
+    }
+
+    pub fn get_payment_token(&self) -> Option<AccountId> {
+        self.payment_token.clone()
+    }
+
+    // NEP-141 receiver: credit the transferred payment tokens to the sender's balance
+    // only an empty msg is supported, the whole amount is returned to the sender otherwise
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        self.assert_not_paused(Feature::Tokens);
+        require!(
+            self.payment_token.as_ref() == Some(&env::predecessor_account_id()),
+            "only the payment token can be transferred to this contract"
+        );
+        if !msg.is_empty() {
+            return PromiseOrValue::Value(amount);
+        }
+
+        self.credit_tokens(&sender_id, amount.0);
+        self.emit_event("tokens_deposited", json!({
+            "account_id": sender_id,
+            "amount": amount,
+        }));
+        PromiseOrValue::Value(U128(0))
+    }
+
+    // accept an offer paying with payment tokens, amount is moved from the caller's token balance into escrow
+    // the attached deposit must cover the storage of the deal, the rest is refunded
+    #[payable]
+    pub fn accept_offer_with_tokens(&mut self, offer_id: u64, amount: U128) -> u64 {
+        require!(amount.0 > 0, "amount must be greater than 0");
+
+        let initial_storage_usage = env::storage_usage();
+        let deal = self.internal_accept_offer(offer_id);
+        self.debit_tokens(&deal.client, amount.0);
+        self.deal_token_escrow.insert(&deal.id, &amount.0);
+        self.refund_deposit(initial_storage_usage);
+
+        self.emit_event("offer_accepted", json!({
+            "offer_id": offer_id,
+            "deal_id": deal.id,
+            "client": deal.client,
+            "provider_id": deal.provider_id,
+            "token_escrow": amount,
+        }));
+        deal.id
+    }
+
+    // withdraw payment tokens from the caller's balance, credited back if the transfer fails
+    #[payable]
+    pub fn withdraw_tokens(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        self.assert_not_paused(Feature::Tokens);
+        let token_id = self.payment_token.clone().expect("no payment token is set");
+        let account_id = env::predecessor_account_id();
+
+        self.debit_tokens(&account_id, amount.0);
+
+        ext_ft::ft_transfer(account_id.clone(), amount, None, token_id, 1, GAS_FOR_FT_TRANSFER)
+            .then(ext_self::on_tokens_withdrawn(account_id, amount, env::current_account_id(), 0, GAS_FOR_ON_TOKENS_WITHDRAWN))
+    }
+
+    #[private]
+    pub fn on_tokens_withdrawn(&mut self, account_id: AccountId, amount: U128) {
+        if is_promise_success() {
+            self.emit_event("tokens_withdrawn", json!({
+                "account_id": account_id,
+                "amount": amount,
+            }));
+        } else {
+            env::log_str(&format!("on_tokens_withdrawn(): transfer of {} to {} failed, credited back", amount.0, account_id));
+            self.credit_tokens(&account_id, amount.0);
+        }
+    }
+
+    // get the payment token balance of an account
+    pub fn get_token_balance(&self, account_id: AccountId) -> U128 {
+        U128(self.token_balances.get(&account_id).unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{account, context, fil, gib, set_context, NEAR};
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, testing_env_with_promise_results};
+    use near_sdk::PromiseResult;
+
+    // a contract paying in wfil_near with an open offer 0 by f01234 on a request by client_near
+    fn token_contract() -> FilMarket {
+        set_context("carol_near", NEAR, 0);
+        let mut contract = FilMarket::new();
+        contract.set_payment_token(Some(account("wfil_near")));
+        let sp_list = vec![StorageProvider { id: "f01234".to_string(), region: REGION_EUROPE, power: gib(10.0), price: fil(0.1), ..Default::default() }];
+        contract.update_storage_providers(sp_list, None);
+        set_context("miner_near", NEAR, 0);
+        contract.claim_provider("f01234".to_string());
+        set_context("carol_near", 0, 0);
+        contract.approve_provider_claim("f01234".to_string());
+
+        set_context("client_near", NEAR, 0);
+        contract.post_storage_request(gib(32.0), 518_400, REGION_EUROPE, fil(0.2));
+        set_context("miner_near", NEAR, 0);
+        contract.post_offer(0, "f01234".to_string(), fil(0.15));
+
+        set_context("wfil_near", 0, 0);
+        contract.ft_on_transfer(account("client_near"), fil(10.0), "".to_string());
+        contract
+    }
+
+    #[test]
+    fn pay_deal_with_tokens() {
+        let mut contract = token_contract();
+        assert_eq!(fil(10.0), contract.get_token_balance(account("client_near")));
+
+        set_context("client_near", NEAR, 0);
+        let deal_id = contract.accept_offer_with_tokens(0, fil(4.0));
+        assert_eq!(fil(6.0), contract.get_token_balance(account("client_near")));
+        assert_eq!(fil(4.0), contract.get_deal_token_escrow(deal_id));
+        assert_eq!(U128(0), contract.get_deal_escrow(deal_id));
+
+        set_context("miner_near", 0, 0);
+        contract.accept_deal(deal_id);
+        contract.activate_deal(deal_id);
+        contract.complete_deal(deal_id);
+        set_context("client_near", 0, 0);
+        contract.release_escrow(deal_id);
+
+        assert_eq!(fil(4.0), contract.get_token_balance(account("miner_near")));
+        assert_eq!(U128(0), contract.get_deal_token_escrow(deal_id));
+    }
+
+    #[test]
+    fn withdraw_tokens() {
+        let mut contract = token_contract();
+
+        set_context("client_near", 1, 0);
+        contract.withdraw_tokens(fil(3.0));
+        assert_eq!(fil(7.0), contract.get_token_balance(account("client_near")));
+
+        let receipts = get_created_receipts();
+        assert_eq!(account("wfil_near"), receipts[0].receiver_id);
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall { method_name, deposit, .. } => {
+                assert_eq!("ft_transfer", method_name);
+                assert_eq!(1, *deposit);
+            }
+            _ => panic!("expected a function call"),
+        }
+
+        // a failed transfer is credited back
+        testing_env_with_promise_results(context("filmarket_near", 0, 0).build(), PromiseResult::Failed);
+        contract.on_tokens_withdrawn(account("client_near"), fil(3.0));
+        assert_eq!(fil(10.0), contract.get_token_balance(account("client_near")));
+    }
+
+    #[test]
+    fn ft_on_transfer_with_msg() {
+        let mut contract = token_contract();
+
+        set_context("wfil_near", 0, 0);
+        match contract.ft_on_transfer(account("client_near"), fil(1.0), "pay".to_string()) {
+            PromiseOrValue::Value(unused) => assert_eq!(fil(1.0), unused),
+            _ => panic!("expected a value"),
+        }
+        assert_eq!(fil(10.0), contract.get_token_balance(account("client_near")));
+    }
+
+    #[test]
+    #[should_panic(expected = "only the payment token can be transferred to this contract")]
+    fn ft_on_transfer_from_other_token() {
+        let mut contract = token_contract();
+
+        set_context("other_token_near", 0, 0);
+        contract.ft_on_transfer(account("client_near"), fil(1.0), "".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "token balance of client_near is lower than")]
+    fn accept_offer_with_too_few_tokens() {
+        let mut contract = token_contract();
+
+        set_context("client_near", NEAR, 0);
+        contract.accept_offer_with_tokens(0, fil(11.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot change the payment token while token balances are outstanding")]
+    fn set_payment_token_with_balances() {
+        let mut contract = token_contract();
+
+        set_context("carol_near", 0, 0);
+        contract.set_payment_token(None);
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * Time-weighted average price over the stored price history, each entry weighted by how long it stayed the latest
+ */
+
+use crate::*;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Twap {
+    price: U128,  // attoFIL, rounded down, 0 without samples
+    samples: u64, // price entries within the window, including the one in effect at its start
+}
+
+impl FilMarket {
+    // the global price of an entry, or its price for a region, None if the entry has no price for the region
+    fn price_of(ppr: &PricePerRegion, region: Option<u8>) -> Option<u128> {
+        match region {
+            None => Some(ppr.global.0),
+            Some(region) => ppr.prices.get(&region).map(|price| price.0),
+        }
+    }
+}
+
+#[near_bindgen]
+impl FilMarket {
+    // get the time-weighted average of the global price, or of a region's price, over the last window_seconds
+    // an entry's price holds from its timestamp until the next entry with a price for the region, or now
+    pub fn get_twap(&self, region: Option<u8>, window_seconds: u64) -> Twap {
+        require!(window_seconds > 0, "window_seconds must be greater than 0");
+
+        let now = env::block_timestamp() / NANOSECONDS_PER_SECOND;
+        let start = now.saturating_sub(window_seconds);
+
+        // (start of the period, price) of each sample, the entry in effect at the window start is clipped to it
+        let mut samples: Vec<(u64, u128)> = Vec::new();
+        if let Some(ppr) = self.price_per_region.floor_key(&start).and_then(|timestamp| self.price_per_region.get(&timestamp)) {
+            if let Some(price) = Self::price_of(&ppr, region) {
+                samples.push((start, price));
+            }
+        }
+        if start < now {
+            for (timestamp, ppr) in self.price_per_region.range((Bound::Excluded(start), Bound::Included(now))) {
+                if let Some(price) = Self::price_of(&ppr, region) {
+                    samples.push((timestamp, price));
+                }
+            }
+        }
+
+        let mut weighted_total: u128 = 0;
+        let mut duration: u128 = 0;
+        for (i, (from, price)) in samples.iter().enumerate() {
+            let to = samples.get(i + 1).map_or(now, |(next, _)| *next);
+            let seconds = (to - from) as u128;
+            weighted_total = price.checked_mul(seconds)
+                .and_then(|weighted| weighted_total.checked_add(weighted))
+                .expect("weighted price overflows u128");
+            duration += seconds;
+        }
+
+        // a single entry set at the current second has no duration yet and counts as is
+        let price = weighted_total.checked_div(duration)
+            .unwrap_or_else(|| samples.last().map_or(0, |(_, price)| *price));
+
+        Twap { price: U128(price), samples: samples.len() as u64 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fil, set_context, NEAR};
+
+    #[test]
+    fn get_twap() {
+        set_context("carol_near", NEAR, 100);
+        let mut contract = FilMarket::new();
+        assert_eq!(Twap { price: U128(0), samples: 0 }, contract.get_twap(None, 60));
+
+        for (timestamp, global, europe) in [(10, 0.4, Some(1.0)), (50, 0.1, None), (70, 0.2, Some(2.0))] {
+            let prices = europe.map(|price| vec![(REGION_EUROPE, fil(price))]).unwrap_or_default().into_iter().collect();
+            contract.set_price_per_region(PricePerRegion { prices, global: fil(global), timestamp, ..Default::default() });
+        }
+
+        // window 40..100: 0.4 for 10s, 0.1 for 20s and 0.2 for 30s
+        assert_eq!(Twap { price: fil(0.2), samples: 3 }, contract.get_twap(None, 60));
+        // Europe skips the entry at 50: 1.0 for 30s and 2.0 for 30s
+        assert_eq!(Twap { price: fil(1.5), samples: 2 }, contract.get_twap(Some(REGION_EUROPE), 60));
+        // a window within the latest entry
+        assert_eq!(Twap { price: fil(0.2), samples: 1 }, contract.get_twap(None, 10));
+        assert_eq!(0, contract.get_twap(Some(REGION_ASIA), 60).samples);
+
+        // an entry set at the current second
+        set_context("carol_near", NEAR, 10);
+        assert_eq!(Twap { price: fil(0.4), samples: 1 }, contract.get_twap(None, 5));
+    }
+
+    #[test]
+    #[should_panic(expected = "window_seconds must be greater than 0")]
+    fn get_twap_empty_window() {
+        set_context("carol_near", NEAR, 100);
+        let contract = FilMarket::new();
+        contract.get_twap(None, 0);
+    }
+}